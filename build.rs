@@ -0,0 +1,42 @@
+use std::{env, process::Command};
+
+use chrono::{SecondsFormat, Utc};
+
+fn main() {
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let now = Utc::now();
+
+    println!("cargo:rustc-env=NOTIFY_ME_GIT_SHA={}", git_sha);
+    println!(
+        "cargo:rustc-env=NOTIFY_ME_BUILD_TIMESTAMP={}",
+        now.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    println!(
+        "cargo:rustc-env=NOTIFY_ME_BUILD_DATE={}",
+        now.format("%Y-%m-%d")
+    );
+    println!(
+        "cargo:rustc-env=NOTIFY_ME_HOST_TRIPLE={}",
+        env::var("HOST").unwrap_or_else(|_| "unknown".to_string())
+    );
+
+    let mut features: Vec<_> = env::vars()
+        .filter_map(|(name, _)| {
+            let feature = name.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=NOTIFY_ME_FEATURES={}", features.join(", "));
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,584 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use rusqlite::{Connection, DatabaseName, OpenFlags, Params};
+
+use tracing::info;
+
+use crate::db;
+
+const FILE_PREFIX: &str = "notify_me-";
+const FILE_EXTENSION: &str = "db";
+
+/// Columns every version of the `events` table has had.
+const REQUIRED_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "message",
+    "recurrence_pattern",
+    "date",
+    "deleted_at",
+];
+
+/// Copies the live database to `path` with SQLite's online backup API, which
+/// is safe while the scheduler holds the database open in WAL mode.
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        if let Err(err) = fs::create_dir_all(parent) {
+            return Err(format!("{}: {}", parent.display(), err));
+        }
+    }
+
+    match conn.backup(DatabaseName::Main, path, None) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
+
+/// Where the snapshots taken before destructive operations go, and how many
+/// of them to keep.
+#[derive(Debug, Clone)]
+pub struct PreOpBackup {
+    pub dir: PathBuf,
+    pub retention: u32,
+}
+
+impl PreOpBackup {
+    /// Snapshots the database before `operation`, which labels the file.
+    pub fn take(&self, conn: &Connection, operation: &str) -> Result<(), String> {
+        match snapshot(conn, &self.dir, Some(operation), Some(self.retention)) {
+            Ok(path) => {
+                info!(
+                    "Backed up the database to {} before {}",
+                    path.display(),
+                    operation
+                );
+                Ok(())
+            }
+            Err(err) => Err(format!(
+                "Backup before {} failed: {} (pass --no-backup to skip it)",
+                operation, err
+            )),
+        }
+    }
+}
+
+/// Writes a timestamped snapshot into `dir`, with `label` appended to the
+/// file name when given, and, when `retention` is set, deletes all but the
+/// newest `retention` snapshots there.
+pub fn snapshot(
+    conn: &Connection,
+    dir: &Path,
+    label: Option<&str>,
+    retention: Option<u32>,
+) -> Result<PathBuf, String> {
+    let mut name = format!("{}{}", FILE_PREFIX, Local::now().format("%Y%m%d-%H%M%S"));
+    if let Some(label) = label {
+        name.push('-');
+        name.push_str(label);
+    }
+
+    let path = dir.join(format!("{}.{}", name, FILE_EXTENSION));
+
+    backup_to(conn, &path)?;
+
+    if let Some(retention) = retention {
+        prune(dir, retention as usize)?;
+    }
+
+    Ok(path)
+}
+
+fn prune(dir: &Path, keep: usize) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return Err(format!("{}: {}", dir.display(), err)),
+    };
+
+    // Timestamps in the file names sort chronologically.
+    let mut snapshots = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == FILE_EXTENSION)
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(FILE_PREFIX))
+        })
+        .collect::<Vec<PathBuf>>();
+    snapshots.sort();
+
+    let excess = snapshots.len().saturating_sub(keep);
+    for path in &snapshots[..excess] {
+        if let Err(err) = fs::remove_file(path) {
+            return Err(format!("{}: {}", path.display(), err));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `path` is a notify_me database this build can open: an SQLite
+/// file with an `events` table and a schema that isn't newer than ours.
+pub fn validate(path: &Path) -> Result<(), String> {
+    let conn = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(conn) => conn,
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+
+    let version: u32 = match conn.pragma_query_value(None, "user_version", |row| row.get(0)) {
+        Ok(version) => version,
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+
+    if version > db::schema_version() {
+        return Err(format!(
+            "{} has schema version {}, newer than the supported {}",
+            path.display(),
+            version,
+            db::schema_version()
+        ));
+    }
+
+    let columns = table_columns(&conn, "main", "events")?;
+    if columns.is_empty() {
+        return Err(format!("{} has no events table", path.display()));
+    }
+
+    for column in REQUIRED_COLUMNS {
+        if !columns.iter().any(|existing| existing == column) {
+            return Err(format!(
+                "{}: events table is missing the '{}' column",
+                path.display(),
+                column
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the database at `db_path` with `backup_path`, keeping the
+/// current database as `<db_path>.pre-restore`. The caller must have closed
+/// every connection to `db_path`. Returns where the old database went.
+pub fn restore(db_path: &Path, backup_path: &Path) -> Result<PathBuf, String> {
+    validate(backup_path)?;
+
+    let aside = with_suffix(db_path, ".pre-restore");
+    if db_path.exists() {
+        if let Err(err) = fs::rename(db_path, &aside) {
+            return Err(format!("{}: {}", db_path.display(), err));
+        }
+    }
+
+    // A leftover WAL would be replayed on top of the restored file.
+    for suffix in ["-wal", "-shm"] {
+        let path = with_suffix(db_path, suffix);
+        if path.exists() {
+            if let Err(err) = fs::remove_file(&path) {
+                return Err(format!("{}: {}", path.display(), err));
+            }
+        }
+    }
+
+    match fs::copy(backup_path, db_path) {
+        Ok(_) => Ok(aside),
+        Err(err) => Err(format!("{}: {}", backup_path.display(), err)),
+    }
+}
+
+/// Copies events from `backup_path` that the live database doesn't have.
+/// An event counts as already present when its uuid, or both its id and
+/// date, match; a backup event whose id was reused locally is inserted under
+/// a new id.
+/// Returns how many events were added.
+pub fn merge(conn: &Connection, backup_path: &Path) -> Result<usize, String> {
+    with_attached_copy(conn, backup_path, "backup", copy_missing_events)
+}
+
+/// Which side wins when both databases changed the same event.
+#[derive(Debug, Clone, Copy)]
+pub enum Prefer {
+    Local,
+    Other,
+    Newest,
+}
+
+impl From<&str> for Prefer {
+    fn from(value: &str) -> Self {
+        match value {
+            "local" => Prefer::Local,
+            "other" => Prefer::Other,
+            "newest" => Prefer::Newest,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MergeStats {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// One line per event both sides edited, naming the side that was kept.
+    pub conflicts: Vec<String>,
+}
+
+/// Combines another notify_me database into this one, matching events by
+/// uuid. Events only the other side has are inserted. When both sides
+/// edited an event, `prefer` picks the winner (`Newest` compares
+/// `updated_at`). When the only difference is how far the schedulers
+/// advanced a recurring event, the later occurrence is kept whatever
+/// `prefer` says, so an event never fires twice.
+pub fn merge_database(
+    conn: &Connection,
+    other_path: &Path,
+    prefer: Prefer,
+) -> Result<MergeStats, String> {
+    with_attached_copy(conn, other_path, "other", |conn| {
+        merge_by_uuid(conn, prefer)
+    })
+}
+
+/// Migrates a scratch copy of `path` so both sides have the same columns,
+/// attaches it to `conn` as `alias` for `f`, and cleans up afterwards.
+fn with_attached_copy<T>(
+    conn: &Connection,
+    path: &Path,
+    alias: &str,
+    f: impl FnOnce(&Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    validate(path)?;
+
+    let scratch = std::env::temp_dir().join(format!("notify_me-merge-{}.db", std::process::id()));
+    if let Err(err) = fs::copy(path, &scratch) {
+        return Err(format!("{}: {}", path.display(), err));
+    }
+
+    let result = match db::open(&scratch) {
+        Ok(scratch_conn) => {
+            drop(scratch_conn);
+            attached(conn, &scratch, alias, f)
+        }
+        Err(err) => Err(err.to_string()),
+    };
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(with_suffix(&scratch, suffix));
+    }
+
+    result
+}
+
+fn attached<T>(
+    conn: &Connection,
+    path: &Path,
+    alias: &str,
+    f: impl FnOnce(&Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    if let Err(err) = conn.execute(
+        &format!("ATTACH DATABASE ?1 AS {}", alias),
+        [path.to_string_lossy()],
+    ) {
+        return Err(err.to_string());
+    }
+
+    let result = f(conn);
+
+    if let Err(err) = conn.execute(&format!("DETACH DATABASE {}", alias), ()) {
+        return Err(err.to_string());
+    }
+
+    result
+}
+
+fn merge_by_uuid(conn: &Connection, prefer: Prefer) -> Result<MergeStats, String> {
+    let columns = table_columns(conn, "main", "events")?
+        .into_iter()
+        .filter(|column| column != "id")
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let tx = match conn.unchecked_transaction() {
+        Ok(tx) => tx,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    if let Err(err) = tx.execute(
+        "CREATE TEMP TABLE merge_existing AS SELECT id FROM main.events",
+        (),
+    ) {
+        return Err(err.to_string());
+    }
+
+    let inserted = match tx.execute(
+        &format!(
+            "INSERT INTO main.events ({columns}) \
+                 SELECT {columns} FROM other.events \
+                 WHERE uuid NOT IN (SELECT uuid FROM main.events)"
+        ),
+        (),
+    ) {
+        Ok(count) => count,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    copy_tags(
+        &tx,
+        "other",
+        "m.id NOT IN (SELECT id FROM temp.merge_existing)",
+        (),
+    )?;
+
+    if let Err(err) = tx.execute("DROP TABLE temp.merge_existing", ()) {
+        return Err(err.to_string());
+    }
+
+    let mut stats = MergeStats {
+        inserted,
+        ..Default::default()
+    };
+
+    let edited = format!(
+        "m.name IS NOT o.name OR m.message IS NOT o.message \
+         OR m.recurrence_pattern IS NOT o.recurrence_pattern \
+         OR m.deleted_at IS NOT o.deleted_at OR m.calendar IS NOT o.calendar \
+         OR m.priority IS NOT o.priority OR m.location IS NOT o.location \
+         OR m.url IS NOT o.url OR m.color IS NOT o.color \
+         OR m.paused IS NOT o.paused OR m.timezone IS NOT o.timezone \
+         OR {} IS NOT {}",
+        tag_list_sql("main", "m"),
+        tag_list_sql("other", "o")
+    );
+
+    let divergent = {
+        let mut stmt = match tx.prepare(&format!(
+            "SELECT m.id, o.id, m.name, m.uuid, m.updated_at < o.updated_at, {edited}, \
+                 o.date > m.date \
+                 OR coalesce(o.last_notified_at, '') > coalesce(m.last_notified_at, '') \
+             FROM main.events m JOIN other.events o ON o.uuid = m.uuid \
+             WHERE m.date IS NOT o.date OR m.last_notified_at IS NOT o.last_notified_at \
+                 OR {edited}"
+        )) {
+            Ok(stmt) => stmt,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let rows = match stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, bool>(4)?,
+                row.get::<_, bool>(5)?,
+                row.get::<_, bool>(6)?,
+            ))
+        }) {
+            Ok(rows) => rows.collect::<rusqlite::Result<Vec<_>>>(),
+            Err(err) => return Err(err.to_string()),
+        };
+
+        match rows {
+            Ok(rows) => rows,
+            Err(err) => return Err(err.to_string()),
+        }
+    };
+
+    for (local_id, other_id, name, uuid, other_is_newer, edited, other_is_ahead) in divergent {
+        let sql = if edited {
+            let take_other = match prefer {
+                Prefer::Local => false,
+                Prefer::Other => true,
+                Prefer::Newest => other_is_newer,
+            };
+            stats.conflicts.push(format!(
+                "{} ({}): kept {}",
+                name,
+                uuid,
+                if take_other { "other" } else { "local" }
+            ));
+
+            if !take_other {
+                continue;
+            }
+
+            "UPDATE main.events SET (name, message, recurrence_pattern, date, deleted_at, \
+                 last_notified_at, updated_at, calendar, priority, location, url, color, paused, \
+                 timezone) = \
+                 (SELECT name, message, recurrence_pattern, date, deleted_at, \
+                 last_notified_at, updated_at, calendar, priority, location, url, color, \
+                 paused, timezone FROM other.events WHERE id = ?2) \
+             WHERE id = ?1"
+        } else if other_is_ahead {
+            "UPDATE main.events SET \
+                 date = (SELECT max(main.events.date, o.date) FROM other.events o WHERE o.id = ?2), \
+                 last_notified_at = (SELECT nullif(max(coalesce(main.events.last_notified_at, ''), \
+                     coalesce(o.last_notified_at, '')), '') FROM other.events o WHERE o.id = ?2) \
+             WHERE id = ?1"
+        } else {
+            continue;
+        };
+
+        if let Err(err) = tx.execute(sql, (local_id, other_id)) {
+            return Err(err.to_string());
+        }
+
+        if edited {
+            if let Err(err) = tx.execute(
+                "DELETE FROM main.event_tags WHERE event_id = ?1",
+                [local_id],
+            ) {
+                return Err(err.to_string());
+            }
+            copy_tags(&tx, "other", "m.id = ?1", [local_id])?;
+        }
+
+        stats.updated += 1;
+    }
+
+    let total: usize = match tx.query_row("SELECT count(*) FROM other.events", [], |row| row.get(0))
+    {
+        Ok(total) => total,
+        Err(err) => return Err(err.to_string()),
+    };
+    stats.skipped = total - stats.inserted - stats.updated;
+
+    match tx.commit() {
+        Ok(()) => Ok(stats),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn copy_missing_events(conn: &Connection) -> Result<usize, String> {
+    let columns = table_columns(conn, "main", "events")?
+        .into_iter()
+        .filter(|column| column != "id")
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let tx = match conn.unchecked_transaction() {
+        Ok(tx) => tx,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    if let Err(err) = tx.execute(
+        "CREATE TEMP TABLE merge_existing AS SELECT id FROM main.events",
+        (),
+    ) {
+        return Err(err.to_string());
+    }
+
+    let with_ids = match tx.execute(
+        &format!(
+            "INSERT INTO main.events (id, {columns}) \
+                 SELECT id, {columns} FROM backup.events \
+                 WHERE id NOT IN (SELECT id FROM main.events) \
+                 AND uuid NOT IN (SELECT uuid FROM main.events)"
+        ),
+        (),
+    ) {
+        Ok(count) => count,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let renumbered = match tx.execute(
+        &format!(
+            "INSERT INTO main.events ({columns}) \
+                 SELECT {columns} FROM backup.events b \
+                 WHERE EXISTS (SELECT 1 FROM main.events e WHERE e.id = b.id AND e.date != b.date) \
+                 AND uuid NOT IN (SELECT uuid FROM main.events)"
+        ),
+        (),
+    ) {
+        Ok(count) => count,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    copy_tags(
+        &tx,
+        "backup",
+        "m.id NOT IN (SELECT id FROM temp.merge_existing)",
+        (),
+    )?;
+
+    if let Err(err) = tx.execute("DROP TABLE temp.merge_existing", ()) {
+        return Err(err.to_string());
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(with_ids + renumbered),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// The tags of event `alias` in `schema`, comma-separated in name order.
+fn tag_list_sql(schema: &str, alias: &str) -> String {
+    format!(
+        "(SELECT group_concat(t.name, ',' ORDER BY t.name) FROM {schema}.event_tags et \
+         JOIN {schema}.tags t ON t.id = et.tag_id WHERE et.event_id = {alias}.id)"
+    )
+}
+
+/// Gives the local events picked by `filter` (over `m`) the tags their
+/// uuid twin has in the attached `schema`, creating tags as needed.
+fn copy_tags<P: Params + Copy>(
+    conn: &Connection,
+    schema: &str,
+    filter: &str,
+    params: P,
+) -> Result<(), String> {
+    let joins = format!(
+        "FROM main.events m JOIN {schema}.events o ON o.uuid = m.uuid \
+         JOIN {schema}.event_tags oet ON oet.event_id = o.id \
+         JOIN {schema}.tags ot ON ot.id = oet.tag_id"
+    );
+
+    if let Err(err) = conn.execute(
+        &format!(
+            "INSERT INTO main.tags (name) SELECT DISTINCT ot.name {joins} WHERE {filter} \
+             ON CONFLICT (name) DO NOTHING"
+        ),
+        params,
+    ) {
+        return Err(err.to_string());
+    }
+
+    match conn.execute(
+        &format!(
+            "INSERT OR IGNORE INTO main.event_tags (event_id, tag_id) \
+             SELECT m.id, mt.id {joins} JOIN main.tags mt ON mt.name = ot.name WHERE {filter}"
+        ),
+        params,
+    ) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = match conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table)) {
+        Ok(stmt) => stmt,
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let columns = match stmt.query_map([], |row| row.get::<_, String>(1)) {
+        Ok(columns) => columns.collect::<rusqlite::Result<Vec<String>>>(),
+        Err(err) => return Err(err.to_string()),
+    };
+
+    columns.map_err(|err| err.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
@@ -1,173 +1,3149 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
-use dialoguer::{theme::ColorfulTheme, Input, Select};
-use rusqlite::Connection;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    thread,
+};
 
-use crate::event::{Event, EventList, RecurrencePattern};
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, LocalResult, Months, NaiveDate, NaiveDateTime,
+    SecondsFormat, TimeZone, Timelike, Utc, Weekday,
+};
+use clap::{builder::PossibleValue, ValueEnum};
+use console::{style, Term};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use rusqlite::{Connection, OptionalExtension, Params};
+use tracing::warn;
 
-enum Operation {
-    Today,
-    Create,
-    Update,
-    Delete,
+use crate::{
+    backup::PreOpBackup,
+    clock::Clock,
+    csv,
+    db::{self, to_sql_date, DEFAULT_CALENDAR},
+    diff::{self, EventDiff},
+    error::NotifyMeError,
+    event::{
+        advance_date_in, nth_name, occurrences, parse_optional, parse_tags, partition_rows,
+        validate_event_message, validate_event_name, validate_event_url, weekday_name, Event,
+        EventField, EventList, EventSort, EventWithStatus, Priority, RecurrencePattern, SkippedRow,
+        EVENT_COLUMNS, LAST_WEEKDAY, MAX_NAME_LENGTH, MAX_WEEKS, PRIORITY_ORDER, WEEKDAYS,
+    },
+    ics,
+    json::{self, Value},
+    output, remind,
+    repository::{local_day_bounds, unbounded_range, EventFilter, EventRepository, NewEvent},
+    scheduler,
+    zone::Zone,
+};
+
+/// An entry of the interactive menu.
+enum MenuItem {
+    /// Runs an operation, then shows the same menu again.
+    Action(&'static str, fn(&Client<'_>) -> Result<(), NotifyMeError>),
+    /// Opens a menu of its own, left through its "Back" entry.
+    Submenu(&'static str, &'static [MenuItem]),
+}
+
+impl MenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuItem::Action(label, _) | MenuItem::Submenu(label, _) => label,
+        }
+    }
+}
+
+const MENU: &[MenuItem] = &[
+    MenuItem::Submenu(
+        "Events",
+        &[
+            MenuItem::Action("Today", |client| {
+                println!("{}", client.fetch_current_day_events()?);
+                Ok(())
+            }),
+            MenuItem::Action("Upcoming", |client| {
+                println!("{}", client.fetch_events_grouped_by_proximity()?);
+                Ok(())
+            }),
+            MenuItem::Action("Month", |client| client.month_view_menu()),
+            MenuItem::Action("Overdue", |client| client.overdue_menu()),
+            MenuItem::Action("Create", |client| client.create_event()),
+            MenuItem::Action("Edit", |client| client.edit_menu()),
+            MenuItem::Action("Delete", |client| client.delete_event()),
+            MenuItem::Action("Mark done", |client| client.mark_done_menu()),
+            MenuItem::Action("Pause/resume", |client| client.pause_menu()),
+            MenuItem::Action("Bulk reschedule", |client| client.bulk_reschedule_menu()),
+        ],
+    ),
+    MenuItem::Submenu(
+        "Manage",
+        &[
+            MenuItem::Action("Trash", |client| client.trash_menu()),
+            MenuItem::Action("Purge trash", |client| {
+                let removed = client.purge(PurgeTarget::AllDeleted, false)?;
+                println!("Purged {} events", removed);
+                Ok(())
+            }),
+            MenuItem::Action("Export to .ics", |client| client.export_ics_menu()),
+        ],
+    ),
+    MenuItem::Submenu(
+        "Settings",
+        &[
+            MenuItem::Action("Test notification", |_| {
+                for check in scheduler::check_notifications() {
+                    match check.outcome {
+                        Ok(found) => println!("{}: {}", check.label, found),
+                        Err(err) => println!("{}: failed: {}", check.label, err),
+                    }
+                }
+                Ok(())
+            }),
+            MenuItem::Action("Config path", |client| {
+                match &client.config_path {
+                    Some(path) => println!("{}", path.display()),
+                    None => println!("No config file, using the defaults"),
+                }
+                Ok(())
+            }),
+        ],
+    ),
+];
+
+const DATE_FORMAT: &str = "%d/%m/%Y %H:%M";
+
+/// How many of the most recently deleted events the trash view shows.
+pub const TRASH_LIMIT: u32 = 50;
+
+const WATCH_BAR_WIDTH: usize = 30;
+
+/// How far ahead the banner and watch view count events as due soon.
+pub const DUE_SOON_MINUTES: u32 = 30;
+
+/// How much of a new event's message is compared against existing ones when
+/// looking for duplicates.
+const DUPLICATE_PREFIX_CHARS: usize = 20;
+
+pub enum PurgeTarget {
+    Event(i32),
+    AllDeleted,
+    OlderThan(Duration),
+}
+
+/// What a JSON import does with a record matching an existing event.
+#[derive(Debug, Clone, Copy)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    Duplicate,
+}
+
+impl ValueEnum for OnConflict {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            OnConflict::Skip,
+            OnConflict::Overwrite,
+            OnConflict::Duplicate,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            OnConflict::Skip => "skip",
+            OnConflict::Overwrite => "overwrite",
+            OnConflict::Duplicate => "duplicate",
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct Calendar {
+    pub name: String,
+    pub events: usize,
+    pub muted: bool,
+}
+
+/// Upcoming events by how soon they are: today, tomorrow, and the five days
+/// after that.
+pub struct ProximityGroupedEvents {
+    pub today: EventList,
+    pub tomorrow: EventList,
+    pub this_week: EventList,
+}
+
+impl fmt::Display for ProximityGroupedEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections = [
+            ("Today", &self.today),
+            ("Tomorrow", &self.tomorrow),
+            ("Later this week", &self.this_week),
+        ];
+
+        let mut empty = true;
+        for (title, events) in sections {
+            if events.0.is_empty() {
+                continue;
+            }
+
+            if !empty {
+                write!(f, "\n\n")?;
+            }
+            empty = false;
+
+            let header = format!("{} ({})", title, events.0.len());
+            write!(
+                f,
+                "{}\n{}\n{}",
+                style(&header).bold(),
+                "\u{2500}".repeat(header.chars().count()),
+                events
+            )?;
+        }
+
+        if empty {
+            write!(f, "Nothing scheduled this week")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One-time events whose time passed unnoticed, each followed by how long
+/// ago that was.
+pub struct OverdueEvents {
+    pub events: EventList,
+    pub now: DateTime<Local>,
+}
+
+impl fmt::Display for OverdueEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.events.0.is_empty() {
+            return write!(f, "No overdue events");
+        }
+
+        for (i, event) in self.events.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(
+                f,
+                "{}\n{}",
+                event,
+                style(overdue_age(self.now - event.date)).red()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How many later dates `create_event` shows for a recurring event.
+const PREVIEW_OCCURRENCES: u32 = 5;
+
+/// Dates as "2024-03-15, 2024-03-22", or with times where they differ
+/// from the first, as a DST change can make them.
+pub fn format_occurrences(dates: &[DateTime<Local>]) -> String {
+    let same_time = dates
+        .windows(2)
+        .all(|pair| pair[0].time() == pair[1].time());
+    let format = if same_time {
+        "%Y-%m-%d"
+    } else {
+        "%Y-%m-%d %H:%M"
+    };
+
+    dates
+        .iter()
+        .map(|date| date.format(format).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// How many days ahead `stats` counts events for.
+const STATS_DAYS: u64 = 7;
+
+/// Counts describing the database, as `stats` prints them.
+#[derive(Debug)]
+pub struct Stats {
+    /// Events outside the trash.
+    pub total: usize,
+    pub deleted: usize,
+    /// Events outside the trash by recurrence pattern as stored, sorted.
+    pub by_recurrence: Vec<(String, usize)>,
+    /// Events on each of the next days, starting today.
+    pub next_days: Vec<(NaiveDate, usize)>,
+    /// Events outside the trash on each weekday, from Monday.
+    pub by_weekday: [usize; 7],
+}
+
+impl Stats {
+    /// The weekday with the most events, the earliest in the week on a
+    /// tie, or `None` without events.
+    pub fn busiest_weekday(&self) -> Option<(Weekday, usize)> {
+        let (index, count) = self
+            .by_weekday
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, count)| **count)?;
+
+        (*count > 0).then(|| (Weekday::try_from(index as u8).unwrap(), *count))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let counts = |counts: Vec<(String, usize)>| {
+            Value::Object(
+                counts
+                    .into_iter()
+                    .map(|(key, count)| (key, Value::Number(count as i64)))
+                    .collect(),
+            )
+        };
+
+        Value::Object(vec![
+            ("total".to_string(), Value::Number(self.total as i64)),
+            ("deleted".to_string(), Value::Number(self.deleted as i64)),
+            (
+                "by_recurrence".to_string(),
+                counts(self.by_recurrence.clone()),
+            ),
+            (
+                "next_days".to_string(),
+                Value::Array(
+                    self.next_days
+                        .iter()
+                        .map(|(day, count)| {
+                            Value::Object(vec![
+                                ("date".to_string(), day.to_string().into()),
+                                ("events".to_string(), Value::Number(*count as i64)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "by_weekday".to_string(),
+                counts(
+                    WEEKDAYS
+                        .iter()
+                        .zip(self.by_weekday)
+                        .map(|(weekday, count)| (weekday_name(*weekday).to_lowercase(), count))
+                        .collect(),
+                ),
+            ),
+            (
+                "busiest_weekday".to_string(),
+                self.busiest_weekday()
+                    .map(|(weekday, _)| weekday_name(weekday).to_lowercase())
+                    .into(),
+            ),
+        ])
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Events:    {}", self.total)?;
+        writeln!(f, "In trash:  {}", self.deleted)?;
+
+        if !self.by_recurrence.is_empty() {
+            writeln!(f, "\nBy recurrence")?;
+            let width = self
+                .by_recurrence
+                .iter()
+                .map(|(pattern, _)| pattern.len())
+                .max()
+                .unwrap_or(0);
+            for (pattern, count) in &self.by_recurrence {
+                writeln!(f, "  {:<width$}  {}", pattern, count, width = width)?;
+            }
+        }
+
+        writeln!(f, "\nNext {} days", self.next_days.len())?;
+        let most = self
+            .next_days
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(0);
+        for (day, count) in &self.next_days {
+            writeln!(
+                f,
+                "  {}  {:<width$}  {}",
+                day.format("%a %d/%m"),
+                "#".repeat(*count),
+                count,
+                width = most
+            )?;
+        }
+
+        match self.busiest_weekday() {
+            Some((weekday, count)) => write!(
+                f,
+                "\nBusiest weekday: {} ({} events)",
+                weekday_name(weekday),
+                count
+            ),
+            None => write!(f, "\nBusiest weekday: none yet"),
+        }
+    }
+}
+
+/// Width of a day in the month calendar; event names are cut to fit.
+const MONTH_CELL_WIDTH: usize = 8;
+
+/// A month's events laid out as a calendar, a week to a row from Monday,
+/// with the names of each day's events under its number.
+pub struct MonthCalendar {
+    pub year: i32,
+    pub month: u32,
+    pub events: EventList,
+}
+
+impl fmt::Display for MonthCalendar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let first = NaiveDate::from_ymd_opt(self.year, self.month, 1).ok_or(fmt::Error)?;
+        let mut names: HashMap<NaiveDate, Vec<String>> = HashMap::new();
+        for event in &self.events.0 {
+            names
+                .entry(event.date.date_naive())
+                .or_default()
+                .push(event.name.chars().take(MONTH_CELL_WIDTH).collect());
+        }
+
+        let row = |cells: &mut dyn Iterator<Item = &str>| {
+            cells
+                .map(|cell| format!("{:<1$}", cell, MONTH_CELL_WIDTH))
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim_end()
+                .to_string()
+        };
+
+        write!(
+            f,
+            "{}\n{}",
+            first.format("%B %Y"),
+            row(&mut ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].into_iter())
+        )?;
+
+        let mut monday = first - Days::new(first.weekday().num_days_from_monday().into());
+        while monday.month() == self.month || monday <= first {
+            let week = (0..7)
+                .map(|i| monday + Days::new(i))
+                .map(|day| {
+                    if day.month() != self.month {
+                        return Vec::new();
+                    }
+                    let mut cell = vec![day.day().to_string()];
+                    cell.extend(names.get(&day).into_iter().flatten().cloned());
+                    cell
+                })
+                .collect::<Vec<_>>();
+
+            writeln!(f)?;
+            for line in 0..week.iter().map(Vec::len).max().unwrap_or(0) {
+                let mut cells = week
+                    .iter()
+                    .map(|cell| cell.get(line).map_or("", String::as_str));
+                write!(f, "\n{}", row(&mut cells))?;
+            }
+
+            monday = monday + Days::new(7);
+        }
+
+        Ok(())
+    }
+}
+
+/// What search snippets mark matched terms with, as FTS5's `snippet()`
+/// is asked to.
+const SNIPPET_MARKS: (char, char) = ('\u{2}', '\u{3}');
+
+/// An event `search_with_snippets` found, with the text it matched on.
+#[derive(Debug)]
+pub struct SearchHit {
+    pub event: Event,
+    /// The matched terms sit between `SNIPPET_MARKS`.
+    pub snippet: String,
+}
+
+/// Search results, each as its name and date over the matching text with
+/// the matched terms highlighted, or bracketed without colors.
+#[derive(Debug)]
+pub struct SearchHits(pub Vec<SearchHit>);
+
+impl fmt::Display for SearchHits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let highlight = |term: &str| {
+            if console::colors_enabled() {
+                style(term).bold().yellow().to_string()
+            } else {
+                format!("[{}]", term)
+            }
+        };
+
+        for (i, hit) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+
+            let mut snippet = String::new();
+            for (j, part) in hit.snippet.split(SNIPPET_MARKS.0).enumerate() {
+                match part.split_once(SNIPPET_MARKS.1) {
+                    Some((term, rest)) if j > 0 => {
+                        snippet.push_str(&highlight(term));
+                        snippet.push_str(rest);
+                    }
+                    _ => snippet.push_str(part),
+                }
+            }
+
+            write!(
+                f,
+                "{} ({})\n  {}",
+                style(&hit.event.name).bold(),
+                hit.event.date.format("%Y-%m-%d %H:%M"),
+                snippet
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `text` as a `LIKE` pattern matching it literally, with `\\` as the
+/// `ESCAPE` character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// The words of a search query as `LIKE` matches them: FTS5 operators,
+/// quotes and prefix stars left out.
+fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "AND" | "OR" | "NOT"))
+        .map(|word| word.trim_matches(|c| c == '"' || c == '*').to_string())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// `text` with each occurrence of `terms` between `SNIPPET_MARKS`, ignoring
+/// ASCII case as `LIKE` does.
+fn mark_terms(text: &str, terms: &[String]) -> String {
+    let mut marked = String::new();
+    let mut rest = text;
+
+    'text: while let Some(c) = rest.chars().next() {
+        for term in terms {
+            let matched = rest
+                .get(..term.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(term));
+            if let Some(matched) = matched {
+                marked.push(SNIPPET_MARKS.0);
+                marked.push_str(matched);
+                marked.push(SNIPPET_MARKS.1);
+                rest = &rest[term.len()..];
+                continue 'text;
+            }
+        }
+
+        marked.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    marked
+}
+
+/// Narrowest day column the week calendar lays out side by side; below it
+/// the days are listed one after another.
+const WEEK_MIN_COLUMN: usize = 8;
+
+/// Hours the week calendar shows even when no event falls in them.
+const WEEK_HOURS: (u32, u32) = (8, 18);
+
+/// The seven days from `start` as a calendar `width` columns wide: a row per
+/// hour with each event in its day's column, or a list per day when the
+/// columns would be too narrow to read. `events` should hold one entry per
+/// occurrence, as `Client::fetch_events_in_week` returns them.
+pub struct WeekCalendar {
+    pub start: NaiveDate,
+    pub events: EventList,
+    pub width: usize,
 }
 
-impl From<&str> for Operation {
-    fn from(val: &str) -> Self {
-        match val.trim().to_lowercase().as_str() {
-            "today" => Operation::Today,
-            "create" => Operation::Create,
-            "update" => Operation::Update,
-            "delete" => Operation::Delete,
-            _ => unreachable!(),
+impl WeekCalendar {
+    fn days(&self) -> Vec<(NaiveDate, Vec<&Event>)> {
+        (0..7)
+            .map(|offset| {
+                let day = self.start + Days::new(offset);
+                let events = self
+                    .events
+                    .0
+                    .iter()
+                    .filter(|event| event.date.date_naive() == day)
+                    .collect();
+                (day, events)
+            })
+            .collect()
+    }
+
+    fn fmt_list(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (day, events) in self.days() {
+            write!(f, "\n{}", day.format("%a %d/%m"))?;
+            if events.is_empty() {
+                write!(f, "\n  -")?;
+            }
+            for event in events {
+                write!(f, "\n  {} {}", event.date.format("%H:%M"), event.name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fmt_grid(&self, f: &mut fmt::Formatter<'_>, column: usize) -> fmt::Result {
+        let days = self.days();
+        let hours = days
+            .iter()
+            .flat_map(|(_, events)| events.iter().map(|event| event.date.hour()));
+        let first = hours
+            .clone()
+            .min()
+            .map_or(WEEK_HOURS.0, |hour| hour.min(WEEK_HOURS.0));
+        let last = hours
+            .max()
+            .map_or(WEEK_HOURS.1, |hour| hour.max(WEEK_HOURS.1));
+
+        let row = |label: &str, cells: Vec<String>| {
+            let cells = cells
+                .iter()
+                .map(|cell| format!("{:<1$}", cell, column))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("\n{:<5} {}", label, cells).trim_end().to_string()
+        };
+
+        write!(
+            f,
+            "{}",
+            row(
+                "",
+                days.iter()
+                    .map(|(day, _)| day.format("%a %d").to_string())
+                    .collect()
+            )
+        )?;
+
+        for hour in first..=last {
+            let cells: Vec<Vec<String>> = days
+                .iter()
+                .map(|(_, events)| {
+                    events
+                        .iter()
+                        .filter(|event| event.date.hour() == hour)
+                        .map(|event| {
+                            format!(":{} {}", event.date.format("%M"), event.name)
+                                .chars()
+                                .take(column)
+                                .collect()
+                        })
+                        .collect()
+                })
+                .collect();
+
+            for line in 0..cells.iter().map(Vec::len).max().unwrap_or(0).max(1) {
+                let label = if line == 0 {
+                    format!("{:02}:00", hour)
+                } else {
+                    String::new()
+                };
+                let line = cells
+                    .iter()
+                    .map(|cell| cell.get(line).cloned().unwrap_or_default())
+                    .collect();
+                write!(f, "{}", row(&label, line))?;
+            }
         }
+
+        Ok(())
     }
 }
 
-impl From<Operation> for &str {
-    fn from(val: Operation) -> Self {
-        match val {
-            Operation::Today => "today",
-            Operation::Create => "create",
-            Operation::Update => "update",
-            Operation::Delete => "delete",
+impl fmt::Display for WeekCalendar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let end = self.start + Days::new(6);
+        write!(
+            f,
+            "Week {}, {} to {}",
+            self.start.iso_week().week(),
+            self.start.format("%d %b"),
+            end.format("%d %b %Y")
+        )?;
+
+        // The hour labels, and a space between each of the seven columns.
+        let column = self.width.saturating_sub(6 + 6) / 7;
+        if column < WEEK_MIN_COLUMN {
+            self.fmt_list(f)
+        } else {
+            self.fmt_grid(f, column)
         }
     }
 }
 
+/// The columns `import_csv` reads, in the order a file without a header row
+/// has them. A header row may name them in any order and leave out all but
+/// `name` and `date`; columns it names that aren't here are ignored.
+pub const CSV_COLUMNS: &[&str] = &[
+    "name",
+    "message",
+    "recurrence_pattern",
+    "date",
+    "deleted_at",
+    "tags",
+    "calendar",
+    "priority",
+    "location",
+    "url",
+    "timezone",
+];
+
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    pub inserted: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Queries and edits events on behalf of the CLI, both for the interactive
+/// menu and the one-shot subcommands.
 pub struct Client<'a> {
-    conn: &'a Connection,
+    events: EventRepository<'a>,
+    sort: EventSort,
+    range: (DateTime<Utc>, DateTime<Utc>),
+    calendar: Option<String>,
+    tags: Vec<String>,
+    any_tag: bool,
+    recurrence: Option<RecurrencePattern>,
+    config_path: Option<PathBuf>,
+    pre_op_backup: Option<PreOpBackup>,
 }
 
 impl<'a> Client<'a> {
+    /// A client over a migrated database listing every event in every
+    /// calendar, by date.
     pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+        Self {
+            events: EventRepository::new(conn),
+            sort: EventSort::default(),
+            range: date_range(None, None).unwrap(),
+            calendar: None,
+            tags: Vec::new(),
+            any_tag: false,
+            recurrence: None,
+            config_path: None,
+            pre_op_backup: None,
+        }
     }
 
-    pub fn start(&self) {
-        let operations: &[&str; 4] = &[
-            Operation::Today.into(),
-            Operation::Create.into(),
-            Operation::Update.into(),
-            Operation::Delete.into(),
-        ];
+    pub fn with_sort(mut self, sort: EventSort) -> Self {
+        self.sort = sort;
+        self
+    }
 
-        let operation = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose an operation")
-            .default(0)
-            .items(&operations[..])
-            .interact()
-            .unwrap();
+    /// Restricts listings and exports to events dated within `range`, as
+    /// built by `date_range`.
+    pub fn with_range(mut self, range: (DateTime<Utc>, DateTime<Utc>)) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Restricts listings to one calendar, which new events also go into.
+    /// Without one, listings cover every calendar and new events go into
+    /// the default calendar.
+    pub fn with_calendar(mut self, calendar: Option<String>) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
+    /// Restricts listings to events carrying `tag`, compared case-insensitively.
+    pub fn with_tag(self, tag: Option<String>) -> Self {
+        self.with_tags(tag.as_slice(), false)
+    }
+
+    /// Restricts listings to events carrying all of `tags`, or any of them
+    /// with `any`, compared case-insensitively. No tags lists every event.
+    pub fn with_tags(mut self, tags: &[String], any: bool) -> Self {
+        self.tags = parse_tags(&tags.join(","));
+        self.any_tag = any;
+        self
+    }
+
+    /// Restricts listings and counts to events repeating by `recurrence`.
+    pub fn with_recurrence(mut self, recurrence: Option<RecurrencePattern>) -> Self {
+        self.recurrence = recurrence;
+        self
+    }
+
+    /// The config file the settings came from, for the interactive menu to
+    /// show.
+    pub fn with_config_path(mut self, path: Option<PathBuf>) -> Self {
+        self.config_path = path;
+        self
+    }
+
+    /// Where `purge` snapshots the database before removing anything; no
+    /// snapshot is taken without one.
+    pub fn with_pre_op_backup(mut self, backup: Option<PreOpBackup>) -> Self {
+        self.pre_op_backup = backup;
+        self
+    }
+
+    /// Reads "now" from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.events = self.events.with_clock(clock);
+        self
+    }
+
+    fn conn(&self) -> &'a Connection {
+        self.events.conn()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.events.clock().now_utc()
+    }
 
-        let operation_selection = Operation::from(operations[operation]);
+    fn now_local(&self) -> DateTime<Local> {
+        self.events.clock().now_local()
+    }
 
-        match operation_selection {
-            Operation::Today => println!("{}", self.fetch_current_day_events().unwrap()),
-            Operation::Create => self.create_event().unwrap(),
-            _ => todo!(),
+    /// The listing restrictions set on the client.
+    fn filter(&self) -> EventFilter {
+        EventFilter {
+            range: self.range,
+            calendar: self.calendar.clone(),
+            tags: self.tags.clone(),
+            any_tag: self.any_tag,
+            recurrence: self.recurrence,
+            include_deleted: false,
+            order_by: self.sort.order_by().to_string(),
+            limit: None,
         }
     }
 
-    fn create_event(&self) -> Result<(), String> {
-        let mut stmt = match self.conn.prepare(
-            "INSERT INTO EVENTS (name, message, recurrence_pattern, date) VALUES (?1, ?2, ?3, ?4)",
+    pub fn start(mut self) -> Result<(), NotifyMeError> {
+        match (
+            self.count_events_today(),
+            self.fetch_events_due_soon(DUE_SOON_MINUTES),
         ) {
-            Ok(stmt) => stmt,
-            Err(err) => return Err(err.to_string()),
-        };
+            (Ok(today), Ok(soon)) => println!(
+                "You have {} events today, {} in the next {} minutes.",
+                today,
+                soon.0.len(),
+                DUE_SOON_MINUTES
+            ),
+            (Err(err), _) | (_, Err(err)) => warn!("Failed to count events: {}", err),
+        }
 
+        if self.calendar.is_none() {
+            match self.select_calendar() {
+                Ok(calendar) => self.calendar = calendar,
+                Err(NotifyMeError::Cancelled) => return restore_terminal(),
+                Err(err) => warn!("Failed to list calendars: {}", err),
+            }
+        }
+
+        let _sigint = IgnoreSigint::new();
+
+        self.menu("Choose an operation", MENU, "Exit")?;
+        restore_terminal()
+    }
+
+    /// Shows `items` and runs the chosen ones until `leave` is chosen or the
+    /// menu is cancelled. Cancelling an operation comes back here.
+    fn menu(&self, prompt: &str, items: &[MenuItem], leave: &str) -> Result<(), NotifyMeError> {
+        let mut labels = items.iter().map(MenuItem::label).collect::<Vec<_>>();
+        labels.push(leave);
+        let mut default = 0;
+
+        loop {
+            let selection = match Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(default)
+                .items(&labels)
+                .interact_opt()
+                .or_cancel()
+            {
+                Err(NotifyMeError::Cancelled) => return Ok(()),
+                selection => selection?,
+            };
+            default = selection;
+
+            match items.get(selection) {
+                None => return Ok(()),
+                Some(MenuItem::Submenu(label, items)) => self.menu(label, items, "Back")?,
+                Some(MenuItem::Action(_, action)) => match action(self) {
+                    Err(NotifyMeError::Cancelled) => {
+                        restore_terminal()?;
+                        println!("Cancelled");
+                    }
+                    result => result?,
+                },
+            }
+        }
+    }
+
+    /// Offers a choice of calendar when there is more than one; `None`
+    /// stands for all of them.
+    fn select_calendar(&self) -> Result<Option<String>, NotifyMeError> {
+        let calendars = self.list_calendars()?;
+        if calendars.len() < 2 {
+            return Ok(None);
+        }
+
+        let mut items = vec!["All calendars".to_string()];
+        items.extend(calendars.iter().map(|calendar| calendar.name.clone()));
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose a calendar")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        Ok(calendars
+            .into_iter()
+            .nth(selection.wrapping_sub(1))
+            .map(|calendar| calendar.name))
+    }
+
+    fn create_event(&self) -> Result<(), NotifyMeError> {
         let event_name: String = Input::with_theme(&ColorfulTheme::default())
             .with_prompt("Event name")
-            .interact_text()
-            .unwrap();
+            .validate_with(|input: &String| validate_event_name(input))
+            .interact_text()?;
 
         let event_description: String = Input::with_theme(&ColorfulTheme::default())
             .with_prompt("Event description")
             .allow_empty(true)
-            .interact_text()
-            .unwrap();
+            .validate_with(|input: &String| validate_event_message(input))
+            .interact_text()?;
+
+        let similar = self.find_near_duplicate_by_content(&event_description)?;
+        if !similar.is_empty() {
+            println!("Similar events found:");
+            for event in &similar {
+                println!("  {} ({})", event.name, event.date_description());
+            }
+
+            if !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Continue anyway?")
+                .default(false)
+                .interact_opt()
+                .or_cancel()?
+            {
+                return Ok(());
+            }
+        }
 
-        let date_format = "%d/%m/%Y %H:%M";
         let event_date_input: String = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("Event date (dd/mm/yyyy hh:mm)")
-            .validate_with({
-                move |input: &String| -> Result<(), &str> {
-                    if NaiveDateTime::parse_from_str(input, date_format).is_ok() {
-                        Ok(())
-                    } else {
-                        Err("Invalid date format. Please use 'dd/mm/yyyy hh:mm'")
-                    }
+            .with_prompt("Event date (dd/mm/yyyy hh:mm [time zone])")
+            .validate_with(|input: &String| parse_date(input, None).map(|_| ()))
+            .interact_text()?;
+
+        let (event_date, zone) = parse_date(&event_date_input, None)?;
+
+        let recurrence_selection = select_recurrence()?;
+        let preview = occurrences(
+            event_date.with_timezone(&Local),
+            &recurrence_selection,
+            zone.as_ref(),
+            PREVIEW_OCCURRENCES,
+        );
+        if !preview.is_empty() {
+            println!(
+                "Next {} occurrences: {}",
+                preview.len(),
+                format_occurrences(&preview)
+            );
+        }
+
+        let location_input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Location")
+            .allow_empty(true)
+            .interact_text()?;
+
+        let url_input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Link (http or https URL)")
+            .allow_empty(true)
+            .validate_with(|input: &String| validate_event_url(input))
+            .interact_text()?;
+
+        let color_input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Color label (name or #rrggbb)")
+            .allow_empty(true)
+            .validate_with(|input: &String| output::parse_color(input).map(|_| ()))
+            .interact_text()?;
+
+        let priority = select_priority()?;
+
+        let tags_input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Tags (comma-separated)")
+            .allow_empty(true)
+            .interact_text()?;
+
+        let event = self.save_event(NewEvent {
+            message: event_description,
+            recurrence: recurrence_selection,
+            priority,
+            location: parse_optional(&location_input),
+            url: parse_optional(&url_input),
+            color: output::parse_color(&color_input)?,
+            timezone: zone.as_ref().map(|zone| zone.name().to_string()),
+            tags: parse_tags(&tags_input),
+            ..NewEvent::new(event_name, event_date)
+        })?;
+        println!("Created {}. {}", event.name, event.recurrence_description());
+
+        Ok(())
+    }
+
+    fn edit_menu(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+        if events.is_empty() {
+            println!("No events to edit");
+            return Ok(());
+        }
+
+        let items = events
+            .iter()
+            .map(|event| format!("{} ({})", event.name, event.date.format("%Y-%m-%d %H:%M")))
+            .collect::<Vec<String>>();
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose an event to edit")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+        let event = &events[selection];
+
+        let fields = EventField::ALL.map(<&str>::from);
+        let field = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose a field")
+            .default(0)
+            .items(&fields)
+            .interact_opt()
+            .or_cancel()?;
+        let field = EventField::ALL[field];
+
+        let input = |prompt: &str, current: String| -> Result<String, NotifyMeError> {
+            Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .with_initial_text(current)
+                .allow_empty(true)
+                .interact_text()
+                .map_err(Into::into)
+        };
+
+        let value = match field {
+            EventField::Name => Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Event name")
+                .with_initial_text(event.name.clone())
+                .validate_with(|input: &String| validate_event_name(input))
+                .interact_text()?,
+            EventField::Message => Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Event description")
+                .with_initial_text(event.message.clone())
+                .allow_empty(true)
+                .validate_with(|input: &String| validate_event_message(input))
+                .interact_text()?,
+            EventField::Date => {
+                // Entered as wall-clock time in the event's own zone.
+                let zone = event.zone();
+                let current = match &zone {
+                    Some(zone) => event.date.with_timezone(zone).format(DATE_FORMAT),
+                    None => event.date.format(DATE_FORMAT),
                 }
-            })
-            .interact_text()
-            .unwrap();
+                .to_string();
 
-        let event_date: DateTime<Utc> = {
-            let naive_date = NaiveDateTime::parse_from_str(&event_date_input, date_format)
-                .expect("Failed to parse date");
-            let local = Local.from_local_datetime(&naive_date).unwrap();
+                let date: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Event date (dd/mm/yyyy hh:mm)")
+                    .with_initial_text(current)
+                    .validate_with(|input: &String| parse_date(input, zone.clone()).map(|_| ()))
+                    .interact_text()?;
 
-            local.with_timezone(&Utc)
+                to_sql_date(&parse_date(&date, zone)?.0)
+            }
+            EventField::RecurrencePattern => select_recurrence()?.into(),
+            EventField::Priority => <&str>::from(select_priority()?).to_string(),
+            EventField::Location => input("Location", event.location.clone().unwrap_or_default())?,
+            EventField::Url => Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Link (http or https URL)")
+                .with_initial_text(event.url.clone().unwrap_or_default())
+                .allow_empty(true)
+                .validate_with(|input: &String| validate_event_url(input))
+                .interact_text()?,
+            EventField::Color => Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Color label (name or #rrggbb)")
+                .with_initial_text(event.color.clone().unwrap_or_default())
+                .allow_empty(true)
+                .validate_with(|input: &String| output::parse_color(input).map(|_| ()))
+                .interact_text()?,
+            EventField::Tags => input("Tags (comma-separated)", event.tags.join(", "))?,
         };
 
-        let recurrences: &[&str; 4] = &[
-            RecurrencePattern::Once.into(),
-            RecurrencePattern::Daily.into(),
-            RecurrencePattern::Weekly.into(),
-            RecurrencePattern::Monthly.into(),
-        ];
+        self.update_event_field(event.id, field, &value)?;
+        println!("Updated the {} of {}", <&str>::from(field), event.name);
 
-        let recurrence = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose an operation")
-            .default(0)
-            .items(&recurrences[..])
-            .interact()
-            .unwrap();
+        Ok(())
+    }
+
+    /// Sets one field of event `id` from its text form: the date as RFC
+    /// 3339, the recurrence and priority as stored, tags comma-separated.
+    /// A blank location, link or color clears it.
+    pub fn update_event_field(
+        &self,
+        id: i32,
+        field: EventField,
+        value: &str,
+    ) -> Result<(), NotifyMeError> {
+        self.get_event(id)?;
+
+        let (column, value) = match field {
+            EventField::Name => {
+                validate_event_name(value)?;
+                ("name", Some(value.trim().to_string()))
+            }
+            EventField::Message => {
+                validate_event_message(value)?;
+                ("message", Some(value.to_string()))
+            }
+            EventField::Date => match DateTime::parse_from_rfc3339(value.trim()) {
+                Ok(date) => ("date", Some(to_sql_date(&date))),
+                Err(err) => return Err(NotifyMeError::DateParse(format!("{}: {}", value, err))),
+            },
+            EventField::RecurrencePattern => {
+                let recurrence = value.parse::<RecurrencePattern>()?;
+                ("recurrence_pattern", Some(recurrence.into()))
+            }
+            EventField::Priority => {
+                let priority: &str = value.parse::<Priority>()?.into();
+                ("priority", Some(priority.to_string()))
+            }
+            EventField::Location => ("location", parse_optional(value)),
+            EventField::Url => {
+                validate_event_url(value)?;
+                ("url", parse_optional(value))
+            }
+            EventField::Color => ("color", output::parse_color(value)?),
+            EventField::Tags => {
+                let tx = self.conn().unchecked_transaction()?;
+                db::set_event_tags(&tx, id.into(), &parse_tags(value))?;
+                tx.execute(
+                    "UPDATE events SET updated_at = ?1 WHERE id = ?2",
+                    (to_sql_date(&self.now()), id),
+                )?;
+                tx.commit()?;
+
+                return Ok(());
+            }
+        };
+
+        self.conn().execute(
+            &format!(
+                "UPDATE events SET {} = ?1, updated_at = ?2 WHERE id = ?3",
+                column
+            ),
+            (value, to_sql_date(&self.now()), id),
+        )?;
 
-        let recurrence_selection = RecurrencePattern::from(recurrences[recurrence]);
+        Ok(())
+    }
 
-        match stmt.execute((
-            event_name,
-            event_description,
-            recurrence_selection,
-            event_date.to_rfc3339(),
-        )) {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err.to_string()),
+    /// Inserts an event, into the client's calendar unless it names one.
+    pub fn save_event(&self, mut event: NewEvent) -> Result<Event, NotifyMeError> {
+        if event.calendar.is_none() {
+            event.calendar = self.calendar.clone();
         }
+
+        self.events.insert(event)
     }
 
-    fn fetch_current_day_events(&self) -> Result<EventList, String> {
-        let mut stmt = match self.conn.prepare(
-            "SELECT id, name, message, recurrence_pattern, date, deleted_at FROM events
-       WHERE strftime('%Y-%m-%d', date) = strftime('%Y-%m-%d', 'now')
-       AND deleted_at IS NULL;",
-        ) {
-            Ok(stmt) => stmt,
-            Err(err) => return Err(err.to_string()),
-        };
-
-        let events = match stmt.query_map([], |row| {
-            Ok(Event {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                message: row.get(2)?,
-                recurrence_pattern: row.get(3)?,
-                date: DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
-                    .unwrap()
-                    .with_timezone(&Local),
-                deleted_at: row.get::<_, Option<String>>(5)?.and_then(|dt| {
-                    DateTime::parse_from_rfc3339(&dt)
-                        .ok()
-                        .map(|dt| dt.with_timezone(&Utc))
-                }),
-            })
-        }) {
-            Ok(events) => events
-                .filter_map(|event| event.ok())
-                .collect::<Vec<Event>>(),
-            Err(err) => return Err(err.to_string()),
+    /// Inserts an event made with `EventBuilder`, returning its id.
+    pub fn insert_event(&self, event: Event) -> Result<i32, NotifyMeError> {
+        Ok(self.events.insert(event.into())?.id)
+    }
+
+    /// Like `save_event`, but keeps the given id so imports and restores
+    /// reproduce the original rows. An existing event with that id is
+    /// updated in place, keeping its uuid, and taken out of the trash; an
+    /// upsert rather than `INSERT OR REPLACE`, whose implicit delete would
+    /// leave the old text in the search index.
+    pub fn create_event_with_id(
+        &self,
+        id: i32,
+        name: &str,
+        message: &str,
+        recurrence: RecurrencePattern,
+        date: DateTime<Utc>,
+        tags: &[String],
+    ) -> Result<(), NotifyMeError> {
+        if id <= 0 {
+            return Err(NotifyMeError::Other("Invalid ID".into()));
+        }
+
+        self.conn().execute(
+            "INSERT INTO events \
+             (id, name, message, recurrence_pattern, date, created_at, updated_at, calendar) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7) \
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, message = excluded.message, \
+             recurrence_pattern = excluded.recurrence_pattern, date = excluded.date, \
+             updated_at = excluded.updated_at, calendar = excluded.calendar, deleted_at = NULL",
+            (
+                id,
+                name,
+                message,
+                recurrence,
+                to_sql_date(&date),
+                to_sql_date(&self.now()),
+                self.calendar_or_default(),
+            ),
+        )?;
+
+        db::set_event_tags(self.conn(), id.into(), tags)?;
+
+        Ok(())
+    }
+
+    pub fn fetch_current_day_events(&self) -> Result<EventList, NotifyMeError> {
+        let events = self
+            .events
+            .find_on_day(self.now_local().date_naive(), &self.day_view_filter())
+            .map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    pub fn fetch_events_grouped_by_proximity(
+        &self,
+    ) -> Result<ProximityGroupedEvents, NotifyMeError> {
+        let today = self.now_local().date_naive();
+        let (today_start, tomorrow_start) = local_day_bounds(today);
+        let (_, later_start) = local_day_bounds(today + Days::new(1));
+        let (_, week_end) = local_day_bounds(today + Days::new(6));
+
+        Ok(ProximityGroupedEvents {
+            today: self.fetch_events_between(today_start, tomorrow_start)?,
+            tomorrow: self.fetch_events_between(tomorrow_start, later_start)?,
+            this_week: self.fetch_events_between(later_start, week_end)?,
+        })
+    }
+
+    /// Non-deleted events from `start` up to `end`, high priority first.
+    fn fetch_events_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<EventList, NotifyMeError> {
+        let filter = EventFilter {
+            range: (start, end),
+            ..self.day_view_filter()
         };
+        let events = self.events.list(&filter).map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// One-time events whose date passed without a notification, e.g. while
+    /// the scheduler wasn't running, oldest first.
+    pub fn fetch_overdue_events(&self) -> Result<EventList, NotifyMeError> {
+        let events = self
+            .events
+            .find_overdue_once(self.now())
+            .map(warn_skipped)?;
 
         Ok(EventList(events))
     }
+
+    /// Records events as notified, which takes overdue ones out of
+    /// `fetch_overdue_events`.
+    pub fn acknowledge(&self, ids: &[i32]) -> Result<(), NotifyMeError> {
+        let now = self.now();
+        self.events.transaction(|events| {
+            for id in ids {
+                events.mark_notified(*id, now)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Shows the overdue events and offers to deal with them.
+    pub fn overdue_menu(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_overdue_events()?;
+        let empty = events.0.is_empty();
+        let ids: Vec<i32> = events.0.iter().map(|event| event.id).collect();
+        println!(
+            "{}",
+            OverdueEvents {
+                events,
+                now: self.now_local(),
+            }
+        );
+
+        if empty {
+            return Ok(());
+        }
+        self.resolve_overdue(&ids)
+    }
+
+    /// Asks whether to acknowledge overdue events `ids`, move them all to a
+    /// new date, or leave them be.
+    pub fn resolve_overdue(&self, ids: &[i32]) -> Result<(), NotifyMeError> {
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} overdue events", ids.len()))
+            .default(0)
+            .items(&["Acknowledge all", "Reschedule all", "Leave them"])
+            .interact_opt()
+            .or_cancel()?;
+
+        match selection {
+            0 => {
+                self.acknowledge(ids)?;
+                println!("Acknowledged {} events", ids.len());
+            }
+            1 => {
+                let date_input: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("New date (dd/mm/yyyy hh:mm [time zone])")
+                    .validate_with(|input: &String| parse_date(input, None).map(|_| ()))
+                    .interact_text()?;
+                let (date, _) = parse_date(&date_input, None)?;
+
+                self.events.transaction(|events| {
+                    for id in ids {
+                        events.update_date(*id, &date)?;
+                    }
+
+                    Ok(())
+                })?;
+                println!(
+                    "Rescheduled {} events to {}",
+                    ids.len(),
+                    date.with_timezone(&Local).format(DATE_FORMAT)
+                );
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Events in a local calendar month, by date.
+    pub fn fetch_events_in_month(&self, year: i32, month: u32) -> Result<EventList, NotifyMeError> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)
+            .ok_or_else(|| format!("There is no month {} in {}", month, year))?;
+        let next = first + Months::new(1);
+
+        let events = self
+            .events
+            .list(&EventFilter {
+                range: (local_day_bounds(first).0, local_day_bounds(next).0),
+                order_by: "date".to_string(),
+                ..self.filter()
+            })
+            .map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// Every occurrence in the client's calendar during the seven local
+    /// days from `start`, recurring events expanded to one entry per
+    /// occurrence, by date.
+    pub fn fetch_events_in_week(&self, start: NaiveDate) -> Result<EventList, NotifyMeError> {
+        let (week_start, _) = local_day_bounds(start);
+        let (week_end, _) = local_day_bounds(start + Days::new(7));
+        let (week_start, week_end) = (
+            week_start.with_timezone(&Local),
+            week_end.with_timezone(&Local),
+        );
+
+        let events = self
+            .events
+            .list(&EventFilter {
+                range: (unbounded_range().0, week_end.with_timezone(&Utc)),
+                order_by: "date".to_string(),
+                ..self.filter()
+            })
+            .map(warn_skipped)?;
+
+        let mut occurrences = Vec::new();
+        for event in events {
+            let Some(mut date) = event.next_occurrence(week_start) else {
+                continue;
+            };
+            let zone = event.zone();
+            while date < week_end {
+                occurrences.push(Event {
+                    date,
+                    ..event.clone()
+                });
+
+                let next = advance_date_in(date, &event.recurrence_pattern, zone.as_ref());
+                if next <= date {
+                    break;
+                }
+                date = next;
+            }
+        }
+        occurrences.sort();
+
+        Ok(EventList(occurrences))
+    }
+
+    fn month_view_menu(&self) -> Result<(), NotifyMeError> {
+        let month_input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Month (yyyy-mm)")
+            .default(self.now_local().format("%Y-%m").to_string())
+            .validate_with(|input: &String| parse_month(input).map(|_| ()))
+            .interact_text()?;
+
+        let (year, month) = parse_month(&month_input)?;
+        println!(
+            "{}",
+            MonthCalendar {
+                year,
+                month,
+                events: self.fetch_events_in_month(year, month)?,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// The client's calendar, any tag, high priority first.
+    fn day_view_filter(&self) -> EventFilter {
+        EventFilter {
+            tags: Vec::new(),
+            order_by: format!("{}, {}", PRIORITY_ORDER, self.sort.order_by()),
+            ..self.filter()
+        }
+    }
+
+    /// Re-renders today's events every `refresh`, with a countdown to the
+    /// next event and a bar showing how far along we are since the last one.
+    pub fn watch(&self, refresh: std::time::Duration) -> Result<(), NotifyMeError> {
+        let term = Term::stdout();
+
+        loop {
+            let events = self.fetch_current_day_events()?;
+            let now = self.now_local();
+
+            term.clear_screen()?;
+
+            println!("{}\n", events);
+
+            let soon = self.fetch_events_due_soon(DUE_SOON_MINUTES)?;
+            println!(
+                "{} events in the next {} minutes",
+                soon.0.len(),
+                DUE_SOON_MINUTES
+            );
+
+            match self.fetch_adjacent_event(now, true)? {
+                Some(next) => {
+                    println!(
+                        "Next: {} in {}",
+                        next.name,
+                        format_duration_human(next.date - now)
+                    );
+
+                    if let Some(previous) = self.fetch_adjacent_event(now, false)? {
+                        let total = (next.date - previous.date).num_seconds() as f64;
+                        let elapsed = (now - previous.date).num_seconds() as f64;
+                        println!("{}", progress_bar(elapsed / total, WATCH_BAR_WIDTH));
+                    }
+                }
+                None => println!("Nothing scheduled"),
+            }
+
+            thread::sleep(refresh);
+        }
+    }
+
+    /// The closest listed event that isn't paused after `now`, or at or
+    /// before it when `after` is false.
+    pub fn fetch_adjacent_event(
+        &self,
+        now: DateTime<Local>,
+        after: bool,
+    ) -> Result<Option<Event>, NotifyMeError> {
+        // Dates are stored to the second, so the next second splits "after"
+        // from "at or before".
+        let split = now.with_timezone(&Utc) + Duration::seconds(1);
+        let filter = if after {
+            EventFilter {
+                range: (split.max(self.range.0), self.range.1),
+                order_by: "date".to_string(),
+                ..self.filter()
+            }
+        } else {
+            EventFilter {
+                range: (self.range.0, split.min(self.range.1)),
+                order_by: "date DESC".to_string(),
+                ..self.filter()
+            }
+        };
+        let events = self.events.list(&filter).map(warn_skipped)?;
+
+        Ok(events.into_iter().find(|event| !event.paused))
+    }
+
+    pub fn count_events_today(&self) -> Result<usize, NotifyMeError> {
+        let (start, end) = local_day_bounds(self.now_local().date_naive());
+
+        self.events.count_between(start, end)
+    }
+
+    /// Events firing in the next `minutes`, the way the scheduler's window
+    /// counts them, soonest first. Read-only, for display.
+    pub fn fetch_events_due_soon(&self, minutes: u32) -> Result<EventList, NotifyMeError> {
+        let filter = EventFilter {
+            tags: Vec::new(),
+            order_by: "date".to_string(),
+            ..self.filter()
+        };
+        let events = self
+            .events
+            .find_due_soon(self.now(), Duration::minutes(minutes.into()), &filter)
+            .map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// The `n` nearest events from now on, however far ahead they are,
+    /// soonest first.
+    pub fn fetch_next_n_events(&self, n: u32) -> Result<EventList, NotifyMeError> {
+        let filter = EventFilter {
+            range: (self.now().max(self.range.0), self.range.1),
+            order_by: "date".to_string(),
+            limit: Some(n),
+            ..self.filter()
+        };
+        let events = self.events.list(&filter).map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// The next `count` dates event `id` will move to after its current
+    /// one, as the scheduler would advance it. Nothing is written.
+    pub fn event_timeline(
+        &self,
+        event_id: i32,
+        count: u32,
+    ) -> Result<Vec<DateTime<Local>>, NotifyMeError> {
+        let event = self.get_event(event_id)?;
+
+        Ok(occurrences(
+            event.date,
+            &event.recurrence_pattern,
+            event.zone().as_ref(),
+            count,
+        ))
+    }
+
+    /// The earliest upcoming event that will notify, i.e. isn't paused.
+    pub fn next_event(&self) -> Result<Option<Event>, NotifyMeError> {
+        let filter = EventFilter {
+            range: (self.now().max(self.range.0), self.range.1),
+            order_by: "date".to_string(),
+            ..self.filter()
+        };
+        let events = self.events.list(&filter).map(warn_skipped)?;
+
+        Ok(events.into_iter().find(|event| !event.paused))
+    }
+
+    /// Imports the reminders `remind::parse` understands from a remind(1)
+    /// file. A reminder has only its `MSG` text, which becomes the event's
+    /// message and, cut to `MAX_NAME_LENGTH`, its name.
+    pub fn import_remind(&self, path: &Path) -> Result<ImportStats, NotifyMeError> {
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+        };
+
+        let file = remind::parse(&input);
+        let omits: HashSet<_> = file.omits.iter().collect();
+        let mut stats = ImportStats {
+            skipped: file.unsupported,
+            ..Default::default()
+        };
+
+        let tx = self.conn().unchecked_transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO events (name, message, recurrence_pattern, date, created_at, updated_at, calendar) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+            )?;
+
+            for reminder in &file.reminders {
+                let name: String = reminder.message.chars().take(MAX_NAME_LENGTH).collect();
+
+                for date in remind::occurrences(reminder) {
+                    if omits.contains(&date) {
+                        stats.skipped += 1;
+                        continue;
+                    }
+
+                    let event_date = match Local
+                        .from_local_datetime(&date.and_time(reminder.time))
+                        .earliest()
+                    {
+                        Some(local) => local.with_timezone(&Utc),
+                        None => {
+                            warn!(
+                                "{} {} does not exist locally, skipping",
+                                date, reminder.time
+                            );
+                            stats.failed += 1;
+                            continue;
+                        }
+                    };
+
+                    match stmt.execute((
+                        &name,
+                        &reminder.message,
+                        reminder.recurrence(),
+                        to_sql_date(&event_date),
+                        to_sql_date(&self.now()),
+                        self.calendar_or_default(),
+                    )) {
+                        Ok(_) => stats.inserted += 1,
+                        Err(err) => {
+                            warn!("Failed to import '{}': {}", reminder.message, err);
+                            stats.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Imports events from a CSV file, one per row; see `CSV_COLUMNS`. Rows
+    /// that can't be read are logged and counted as failed, and rows
+    /// matching an existing event by name and date are skipped, without
+    /// holding up the rest.
+    pub fn import_csv(&self, path: &Path, has_header: bool) -> Result<ImportStats, NotifyMeError> {
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+        };
+
+        let mut records = csv::parse(&input).into_iter();
+        let columns: Vec<String> = if has_header {
+            match records.next() {
+                Some(csv::Record {
+                    fields: Ok(header), ..
+                }) => header
+                    .iter()
+                    .map(|column| column.trim().to_lowercase())
+                    .collect(),
+                Some(csv::Record {
+                    line,
+                    fields: Err(err),
+                }) => return Err(format!("{}:{}: {}", path.display(), line, err).into()),
+                None => return Ok(ImportStats::default()),
+            }
+        } else {
+            CSV_COLUMNS.iter().map(ToString::to_string).collect()
+        };
+
+        for required in ["name", "date"] {
+            if !columns.iter().any(|column| column == required) {
+                return Err(format!("{}: no {} column", path.display(), required).into());
+            }
+        }
+
+        self.events.transaction(|events| {
+            let mut stats = ImportStats::default();
+
+            for record in records {
+                let (event, deleted_at) = match record
+                    .fields
+                    .and_then(|fields| self.csv_event(&columns, &fields))
+                {
+                    Ok(row) => row,
+                    Err(err) => {
+                        warn!(line = record.line, "{}, skipping", err);
+                        stats.failed += 1;
+                        continue;
+                    }
+                };
+
+                let exists: bool = events.conn().query_row(
+                    "SELECT EXISTS (SELECT 1 FROM events WHERE name = ?1 AND date = ?2)",
+                    (&event.name, to_sql_date(&event.date)),
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    stats.skipped += 1;
+                    continue;
+                }
+
+                let id = events.insert(event)?.id;
+                if let Some(deleted_at) = deleted_at {
+                    events.conn().execute(
+                        "UPDATE events SET deleted_at = ?1 WHERE id = ?2",
+                        (to_sql_date(&deleted_at), id),
+                    )?;
+                }
+                stats.inserted += 1;
+            }
+
+            Ok(stats)
+        })
+    }
+
+    /// The event in a CSV row, and when it was deleted if it was.
+    fn csv_event(
+        &self,
+        columns: &[String],
+        fields: &[String],
+    ) -> Result<(NewEvent, Option<DateTime<Utc>>), String> {
+        let field = |name: &str| {
+            columns
+                .iter()
+                .position(|column| column == name)
+                .and_then(|index| fields.get(index))
+                .map_or("", |field| field.trim())
+        };
+
+        let name = field("name");
+        validate_event_name(name)?;
+        let message = field("message");
+        validate_event_message(message)?;
+        let url = field("url");
+        validate_event_url(url)?;
+
+        let recurrence = match field("recurrence_pattern") {
+            "" => RecurrencePattern::Once,
+            pattern => pattern.parse()?,
+        };
+        let priority = match field("priority") {
+            "" => Priority::default(),
+            priority => priority.parse()?,
+        };
+        let timezone = match field("timezone") {
+            "" => None,
+            name => Some(Zone::load(name)?.name().to_string()),
+        };
+        let deleted_at = match field("deleted_at") {
+            "" => None,
+            date => Some(parse_csv_date(date)?),
+        };
+
+        let event = NewEvent {
+            message: message.to_string(),
+            recurrence,
+            priority,
+            location: parse_optional(field("location")),
+            url: parse_optional(url),
+            timezone,
+            tags: parse_tags(field("tags")),
+            calendar: Some(
+                parse_optional(field("calendar"))
+                    .unwrap_or_else(|| self.calendar_or_default().to_string()),
+            ),
+            ..NewEvent::new(name, parse_csv_date(field("date"))?)
+        };
+
+        Ok((event, deleted_at))
+    }
+
+    /// Imports a document written by `export_json`. Records matching an
+    /// existing event by name and date are handled per `on_conflict`. Any
+    /// bad record rolls the whole import back; so does `dry_run`, after
+    /// printing what would have changed.
+    pub fn import_json(
+        &self,
+        path: &Path,
+        on_conflict: OnConflict,
+        dry_run: bool,
+    ) -> Result<ImportStats, NotifyMeError> {
+        let document = match fs::read_to_string(path) {
+            Ok(input) => match json::parse(&input) {
+                Ok(document) => document,
+                Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+            },
+            Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+        };
+
+        match document.get("schema_version").and_then(Value::as_i64) {
+            Some(version) if version <= db::schema_version().into() => (),
+            Some(version) => {
+                return Err(format!(
+                    "{} was exported with schema version {}, newer than this build's {}",
+                    path.display(),
+                    version,
+                    db::schema_version()
+                )
+                .into())
+            }
+            None => return Err(format!("{}: missing schema_version", path.display()).into()),
+        }
+
+        let records = match document.get("events").and_then(Value::as_array) {
+            Some(records) => records,
+            None => return Err(format!("{}: missing events list", path.display()).into()),
+        };
+
+        let tx = self.conn().unchecked_transaction()?;
+
+        let mut stats = ImportStats::default();
+        for (index, record) in records.iter().enumerate() {
+            if let Err(err) = self.import_json_event(record, on_conflict, dry_run, &mut stats) {
+                return Err(format!("Record {}: {}", index, err).into());
+            }
+        }
+
+        if dry_run {
+            return Ok(stats);
+        }
+
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    fn import_json_event(
+        &self,
+        record: &Value,
+        on_conflict: OnConflict,
+        dry_run: bool,
+        stats: &mut ImportStats,
+    ) -> Result<(), NotifyMeError> {
+        let event = Event::from_json(record)?;
+        let date = to_sql_date(&event.date);
+
+        let existing = if event.uuid.is_empty() {
+            self.conn()
+                .query_row(
+                    "SELECT id FROM events WHERE name = ?1 AND date = ?2",
+                    (&event.name, &date),
+                    |row| row.get::<_, i32>(0),
+                )
+                .optional()
+        } else {
+            self.conn()
+                .query_row(
+                    "SELECT id FROM events WHERE uuid = ?1",
+                    [&event.uuid],
+                    |row| row.get::<_, i32>(0),
+                )
+                .optional()
+        }?;
+
+        let action = match (existing, on_conflict) {
+            (Some(_), OnConflict::Skip) => {
+                stats.skipped += 1;
+                "skip"
+            }
+            (Some(id), OnConflict::Overwrite) => {
+                self.conn().execute(
+                    "UPDATE events SET name = ?8, date = ?9, message = ?1, \
+                     recurrence_pattern = ?2, deleted_at = ?3, \
+                     last_notified_at = ?4, updated_at = ?5, calendar = ?6, priority = ?10, \
+                     location = ?11, url = ?12, color = ?13, paused = ?14, timezone = ?15 \
+                     WHERE id = ?7",
+                    (
+                        &event.message,
+                        event.recurrence_pattern,
+                        event.deleted_at.map(|date| to_sql_date(&date)),
+                        event.last_notified_at.map(|date| to_sql_date(&date)),
+                        to_sql_date(&event.updated_at),
+                        &event.calendar,
+                        id,
+                        &event.name,
+                        &date,
+                        event.priority,
+                        &event.location,
+                        &event.url,
+                        &event.color,
+                        event.paused,
+                        &event.timezone,
+                    ),
+                )?;
+                db::set_event_tags(self.conn(), id.into(), &event.tags)?;
+
+                stats.overwritten += 1;
+                "overwrite"
+            }
+            _ => {
+                self.insert_imported_event(&event)?;
+                stats.inserted += 1;
+                "insert"
+            }
+        };
+
+        if dry_run {
+            println!(
+                "Would {} '{}' at {}",
+                action,
+                event.name,
+                event.date.format(DATE_FORMAT)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Inserts an imported event under its original id and uuid when they are
+    /// free; a duplicate gets a fresh uuid from the database.
+    fn insert_imported_event(&self, event: &Event) -> Result<(), NotifyMeError> {
+        let taken = self
+            .conn()
+            .query_row("SELECT 1 FROM events WHERE id = ?1", [event.id], |_| Ok(()))
+            .optional()?
+            .is_some();
+
+        let date = event.date.with_timezone(&Utc);
+        let id: i64 = if event.id > 0 && !taken {
+            let created = self.create_event_with_id(
+                event.id,
+                &event.name,
+                &event.message,
+                event.recurrence_pattern,
+                date,
+                &event.tags,
+            );
+            created?;
+            event.id.into()
+        } else {
+            let saved = self.save_event(NewEvent {
+                message: event.message.clone(),
+                recurrence: event.recurrence_pattern,
+                priority: event.priority,
+                location: event.location.clone(),
+                url: event.url.clone(),
+                color: event.color.clone(),
+                timezone: event.timezone.clone(),
+                tags: event.tags.clone(),
+                ..NewEvent::new(event.name.clone(), date)
+            })?;
+            saved.id.into()
+        };
+
+        self.conn().execute(
+            "UPDATE events SET deleted_at = ?1, last_notified_at = ?2, created_at = ?3, \
+             updated_at = ?4, calendar = ?6, priority = ?8, location = ?9, \
+             url = ?10, color = ?11, paused = ?12, timezone = ?13, uuid = coalesce((SELECT ?5 WHERE ?5 NOT IN (SELECT uuid FROM events)), uuid) \
+             WHERE id = ?7",
+            (
+                event.deleted_at.map(|date| to_sql_date(&date)),
+                event.last_notified_at.map(|date| to_sql_date(&date)),
+                to_sql_date(&event.created_at),
+                to_sql_date(&event.updated_at),
+                Some(&event.uuid).filter(|uuid| !uuid.is_empty()),
+                &event.calendar,
+                id,
+                event.priority,
+                &event.location,
+                &event.url,
+                &event.color,
+                event.paused,
+                &event.timezone,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn delete_event(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+        if events.is_empty() {
+            println!("No events to delete");
+            return Ok(());
+        }
+
+        let items = events
+            .iter()
+            .map(|event| format!("{} ({})", event.name, event.date.format("%Y-%m-%d %H:%M")))
+            .collect::<Vec<String>>();
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose an event to delete")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        self.events.soft_delete(events[selection].id)
+    }
+
+    fn export_ics_menu(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+        if events.is_empty() {
+            println!("No events to export");
+            return Ok(());
+        }
+
+        let items = events
+            .iter()
+            .map(|event| format!("{} ({})", event.name, event.date.format("%Y-%m-%d %H:%M")))
+            .collect::<Vec<String>>();
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose an event to export")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        let event = &events[selection];
+        let path: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Save as")
+            .default(format!("event-{}.ics", event.id))
+            .interact_text()?;
+
+        self.export_ics_single(event.id, Path::new(&path))?;
+        println!("Exported {} to {}", event.name, path);
+
+        Ok(())
+    }
+
+    /// Writes event `id` alone to an `.ics` file, with an alarm at the
+    /// scheduler's advance notification time.
+    pub fn export_ics_single(&self, id: i32, path: &Path) -> Result<(), NotifyMeError> {
+        let event = self.get_event(id)?;
+        let calendar = ics::single_event_calendar(
+            &event,
+            Duration::minutes(scheduler::DEFAULT_ADVANCE_MINUTES.into()),
+        );
+
+        match fs::write(path, calendar) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(format!("{}: {}", path.display(), err).into()),
+        }
+    }
+
+    fn mark_done_menu(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+        if events.is_empty() {
+            println!("No events to mark done");
+            return Ok(());
+        }
+
+        let items = events
+            .iter()
+            .map(|event| format!("{} ({})", event.name, event.date.format("%Y-%m-%d %H:%M")))
+            .collect::<Vec<String>>();
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose an event to mark done")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        self.mark_done(events[selection].id)
+    }
+
+    fn pause_menu(&self) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+        if events.is_empty() {
+            println!("No events to pause");
+            return Ok(());
+        }
+
+        let items = events
+            .iter()
+            .map(|event| {
+                format!(
+                    "{} ({}){}",
+                    event.name,
+                    event.date.format("%Y-%m-%d %H:%M"),
+                    if event.paused { " paused" } else { "" }
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose an event to pause or resume")
+            .default(0)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        let event = &events[selection];
+        self.set_paused(event.id, !event.paused)?;
+        println!(
+            "{} {}",
+            if event.paused { "Resumed" } else { "Paused" },
+            event.name
+        );
+
+        Ok(())
+    }
+
+    /// Pauses or resumes event `id`. The scheduler keeps advancing a paused
+    /// event's date, so resuming picks up at its next occurrence.
+    pub fn set_paused(&self, id: i32, paused: bool) -> Result<(), NotifyMeError> {
+        let event = self.get_event_including_deleted(id)?;
+        if event.deleted_at.is_some() {
+            return Err(format!("Event {} ({}) is in the trash", id, event.name).into());
+        }
+
+        self.conn().execute(
+            "UPDATE events SET paused = ?1, updated_at = ?2 WHERE id = ?3",
+            (paused, to_sql_date(&self.now()), id),
+        )?;
+        Ok(())
+    }
+
+    fn bulk_reschedule_menu(&self) -> Result<(), NotifyMeError> {
+        println!("Change events that repeat");
+        let from = select_recurrence()?;
+        let count = self.count_events_with_recurrence(from)?;
+        if count == 0 {
+            println!("No {} events", from);
+            return Ok(());
+        }
+
+        println!("to");
+        let to = select_recurrence()?;
+        if to == from {
+            return Ok(());
+        }
+
+        if !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Change {} {} events to {}?", count, from, to))
+            .default(false)
+            .interact_opt()
+            .or_cancel()?
+        {
+            return Ok(());
+        }
+
+        let updated = self.bulk_update_recurrence(from, to)?;
+        println!("Changed {} events to {}", updated, to);
+
+        Ok(())
+    }
+
+    /// How many events outside the trash repeat by `pattern`.
+    pub fn count_events_with_recurrence(
+        &self,
+        pattern: RecurrencePattern,
+    ) -> Result<usize, NotifyMeError> {
+        Ok(self.conn().query_row(
+            "SELECT COUNT(*) FROM events WHERE recurrence_pattern = ?1 AND deleted_at IS NULL",
+            [pattern],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// How many events outside the trash repeat by each pattern in use,
+    /// keyed by the pattern as stored, e.g. `every_n_weeks:2`.
+    pub fn count_by_recurrence(&self) -> Result<HashMap<String, usize>, NotifyMeError> {
+        self.events.count_by_recurrence()
+    }
+
+    /// A summary of the whole database, trash included, for `stats`.
+    pub fn stats(&self) -> Result<Stats, NotifyMeError> {
+        let mut by_recurrence: Vec<_> = self.events.count_by_recurrence()?.into_iter().collect();
+        by_recurrence.sort();
+
+        let today = self.now_local().date_naive();
+        let mut next_days = Vec::new();
+        for day in (0..STATS_DAYS).map(|offset| today + Days::new(offset)) {
+            let (start, end) = local_day_bounds(day);
+            next_days.push((day, self.events.count_between(start, end)?));
+        }
+
+        Ok(Stats {
+            total: by_recurrence.iter().map(|(_, count)| count).sum(),
+            deleted: self.events.count_deleted()?,
+            by_recurrence,
+            next_days,
+            by_weekday: self.events.count_by_weekday()?,
+        })
+    }
+
+    /// Switches every event outside the trash repeating by `from` to `to`,
+    /// e.g. meetings entered as one-offs that are really weekly. Returns how
+    /// many events changed.
+    pub fn bulk_update_recurrence(
+        &self,
+        from: RecurrencePattern,
+        to: RecurrencePattern,
+    ) -> Result<usize, NotifyMeError> {
+        Ok(self.conn().execute(
+            "UPDATE events SET recurrence_pattern = ?2, updated_at = ?3 \
+             WHERE recurrence_pattern = ?1 AND deleted_at IS NULL",
+            (from, to, to_sql_date(&self.now())),
+        )?)
+    }
+
+    /// Moves an event to the trash as done rather than unwanted, recording
+    /// it in `completed_events`.
+    pub fn mark_done(&self, id: i32) -> Result<(), NotifyMeError> {
+        let event = self.get_event_including_deleted(id)?;
+        if event.deleted_at.is_some() {
+            return Err(format!("Event {} ({}) is already in the trash", id, event.name).into());
+        }
+
+        self.events.transaction(|events| {
+            events.soft_delete(id)?;
+            events.conn().execute(
+                "INSERT INTO completed_events (event_id, event_name, completed_at) \
+                 SELECT id, name, deleted_at FROM events WHERE id = ?1",
+                [id],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    fn trash_menu(&self) -> Result<(), NotifyMeError> {
+        let trash = self.fetch_deleted_events(TRASH_LIMIT)?;
+        if trash.0.is_empty() {
+            println!("Trash is empty");
+            return Ok(());
+        }
+
+        println!("{}", trash);
+
+        let mut items = trash
+            .0
+            .iter()
+            .map(|event| format!("Restore {}", event.name))
+            .collect::<Vec<String>>();
+        items.push("Back".to_string());
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Trash")
+            .default(items.len() - 1)
+            .items(&items)
+            .interact_opt()
+            .or_cancel()?;
+
+        match trash.0.get(selection) {
+            Some(event) => self.restore_event(event.id),
+            None => Ok(()),
+        }
+    }
+
+    pub fn fetch_deleted_events(&self, limit: u32) -> Result<EventList, NotifyMeError> {
+        let mut stmt = self.conn().prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE deleted_at IS NOT NULL AND date >= ?2 AND date < ?3 \
+             AND (?4 IS NULL OR calendar = ?4) \
+             ORDER BY deleted_at DESC LIMIT ?1;",
+            EVENT_COLUMNS
+        ))?;
+
+        let params = (
+            limit,
+            to_sql_date(&self.range.0),
+            to_sql_date(&self.range.1),
+            &self.calendar,
+        );
+        let events = stmt
+            .query_map(params, Event::try_from_row)
+            .and_then(partition_rows)
+            .map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// Finds the event `reference` points at: a numeric id, a uuid, or a
+    /// prefix of exactly one event's uuid.
+    pub fn resolve_event_id(&self, reference: &str) -> Result<i32, NotifyMeError> {
+        if let Ok(id) = reference.parse::<i32>() {
+            return Ok(id);
+        }
+
+        let reference = reference.to_lowercase();
+        if reference.is_empty() || !reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return Err(format!("'{}' is neither an id nor a uuid", reference).into());
+        }
+
+        let ids = query_ids(
+            self.conn(),
+            "SELECT id FROM events WHERE uuid LIKE ?1 || '%' LIMIT 2",
+            [&reference],
+        )?;
+
+        match ids.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(format!("No event with uuid {}", reference).into()),
+            _ => Err(format!("uuid prefix {} matches several events", reference).into()),
+        }
+    }
+
+    /// Takes an event out of the trash. Recurring events whose occurrence
+    /// passed while they were deleted are moved to their next future date.
+    pub fn restore_event(&self, id: i32) -> Result<(), NotifyMeError> {
+        let event = self.get_event_including_deleted(id)?;
+
+        if event.deleted_at.is_none() {
+            return Err(format!("Event {} ({}) is not in the trash", id, event.name).into());
+        }
+
+        let now = self.now_local();
+        let zone = event.zone();
+        let mut date = event.date;
+        while date < now {
+            let next = advance_date_in(date, &event.recurrence_pattern, zone.as_ref());
+            if next <= date {
+                break;
+            }
+            date = next;
+        }
+
+        let tx = self.conn().unchecked_transaction()?;
+
+        event.restore(&tx)?;
+
+        if date != event.date {
+            tx.execute(
+                "UPDATE events SET date = ?1 WHERE id = ?2",
+                (to_sql_date(&date), id),
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Permanently removes soft-deleted events, asking first unless
+    /// `confirmed`, and takes the pre-op backup, if set, once there is
+    /// something to remove. Rows in auxiliary tables go with them through
+    /// their `ON DELETE CASCADE` foreign keys. Returns how many events were
+    /// removed.
+    pub fn purge(&self, target: PurgeTarget, confirmed: bool) -> Result<usize, NotifyMeError> {
+        let tx = self.conn().unchecked_transaction()?;
+
+        let ids = match target {
+            PurgeTarget::Event(id) => {
+                match tx.query_row(
+                    "SELECT deleted_at IS NOT NULL FROM events WHERE id = ?1",
+                    [id],
+                    |row| row.get::<_, bool>(0),
+                ) {
+                    Ok(true) => vec![id],
+                    Ok(false) => {
+                        return Err(format!(
+                            "Event {} is not deleted; only events in the trash can be purged",
+                            id
+                        )
+                        .into())
+                    }
+                    Err(rusqlite::Error::QueryReturnedNoRows) => {
+                        return Err(NotifyMeError::NotFound(id))
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            PurgeTarget::AllDeleted => query_ids(
+                &tx,
+                "SELECT id FROM events WHERE deleted_at IS NOT NULL",
+                [],
+            )?,
+            PurgeTarget::OlderThan(age) => query_ids(
+                &tx,
+                "SELECT id FROM events WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+                [to_sql_date(&(self.now() - age))],
+            )?,
+        };
+
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        if !confirmed
+            && !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Permanently delete {} events?", ids.len()))
+                .default(false)
+                .interact_opt()
+                .or_cancel()?
+        {
+            return Ok(0);
+        }
+
+        if let Some(backup) = &self.pre_op_backup {
+            backup.take(self.conn(), "purge")?;
+        }
+
+        let mut removed = 0;
+        for id in ids {
+            removed += tx.execute(
+                "DELETE FROM events WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    pub fn export_shell_script(&self, path: &Path) -> Result<(), NotifyMeError> {
+        let events = self.fetch_active_events()?;
+
+        let mut script = format!(
+            "#!/bin/sh\n# notify_me events exported at {}\nset -e\n\n",
+            self.now().to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+
+        for event in events {
+            // The date goes out as wall-clock time in the event's own zone.
+            let zone = event.zone();
+            let date = match &zone {
+                Some(zone) => event
+                    .date
+                    .with_timezone(zone)
+                    .format(DATE_FORMAT)
+                    .to_string(),
+                None => event.date.format(DATE_FORMAT).to_string(),
+            };
+
+            script.push_str(&format!(
+                "{} --create --name {} --message {} --date {} --recurrence {} --priority {} \
+                 --location {} --url {} --color {} --tz {} --tags {} --calendar {}\n",
+                env!("CARGO_PKG_NAME"),
+                shell_quote(&event.name),
+                shell_quote(&event.message),
+                shell_quote(&date),
+                shell_quote(&String::from(event.recurrence_pattern)),
+                shell_quote(event.priority.into()),
+                shell_quote(event.location.as_deref().unwrap_or_default()),
+                shell_quote(event.url.as_deref().unwrap_or_default()),
+                shell_quote(event.color.as_deref().unwrap_or_default()),
+                shell_quote(zone.as_ref().map_or("", Zone::name)),
+                shell_quote(&event.tags.join(",")),
+                shell_quote(&event.calendar),
+            ));
+        }
+
+        if let Err(err) = fs::write(path, script) {
+            return Err(format!("{}: {}", path.display(), err).into());
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Err(err) = fs::set_permissions(path, fs::Permissions::from_mode(0o755)) {
+                return Err(format!("{}: {}", path.display(), err).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every non-deleted event as a `remind` file that
+    /// `import_remind` reads back.
+    pub fn export_remind(&self, path: &Path) -> Result<(), NotifyMeError> {
+        let mut file = format!(
+            "# notify_me events exported at {}\n",
+            self.now().to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+
+        for event in self.fetch_active_events()? {
+            file.push_str(&remind::reminder_line(&event));
+            file.push('\n');
+        }
+
+        match fs::write(path, file) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(format!("{}: {}", path.display(), err).into()),
+        }
+    }
+
+    /// Every event, deleted ones included, ordered by id, along with the
+    /// schema version so an importer knows what it is reading.
+    pub fn export_json(&self) -> Result<Value, NotifyMeError> {
+        let events = self
+            .events
+            .list(&EventFilter {
+                include_deleted: true,
+                order_by: "id".to_string(),
+                ..self.filter()
+            })
+            .map(warn_skipped)?
+            .iter()
+            .map(Event::to_json)
+            .collect();
+
+        Ok(Value::Object(vec![
+            (
+                "schema_version".to_string(),
+                Value::Number(db::user_version(self.conn())?.into()),
+            ),
+            ("events".to_string(), Value::Array(events)),
+        ]))
+    }
+
+    /// Every tag in use by a non-deleted event with how many events carry
+    /// it, most used first.
+    pub fn list_all_tags(&self) -> Result<Vec<(String, usize)>, NotifyMeError> {
+        let mut stmt = self.conn().prepare(
+            "SELECT t.name, count(*) FROM tags t \
+                 JOIN event_tags et ON et.tag_id = t.id \
+                 JOIN events e ON e.id = et.event_id \
+             WHERE e.deleted_at IS NULL AND e.date >= ?1 AND e.date < ?2 \
+             GROUP BY t.id ORDER BY count(*) DESC, t.name;",
+        )?;
+
+        let params = (to_sql_date(&self.range.0), to_sql_date(&self.range.1));
+        let tags = stmt
+            .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, usize)>>>()?;
+
+        Ok(tags)
+    }
+
+    /// Every event, past, deleted and done ones included, newest first.
+    pub fn fetch_all_events_unfiltered(&self) -> Result<Vec<EventWithStatus>, NotifyMeError> {
+        let now = self.now();
+
+        Ok(self
+            .events
+            .list(&EventFilter {
+                include_deleted: true,
+                order_by: "date DESC".to_string(),
+                ..self.filter()
+            })
+            .map(warn_skipped)?
+            .into_iter()
+            .map(|event| EventWithStatus::new(event, now))
+            .collect())
+    }
+
+    fn calendar_or_default(&self) -> &str {
+        self.calendar.as_deref().unwrap_or(DEFAULT_CALENDAR)
+    }
+
+    /// Every calendar with its number of non-deleted events, by name.
+    pub fn list_calendars(&self) -> Result<Vec<Calendar>, NotifyMeError> {
+        let mut stmt = self.conn().prepare(
+            "SELECT e.calendar, count(e.deleted_at IS NULL OR NULL), \
+                 coalesce(s.muted, 0) \
+             FROM events e LEFT JOIN calendar_settings s ON s.calendar = e.calendar \
+             GROUP BY e.calendar ORDER BY e.calendar",
+        )?;
+
+        let calendars = stmt
+            .query_map([], |row| {
+                Ok(Calendar {
+                    name: row.get(0)?,
+                    events: row.get(1)?,
+                    muted: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Calendar>>>()?;
+
+        Ok(calendars)
+    }
+
+    /// Moves every event, and the settings, of calendar `from` to `to`,
+    /// merging them into `to` if it already exists.
+    pub fn rename_calendar(&self, from: &str, to: &str) -> Result<usize, NotifyMeError> {
+        let to = to.trim();
+        if to.is_empty() {
+            return Err("Calendar names can't be empty".into());
+        }
+
+        let tx = self.conn().unchecked_transaction()?;
+
+        let moved = match tx.execute(
+            "UPDATE events SET calendar = ?2, updated_at = ?3 WHERE calendar = ?1",
+            (from, to, to_sql_date(&self.now())),
+        ) {
+            Ok(0) => return Err(format!("No calendar named {}", from).into()),
+            Ok(moved) => moved,
+            Err(err) => return Err(err.into()),
+        };
+
+        tx.execute(
+            "UPDATE OR REPLACE calendar_settings SET calendar = ?2 WHERE calendar = ?1",
+            (from, to),
+        )?;
+
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    /// Mutes or unmutes a calendar; the scheduler keeps advancing muted
+    /// events but doesn't show their notifications.
+    pub fn set_calendar_muted(&self, calendar: &str, muted: bool) -> Result<(), NotifyMeError> {
+        if !self
+            .list_calendars()?
+            .iter()
+            .any(|existing| existing.name == calendar)
+        {
+            return Err(format!("No calendar named {}", calendar).into());
+        }
+
+        self.conn().execute(
+            "INSERT INTO calendar_settings (calendar, muted) VALUES (?1, ?2) \
+             ON CONFLICT (calendar) DO UPDATE SET muted = excluded.muted",
+            (calendar, muted),
+        )?;
+        Ok(())
+    }
+
+    /// Event `id`, unless it is in the trash.
+    pub fn get_event(&self, id: i32) -> Result<Event, NotifyMeError> {
+        self.events.get(id)
+    }
+
+    /// Event `id`, in the trash or not, for restoring it.
+    pub fn get_event_including_deleted(&self, id: i32) -> Result<Event, NotifyMeError> {
+        self.events.get_including_deleted(id)
+    }
+
+    /// Events whose message contains the start of `message`, to catch the
+    /// same reminder being entered twice. Only the first
+    /// `DUPLICATE_PREFIX_CHARS` characters are compared, so a retyped
+    /// message with a different ending still matches.
+    pub fn find_near_duplicate_by_content(
+        &self,
+        message: &str,
+    ) -> Result<Vec<Event>, NotifyMeError> {
+        let prefix = message
+            .trim()
+            .chars()
+            .take(DUPLICATE_PREFIX_CHARS)
+            .collect::<String>();
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pattern = escape_like(&prefix);
+        let mut stmt = self.conn().prepare(&format!(
+            "SELECT {} FROM events WHERE deleted_at IS NULL \
+             AND message LIKE '%' || ?1 || '%' ESCAPE '\\' \
+             ORDER BY date",
+            EVENT_COLUMNS
+        ))?;
+        let events = stmt
+            .query_map([pattern], Event::try_from_row)
+            .and_then(partition_rows)
+            .map(warn_skipped)?;
+
+        Ok(events)
+    }
+
+    /// Events whose name or message match the FTS5 `query`, best match
+    /// first.
+    pub fn full_text_search(&self, query: &str) -> Result<EventList, NotifyMeError> {
+        if !db::has_search_index(self.conn())? {
+            return self.like_search(query);
+        }
+
+        let mut stmt = self.conn().prepare(&format!(
+            "SELECT {} FROM events \
+             JOIN (SELECT rowid, rank FROM events_fts WHERE events_fts MATCH ?1) AS hits \
+             ON events.id = hits.rowid \
+             WHERE deleted_at IS NULL AND (?2 IS NULL OR calendar = ?2) \
+             ORDER BY hits.rank",
+            EVENT_COLUMNS
+        ))?;
+        let events = stmt
+            .query_map((query, &self.calendar), Event::try_from_row)
+            .and_then(partition_rows)
+            .map(warn_skipped)
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(_, Some(message))
+                    if message.starts_with("fts5:") =>
+                {
+                    NotifyMeError::Other(format!("Invalid search query: {}", message))
+                }
+                err => err.into(),
+            })?;
+
+        Ok(EventList(events))
+    }
+
+    /// `full_text_search` with the text each event matched on, the matched
+    /// terms marked for `SearchHits` to highlight.
+    pub fn search_with_snippets(&self, query: &str) -> Result<SearchHits, NotifyMeError> {
+        let events = self.full_text_search(query)?;
+
+        let mut snippets = HashMap::new();
+        if db::has_search_index(self.conn())? {
+            let mut stmt = self.conn().prepare(
+                "SELECT rowid, snippet(events_fts, -1, char(2), char(3), '…', 12) \
+                 FROM events_fts WHERE events_fts MATCH ?1",
+            )?;
+            snippets = stmt
+                .query_map([query], |row| Ok((row.get::<_, i32>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<HashMap<_, String>>>()?;
+        }
+
+        let terms = search_terms(query);
+        let hits = events
+            .0
+            .into_iter()
+            .map(|event| {
+                let snippet = snippets.remove(&event.id).unwrap_or_else(|| {
+                    let message = mark_terms(&event.message, &terms);
+                    if message.contains(SNIPPET_MARKS.0) {
+                        message
+                    } else {
+                        mark_terms(&event.name, &terms)
+                    }
+                });
+                SearchHit { event, snippet }
+            })
+            .collect();
+
+        Ok(SearchHits(hits))
+    }
+
+    /// `full_text_search` for databases without the FTS5 index: events
+    /// whose name or message holds every word of `query`, by date.
+    fn like_search(&self, query: &str) -> Result<EventList, NotifyMeError> {
+        let terms = search_terms(query);
+        if terms.is_empty() {
+            return Ok(EventList(Vec::new()));
+        }
+
+        let conditions = (2..terms.len() + 2)
+            .map(|n| {
+                format!(
+                    "(name LIKE ?{0} ESCAPE '\\' OR message LIKE ?{0} ESCAPE '\\')",
+                    n
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let mut stmt = self.conn().prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE deleted_at IS NULL AND (?1 IS NULL OR calendar = ?1) AND {} \
+             ORDER BY date",
+            EVENT_COLUMNS, conditions
+        ))?;
+
+        let params = std::iter::once(self.calendar.clone()).chain(
+            terms
+                .iter()
+                .map(|term| Some(format!("%{}%", escape_like(term)))),
+        );
+        let events = stmt
+            .query_map(rusqlite::params_from_iter(params), Event::try_from_row)
+            .and_then(partition_rows)
+            .map(warn_skipped)?;
+
+        Ok(EventList(events))
+    }
+
+    /// The link of event `id`, for `notify_me open`.
+    pub fn event_url(&self, id: i32) -> Result<String, NotifyMeError> {
+        match self.get_event(id)?.url {
+            Some(url) => Ok(url),
+            None => Err(format!("Event {} has no link", id).into()),
+        }
+    }
+
+    /// Non-deleted events whose date falls in the client's range. For
+    /// recurring events that is the next scheduled occurrence, not the date
+    /// they were first created for.
+    fn fetch_active_events(&self) -> Result<Vec<Event>, NotifyMeError> {
+        self.events.list(&self.filter()).map(warn_skipped)
+    }
+
+    /// The events carrying every one of `tags`, in the client's calendar
+    /// and range. No tags finds every event.
+    pub fn fetch_events_by_tag_intersection(
+        &self,
+        tags: &[&str],
+    ) -> Result<EventList, NotifyMeError> {
+        let filter = EventFilter {
+            tags: parse_tags(&tags.join(",")),
+            any_tag: false,
+            ..self.filter()
+        };
+
+        Ok(EventList(self.events.list(&filter).map(warn_skipped)?))
+    }
+
+    /// How the events here, trash included, differ from those in `other`,
+    /// another notify_me database at the same schema version.
+    pub fn diff_database(&self, other: &Connection) -> Result<EventDiff, NotifyMeError> {
+        let all = EventFilter {
+            include_deleted: true,
+            ..EventFilter::default()
+        };
+        let ours = self.events.list(&all).map(warn_skipped)?;
+        let theirs = EventRepository::new(other).list(&all).map(warn_skipped)?;
+
+        Ok(diff::diff_events(ours, theirs))
+    }
+
+    /// How many events `list_events` would list.
+    pub fn count_events(&self) -> Result<usize, NotifyMeError> {
+        self.events.count(&self.filter())
+    }
+
+    pub fn list_events(&self) -> Result<EventList, NotifyMeError> {
+        Ok(EventList(self.fetch_active_events()?))
+    }
+}
+
+/// Tells the user about events left out of a listing because their rows
+/// couldn't be read; `db check` lists them.
+fn warn_skipped((events, skipped): (Vec<Event>, Vec<SkippedRow>)) -> Vec<Event> {
+    if !skipped.is_empty() {
+        eprintln!(
+            "Warning: skipped {} unreadable events, run `{} db check` for details",
+            skipped.len(),
+            env!("CARGO_PKG_NAME")
+        );
+    }
+
+    events
+}
+
+/// Parses a `dd/mm/yyyy` day, as taken by `--since` and `--until`.
+pub fn parse_day(input: &str) -> Result<NaiveDate, NotifyMeError> {
+    match NaiveDate::parse_from_str(input.trim(), "%d/%m/%Y") {
+        Ok(day) => Ok(day),
+        Err(_) => Err(NotifyMeError::DateParse(
+            "Invalid date format. Please use 'dd/mm/yyyy'".to_string(),
+        )),
+    }
+}
+
+/// Reads a month given as `yyyy-mm`.
+pub fn parse_month(input: &str) -> Result<(i32, u32), NotifyMeError> {
+    match NaiveDate::parse_from_str(&format!("{}-01", input.trim()), "%Y-%m-%d") {
+        Ok(first) => Ok((first.year(), first.month())),
+        Err(_) => Err(NotifyMeError::DateParse(
+            "Invalid month format. Please use 'yyyy-mm'".to_string(),
+        )),
+    }
+}
+
+/// The UTC bounds covering local days `since` through `until` inclusive.
+/// An open start reaches back to the epoch and an open end to the year 9999,
+/// which still compares correctly as stored text.
+pub fn date_range(
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), NotifyMeError> {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(format!(
+                "--since {} is after --until {}",
+                since.format("%d/%m/%Y"),
+                until.format("%d/%m/%Y")
+            )
+            .into());
+        }
+    }
+
+    let start = match since {
+        Some(since) => local_day_bounds(since).0,
+        None => unbounded_range().0,
+    };
+    let end = match until {
+        Some(until) => local_day_bounds(until).1,
+        None => unbounded_range().1,
+    };
+
+    Ok((start, end))
+}
+
+/// Parses a `DATE_FORMAT` date, optionally followed by an IANA time zone as
+/// in "25/12/2025 15:00 Europe/Berlin". The time is wall-clock time in that
+/// zone, else in `zone`, else local. Returns the zone used, if any.
+pub fn parse_date(
+    input: &str,
+    zone: Option<Zone>,
+) -> Result<(DateTime<Utc>, Option<Zone>), NotifyMeError> {
+    let input = input.trim();
+    let (date, zone) = match input.split_whitespace().collect::<Vec<_>>()[..] {
+        [day, time, name] => (format!("{} {}", day, time), Some(Zone::load(name)?)),
+        _ => (input.to_string(), zone),
+    };
+
+    let naive_date = match NaiveDateTime::parse_from_str(&date, DATE_FORMAT) {
+        Ok(naive_date) => naive_date,
+        Err(_) => {
+            return Err(NotifyMeError::DateParse(
+                "Invalid date format. Please use 'dd/mm/yyyy hh:mm', optionally followed by a time zone"
+                    .to_string(),
+            ))
+        }
+    };
+
+    let date = match &zone {
+        Some(zone) => resolve_local(zone.from_local_datetime(&naive_date), &date)?,
+        None => resolve_local(Local.from_local_datetime(&naive_date), &date)?,
+    };
+
+    Ok((date, zone))
+}
+
+/// The one instant a wall-clock time names. Times a DST change repeats or
+/// skips are rejected rather than guessed at, so the prompt asks again.
+fn resolve_local<Tz: TimeZone>(
+    result: LocalResult<DateTime<Tz>>,
+    input: &str,
+) -> Result<DateTime<Utc>, NotifyMeError> {
+    match result {
+        LocalResult::Single(date) => Ok(date.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earliest, latest) => Err(format!(
+            "Ambiguous local time {} (DST transition): it occurs at both {} and {}. \
+             Please re-enter or use a time outside the repeated hour.",
+            input,
+            earliest.fixed_offset().format("%H:%M %:z"),
+            latest.fixed_offset().format("%H:%M %:z")
+        )
+        .into()),
+        LocalResult::None => Err(format!(
+            "{} doesn't exist in local time, the clocks skip it (DST transition). \
+             Please use a time after the change.",
+            input
+        )
+        .into()),
+    }
+}
+
+/// How long ago an event was due in its largest whole unit, e.g. `2 days
+/// overdue`.
+fn overdue_age(overdue: Duration) -> String {
+    let (count, unit) = match (overdue.num_days(), overdue.num_hours()) {
+        (0, 0) => (overdue.num_minutes(), "minute"),
+        (0, hours) => (hours, "hour"),
+        (days, _) => (days, "day"),
+    };
+
+    format!(
+        "{} {}{} overdue",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// Renders a duration compactly using its two most significant units, e.g.
+/// `2d 3h`, `1h 23m`, `45m` or `30s`.
+pub fn format_duration_human(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (days, hours, minutes) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+    );
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, minutes) => format!("{}m", minutes),
+        (0, hours, 0) => format!("{}h", hours),
+        (0, hours, minutes) => format!("{}h {}m", hours, minutes),
+        (days, 0, _) => format!("{}d", days),
+        (days, hours, _) => format!("{}d {}h", days, hours),
+    }
+}
+
+/// An ASCII progress bar such as `[=====>    ]` for a fraction in 0..=1.
+fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64) as usize).min(width);
+
+    let bar = match filled {
+        0 => " ".repeat(width),
+        filled if filled == width => "=".repeat(width),
+        filled => format!("{}>{}", "=".repeat(filled - 1), " ".repeat(width - filled)),
+    };
+
+    format!("[{}]", bar)
+}
+
+/// Parses ages such as `30d`, `12h` or `2w`.
+pub fn parse_age(input: &str) -> Result<Duration, NotifyMeError> {
+    let input = input.trim();
+    let (amount, unit) = input.split_at(input.len().saturating_sub(1));
+
+    let amount = match amount.parse::<i64>() {
+        Ok(amount) if amount >= 0 => amount,
+        _ => return Err(format!("Invalid age '{}'. Use e.g. 30d, 12h or 2w", input).into()),
+    };
+
+    match unit {
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => Err(format!("Invalid age '{}'. Use e.g. 30d, 12h or 2w", input).into()),
+    }
+}
+
+fn query_ids<P: Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Vec<i32>, NotifyMeError> {
+    let mut stmt = conn.prepare(sql)?;
+
+    let ids = stmt
+        .query_map(params, |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i32>>>()?;
+
+    Ok(ids)
+}
+
+/// Wraps a value in single quotes for POSIX `sh`, escaping embedded quotes.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Turns Esc or q on a `Select` or `Confirm` prompt, which `interact_opt`
+/// reports as `None`, into `NotifyMeError::Cancelled` like Ctrl-C.
+trait OrCancel<T> {
+    fn or_cancel(self) -> Result<T, NotifyMeError>;
+}
+
+impl<T> OrCancel<T> for dialoguer::Result<Option<T>> {
+    fn or_cancel(self) -> Result<T, NotifyMeError> {
+        self?.ok_or(NotifyMeError::Cancelled)
+    }
+}
+
+/// Ignores SIGINT while alive. console re-raises a Ctrl-C typed at a
+/// prompt as SIGINT, which would kill the process mid-prompt with the cursor
+/// hidden; ignored, it surfaces as an interrupted read, i.e. `Cancelled`.
+struct IgnoreSigint {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl IgnoreSigint {
+    fn new() -> Self {
+        Self {
+            // SAFETY: swaps in a disposition, no handler code runs.
+            #[cfg(unix)]
+            previous: unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) },
+        }
+    }
+}
+
+impl Drop for IgnoreSigint {
+    fn drop(&mut self) {
+        // SAFETY: puts back the disposition `new` replaced.
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+/// Shows the cursor again, which prompts hide while they run and Ctrl-C
+/// leaves hidden, and clears what is left of an interrupted prompt.
+fn restore_terminal() -> Result<(), NotifyMeError> {
+    let term = Term::stderr();
+    term.show_cursor()?;
+    term.clear_line()?;
+
+    Ok(())
+}
+
+/// Asks how an event repeats, following up on the interval of every-N-weeks
+/// events and the day of nth-weekday ones.
+fn select_recurrence() -> Result<RecurrencePattern, NotifyMeError> {
+    let recurrences = [
+        RecurrencePattern::Once.to_string(),
+        RecurrencePattern::Daily.to_string(),
+        RecurrencePattern::Weekly.to_string(),
+        RecurrencePattern::Monthly.to_string(),
+        "every n weeks".to_string(),
+        "nth weekday of the month".to_string(),
+    ];
+
+    let recurrence = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Choose a recurrence")
+        .default(0)
+        .items(&recurrences[..])
+        .interact_opt()
+        .or_cancel()?;
+
+    match recurrence {
+        4 => {
+            let weeks: u32 = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Every how many weeks")
+                .validate_with(|weeks: &u32| -> Result<(), String> {
+                    if (1..=MAX_WEEKS).contains(weeks) {
+                        Ok(())
+                    } else {
+                        Err(format!("Enter 1 to {} weeks", MAX_WEEKS))
+                    }
+                })
+                .interact_text()?;
+
+            println!("Every {} weeks", weeks);
+            Ok(RecurrencePattern::EveryNWeeks(weeks))
+        }
+        5 => {
+            let weekday = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Which weekday?")
+                .default(0)
+                .items(&WEEKDAYS.map(weekday_name))
+                .interact_opt()
+                .or_cancel()?;
+            let positions = (1..=LAST_WEEKDAY).map(nth_name).collect::<Vec<_>>();
+            let nth = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Which occurrence?")
+                .default(0)
+                .items(&positions)
+                .interact_opt()
+                .or_cancel()?;
+
+            let pattern =
+                RecurrencePattern::EveryNthWeekdayOfMonth(WEEKDAYS[weekday], nth as u8 + 1);
+            println!("Repeats {}", pattern);
+            Ok(pattern)
+        }
+        _ => Ok(RecurrencePattern::from(recurrences[recurrence].as_str())),
+    }
+}
+
+fn select_priority() -> Result<Priority, NotifyMeError> {
+    let priorities = [Priority::Normal, Priority::High, Priority::Low];
+
+    let priority = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Priority")
+        .default(0)
+        .items(&priorities.map(<&str>::from))
+        .interact_opt()
+        .or_cancel()?;
+
+    Ok(priorities[priority])
+}
+
+/// A date in a CSV import: RFC 3339, or `dd/mm/yyyy hh:mm` in local time as
+/// spreadsheets tend to write them.
+fn parse_csv_date(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    let invalid = || {
+        format!(
+            "invalid date '{}', expected RFC 3339 or dd/mm/yyyy hh:mm",
+            value
+        )
+    };
+    let naive = NaiveDateTime::parse_from_str(value, "%d/%m/%Y %H:%M").map_err(|_| invalid())?;
+    match Local.from_local_datetime(&naive).earliest() {
+        Some(local) => Ok(local.with_timezone(&Utc)),
+        None => Err(format!("{} does not exist locally", value)),
+    }
 }
@@ -0,0 +1,55 @@
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+
+/// Where the current time comes from. Everything that compares against
+/// "now" asks a clock instead of the system, so tests can pin it.
+pub trait Clock: Send + Sync {
+    fn now_utc(&self) -> DateTime<Utc>;
+
+    fn now_local(&self) -> DateTime<Local> {
+        self.now_utc().with_timezone(&Local)
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.now_local().date_naive()
+    }
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that stays where it is put until moved with `set` or `advance`.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,440 @@
+use std::fmt::Write;
+
+use clap::{builder::PossibleValue, Arg, Command, ValueEnum};
+
+/// Shells `completions` can write a script for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+/// Subcommand printing `<id>\t<name>` for each event, which the scripts
+/// call to complete event ids.
+pub const COMPLETE_IDS_COMMAND: &str = "__complete-ids";
+
+/// Value name marking the arguments that take an event id.
+const ID_VALUE_NAME: &str = "ID|UUID";
+
+impl ValueEnum for Shell {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Shell::Bash => PossibleValue::new("bash"),
+            Shell::Zsh => PossibleValue::new("zsh"),
+            Shell::Fish => PossibleValue::new("fish"),
+            Shell::PowerShell => PossibleValue::new("powershell").alias("pwsh"),
+        })
+    }
+}
+
+/// The completion script for `cmd`, installed as `bin`.
+pub fn generate(shell: Shell, cmd: &mut Command, bin: &str) -> String {
+    cmd.build();
+    let mut nodes = Vec::new();
+    collect(cmd, Vec::new(), &mut nodes);
+
+    match shell {
+        Shell::Bash => bash(&nodes, bin),
+        Shell::Zsh => format!(
+            "#compdef {}\n\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+            bin,
+            bash(&nodes, bin)
+        ),
+        Shell::Fish => fish(&nodes, bin),
+        Shell::PowerShell => powershell(&nodes, bin),
+    }
+}
+
+/// A command or subcommand, with what can follow it on the command line.
+struct Node {
+    /// Subcommand names from the top level down; empty for the command.
+    path: Vec<String>,
+    subcommands: Vec<(String, String)>,
+    options: Vec<Opt>,
+    /// Values the positional arguments take, when they are a fixed set.
+    values: Vec<String>,
+    /// Whether a positional argument is an event id.
+    ids: bool,
+}
+
+struct Opt {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    takes_value: bool,
+    values: Vec<String>,
+    ids: bool,
+}
+
+fn collect(cmd: &Command, path: Vec<String>, nodes: &mut Vec<Node>) {
+    let mut node = Node {
+        path: path.clone(),
+        subcommands: Vec::new(),
+        options: Vec::new(),
+        values: Vec::new(),
+        ids: false,
+    };
+
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        if arg.is_positional() {
+            node.values.extend(possible_values(arg));
+            node.ids |= takes_id(arg);
+        } else {
+            node.options.push(Opt {
+                long: arg.get_long().map(str::to_string),
+                short: arg.get_short(),
+                help: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+                takes_value: arg.get_action().takes_values(),
+                values: possible_values(arg),
+                ids: takes_id(arg),
+            });
+        }
+    }
+
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let about = sub.get_about().map(ToString::to_string).unwrap_or_default();
+        node.subcommands.push((sub.get_name().to_string(), about));
+    }
+    nodes.push(node);
+
+    // clap's own `help` subcommand mirrors the whole tree; completing its
+    // first level is enough.
+    for sub in cmd
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+    {
+        let mut path = path.clone();
+        path.push(sub.get_name().to_string());
+        collect(sub, path, nodes);
+    }
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn takes_id(arg: &Arg) -> bool {
+    arg.get_value_names()
+        .is_some_and(|names| names.iter().any(|name| name == ID_VALUE_NAME))
+}
+
+/// Identifier for a node in the generated scripts, e.g. `notify-me__db__check`.
+fn key(bin: &str, path: &[String]) -> String {
+    std::iter::once(bin)
+        .chain(path.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join("__")
+}
+
+fn bash(nodes: &[Node], bin: &str) -> String {
+    let function = format!("_{}", bin.replace('-', "_"));
+    let ids = format!(
+        "$(\"${{COMP_WORDS[0]}}\" {} 2>/dev/null | cut -f1)",
+        COMPLETE_IDS_COMMAND
+    );
+    let mut script = String::new();
+
+    writeln!(script, "{}() {{", function).unwrap();
+    writeln!(script, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(script, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"").unwrap();
+    writeln!(script, "    local cmd=\"{}\" i", bin).unwrap();
+    writeln!(script, "    COMPREPLY=()").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "    for ((i = 1; i < COMP_CWORD; i++)); do").unwrap();
+    writeln!(script, "        case \"${{cmd}},${{COMP_WORDS[i]}}\" in").unwrap();
+    for node in nodes {
+        for (name, _) in &node.subcommands {
+            let mut child = node.path.clone();
+            child.push(name.clone());
+            writeln!(
+                script,
+                "            \"{},{}\") cmd=\"{}\" ;;",
+                key(bin, &node.path),
+                name,
+                key(bin, &child)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(script, "        esac").unwrap();
+    writeln!(script, "    done").unwrap();
+    writeln!(script).unwrap();
+
+    writeln!(script, "    case \"${{cmd}},${{prev}}\" in").unwrap();
+    for node in nodes {
+        for opt in node.options.iter().filter(|opt| opt.takes_value) {
+            let words = if opt.ids {
+                ids.clone()
+            } else if !opt.values.is_empty() {
+                opt.values.join(" ")
+            } else {
+                continue;
+            };
+            let flags = opt
+                .long
+                .iter()
+                .map(|long| format!("\"{},--{}\"", key(bin, &node.path), long))
+                .chain(
+                    opt.short
+                        .iter()
+                        .map(|short| format!("\"{},-{}\"", key(bin, &node.path), short)),
+                )
+                .collect::<Vec<_>>()
+                .join("|");
+            writeln!(
+                script,
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\")); return ;;",
+                flags, words
+            )
+            .unwrap();
+        }
+    }
+    writeln!(script, "    esac").unwrap();
+    writeln!(script).unwrap();
+
+    writeln!(script, "    local words").unwrap();
+    writeln!(script, "    case \"${{cmd}}\" in").unwrap();
+    for node in nodes {
+        let mut words = node
+            .subcommands
+            .iter()
+            .map(|(name, _)| name.clone())
+            .chain(node.values.iter().cloned())
+            .collect::<Vec<_>>();
+        if node.ids {
+            words.push(ids.clone());
+        }
+        for opt in &node.options {
+            words.extend(opt.long.iter().map(|long| format!("--{}", long)));
+            words.extend(opt.short.iter().map(|short| format!("-{}", short)));
+        }
+        writeln!(
+            script,
+            "        {}) words=\"{}\" ;;",
+            key(bin, &node.path),
+            words.join(" ")
+        )
+        .unwrap();
+    }
+    writeln!(script, "    esac").unwrap();
+    writeln!(
+        script,
+        "    COMPREPLY=($(compgen -W \"${{words}}\" -- \"${{cur}}\"))"
+    )
+    .unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "complete -o default -F {} {}", function, bin).unwrap();
+
+    script
+}
+
+fn fish(nodes: &[Node], bin: &str) -> String {
+    let mut script = String::new();
+    let ids = format!("({} {})", bin, COMPLETE_IDS_COMMAND);
+
+    for node in nodes {
+        // fish only knows which subcommands have been typed, not their
+        // nesting, which is enough for a tree this shallow.
+        let condition = match node.path.last() {
+            None => "__fish_use_subcommand".to_string(),
+            Some(name) => {
+                let children = node
+                    .subcommands
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>();
+                if children.is_empty() {
+                    format!("__fish_seen_subcommand_from {}", name)
+                } else {
+                    format!(
+                        "__fish_seen_subcommand_from {}; and not __fish_seen_subcommand_from {}",
+                        name,
+                        children.join(" ")
+                    )
+                }
+            }
+        };
+
+        for (name, about) in &node.subcommands {
+            writeln!(
+                script,
+                "complete -c {} -n '{}' -f -a {} -d '{}'",
+                bin,
+                condition,
+                name,
+                fish_escape(about)
+            )
+            .unwrap();
+        }
+        if node.ids {
+            writeln!(
+                script,
+                "complete -c {} -n '{}' -f -a '{}'",
+                bin, condition, ids
+            )
+            .unwrap();
+        }
+        if !node.values.is_empty() {
+            writeln!(
+                script,
+                "complete -c {} -n '{}' -f -a '{}'",
+                bin,
+                condition,
+                node.values.join(" ")
+            )
+            .unwrap();
+        }
+
+        for opt in &node.options {
+            let mut line = format!("complete -c {} -n '{}'", bin, condition);
+            if let Some(long) = &opt.long {
+                write!(line, " -l {}", long).unwrap();
+            }
+            if let Some(short) = opt.short {
+                write!(line, " -s {}", short).unwrap();
+            }
+            if opt.ids {
+                write!(line, " -x -a '{}'", ids).unwrap();
+            } else if !opt.values.is_empty() {
+                write!(line, " -x -a '{}'", opt.values.join(" ")).unwrap();
+            } else if opt.takes_value {
+                line.push_str(" -r");
+            }
+            write!(line, " -d '{}'", fish_escape(&opt.help)).unwrap();
+            writeln!(script, "{}", line).unwrap();
+        }
+    }
+
+    script
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn powershell(nodes: &[Node], bin: &str) -> String {
+    let mut script = String::new();
+
+    writeln!(script, "using namespace System.Management.Automation").unwrap();
+    writeln!(script).unwrap();
+    writeln!(
+        script,
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "    param($wordToComplete, $commandAst, $cursorPosition)"
+    )
+    .unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "    $cmd = '{}'", bin).unwrap();
+    writeln!(
+        script,
+        "    foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {{"
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "        if ($element.Extent.StartOffset -ge $cursorPosition) {{ break }}"
+    )
+    .unwrap();
+    writeln!(script, "        switch (\"$cmd,$element\") {{").unwrap();
+    for node in nodes {
+        for (name, _) in &node.subcommands {
+            let mut child = node.path.clone();
+            child.push(name.clone());
+            writeln!(
+                script,
+                "            '{},{}' {{ $cmd = '{}' }}",
+                key(bin, &node.path),
+                name,
+                key(bin, &child)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(script, "        }}").unwrap();
+    writeln!(script, "    }}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "    $results = switch ($cmd) {{").unwrap();
+    for node in nodes {
+        writeln!(script, "        '{}' {{", key(bin, &node.path)).unwrap();
+        for (name, about) in &node.subcommands {
+            writeln!(
+                script,
+                "            [CompletionResult]::new('{}', '{}', 'ParameterValue', '{}')",
+                name,
+                name,
+                powershell_escape(about)
+            )
+            .unwrap();
+        }
+        for value in &node.values {
+            writeln!(
+                script,
+                "            [CompletionResult]::new('{}', '{}', 'ParameterValue', '{}')",
+                value, value, value
+            )
+            .unwrap();
+        }
+        if node.ids {
+            writeln!(
+                script,
+                "            & '{}' {} | ForEach-Object {{ $id, $name = $_ -split \"`t\", 2; \
+                 [CompletionResult]::new($id, $id, 'ParameterValue', $name) }}",
+                bin, COMPLETE_IDS_COMMAND
+            )
+            .unwrap();
+        }
+        for opt in &node.options {
+            let flags = opt
+                .long
+                .iter()
+                .map(|long| format!("--{}", long))
+                .chain(opt.short.iter().map(|short| format!("-{}", short)));
+            for flag in flags {
+                writeln!(
+                    script,
+                    "            [CompletionResult]::new('{}', '{}', 'ParameterName', '{}')",
+                    flag,
+                    flag,
+                    powershell_escape(&opt.help)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(script, "        }}").unwrap();
+    }
+    writeln!(script, "    }}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(
+        script,
+        "    $results | Where-Object {{ $_.CompletionText -like \"$wordToComplete*\" }}"
+    )
+    .unwrap();
+    writeln!(script, "}}").unwrap();
+
+    script
+}
+
+fn powershell_escape(text: &str) -> String {
+    let text = text.replace('\'', "''");
+    if text.is_empty() {
+        " ".to_string()
+    } else {
+        text
+    }
+}
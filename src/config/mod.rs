@@ -0,0 +1,325 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{DocumentMut, Item, Value};
+
+#[derive(Debug)]
+pub struct Config {
+    pub db_path: PathBuf,
+    /// Deleted events older than this many days are purged by the scheduler.
+    /// `None` keeps the trash forever.
+    pub trash_retention_days: Option<u32>,
+    /// Where `backup` writes snapshots. Defaults to `backups/` next to the
+    /// database.
+    pub backup_dir: Option<PathBuf>,
+    /// How many snapshots to keep in the backup directory. `None` keeps all.
+    pub backup_retention: Option<u32>,
+    /// How many automatic snapshots taken before destructive operations to
+    /// keep.
+    pub pre_op_backup_retention: u32,
+    /// Where to append log lines as well as stderr. The scheduler's daemon
+    /// mode logs to `notify_me.log` under `default_data_dir` when unset.
+    pub log_file: Option<PathBuf>,
+    /// Whether running with no subcommand outside a terminal, which prints
+    /// today's events, fails when there are none.
+    pub fail_on_empty_today: bool,
+    /// The file the settings were read from, if there was one.
+    pub path: Option<PathBuf>,
+    /// Settings the file gave a value, for `source`.
+    from_file: Vec<String>,
+}
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Default,
+    File,
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Default => "default",
+            Source::File => "from config file",
+            Source::CommandLine => "from command line",
+        })
+    }
+}
+
+/// A resolved setting as `--list-config` prints it: a TOML line commented
+/// with where the value came from. Unset optional settings are commented
+/// out.
+pub struct Setting {
+    pub key: &'static str,
+    pub value: Option<Value>,
+    pub source: Source,
+}
+
+impl Setting {
+    pub fn new(key: &'static str, value: Option<impl Into<Value>>, source: Source) -> Self {
+        Self {
+            key,
+            value: value.map(Into::into),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{} = {}  # {}", self.key, value, self.source),
+            None => write!(f, "# {} is unset  # {}", self.key, self.source),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            db_path: PathBuf::from("notify_me.db"),
+            trash_retention_days: None,
+            backup_dir: None,
+            backup_retention: None,
+            pre_op_backup_retention: 10,
+            log_file: None,
+            fail_on_empty_today: false,
+            path: None,
+            from_file: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file at `path`, or at the default location when no
+    /// path is given. A missing default file just means default settings.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => match Config::parse(&contents) {
+                Ok(config) => Ok(Config {
+                    path: Some(path),
+                    ..config
+                }),
+                Err(err) => Err(format!("{}: {}", path.display(), err)),
+            },
+            Err(err) if required || err.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("{}: {}", path.display(), err))
+            }
+            Err(_) => Ok(Config::default()),
+        }
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        match &self.backup_dir {
+            Some(dir) => dir.clone(),
+            None => self
+                .db_path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join("backups"),
+        }
+    }
+
+    pub fn source(&self, key: &str) -> Source {
+        if self.from_file.iter().any(|set| set == key) {
+            Source::File
+        } else {
+            Source::Default
+        }
+    }
+
+    /// Every setting with its resolved value.
+    pub fn settings(&self) -> Vec<Setting> {
+        let path = |path: &Path| path.display().to_string();
+        let count = |count: u32| i64::from(count);
+
+        vec![
+            Setting::new("db_path", Some(path(&self.db_path)), self.source("db_path")),
+            Setting::new(
+                "trash_retention_days",
+                self.trash_retention_days.map(count),
+                self.source("trash_retention_days"),
+            ),
+            Setting::new(
+                "backup_dir",
+                Some(path(&self.backup_dir())),
+                self.source("backup_dir"),
+            ),
+            Setting::new(
+                "backup_retention",
+                self.backup_retention.map(count),
+                self.source("backup_retention"),
+            ),
+            Setting::new(
+                "pre_op_backup_retention",
+                Some(count(self.pre_op_backup_retention)),
+                self.source("pre_op_backup_retention"),
+            ),
+            Setting::new(
+                "log_file",
+                self.log_file.as_deref().map(path),
+                self.source("log_file"),
+            ),
+            Setting::new(
+                "fail_on_empty_today",
+                Some(self.fail_on_empty_today),
+                self.source("fail_on_empty_today"),
+            ),
+        ]
+    }
+
+    /// Where snapshots taken automatically before destructive operations go.
+    pub fn pre_op_backup_dir(&self) -> PathBuf {
+        self.backup_dir().join("pre-op")
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let doc = match contents.parse::<DocumentMut>() {
+            Ok(doc) => doc,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let mut config = Config::default();
+
+        for (key, item) in doc.iter() {
+            match key {
+                "db_path" => config.db_path = PathBuf::from(string(key, item)?),
+                "trash_retention_days" => config.trash_retention_days = Some(unsigned(key, item)?),
+                "backup_dir" => config.backup_dir = Some(PathBuf::from(string(key, item)?)),
+                "backup_retention" => config.backup_retention = Some(unsigned(key, item)?),
+                "pre_op_backup_retention" => config.pre_op_backup_retention = unsigned(key, item)?,
+                "log_file" => config.log_file = Some(PathBuf::from(string(key, item)?)),
+                "fail_on_empty_today" => config.fail_on_empty_today = boolean(key, item)?,
+                _ => return Err(format!("unknown setting '{}'", key)),
+            }
+            config.from_file.push(key.to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+/// What each setting does, and a value to show for those left unset.
+/// Every key `Config::settings` lists needs an entry.
+const SETTING_DOCS: &[(&str, &str, &str)] = &[
+    (
+        "db_path",
+        "Database file. Relative paths are resolved from the working directory.",
+        "\"notify_me.db\"",
+    ),
+    (
+        "trash_retention_days",
+        "Days deleted events stay in the trash before the scheduler purges\nthem. Unset, the trash is kept forever.",
+        "30",
+    ),
+    (
+        "backup_dir",
+        "Where `backup` writes snapshots. Unset, `backups/` next to the database.",
+        "\"/var/backups/notify_me\"",
+    ),
+    (
+        "backup_retention",
+        "How many snapshots to keep in the backup directory. Unset, all of them.",
+        "7",
+    ),
+    (
+        "pre_op_backup_retention",
+        "How many snapshots taken before destructive operations to keep.",
+        "10",
+    ),
+    (
+        "log_file",
+        "File to append log lines to as well as stderr. Unset, the scheduler\nlogs to notify_me.log in the data directory.",
+        "\"/var/log/notify_me.log\"",
+    ),
+    (
+        "fail_on_empty_today",
+        "Whether running without a subcommand outside a terminal, which prints\ntoday's events, exits 1 when there are none.",
+        "false",
+    ),
+];
+
+/// A config file setting every option to its default, each with a comment
+/// saying what it does. Options without a fixed default are commented out.
+pub fn example() -> String {
+    let mut doc = DocumentMut::new();
+    let mut pending =
+        "# notify_me configuration. Settings left commented out are unset.\n".to_string();
+
+    for setting in Config::default().settings() {
+        let (_, help, example) = SETTING_DOCS
+            .iter()
+            .find(|(key, _, _)| *key == setting.key)
+            .unwrap();
+        let comment = help
+            .lines()
+            .map(|line| format!("# {}\n", line))
+            .collect::<String>();
+
+        // backup_dir's default follows db_path, so pinning it would break
+        // that when db_path changes.
+        match setting.value.filter(|_| setting.key != "backup_dir") {
+            Some(value) => {
+                doc.insert(setting.key, Item::Value(value));
+                doc.key_mut(setting.key)
+                    .unwrap()
+                    .leaf_decor_mut()
+                    .set_prefix(format!("{}\n{}", pending, comment));
+                pending.clear();
+            }
+            None => pending.push_str(&format!("\n{}# {} = {}\n", comment, setting.key, example)),
+        }
+    }
+    doc.set_trailing(pending);
+
+    doc.to_string()
+}
+
+/// `$XDG_CONFIG_HOME/notify_me/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_home.join("notify_me").join("config.toml"))
+}
+
+/// `$XDG_DATA_HOME/notify_me`, falling back to `~/.local/share`.
+pub fn default_data_dir() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+
+    Some(data_home.join("notify_me"))
+}
+
+fn string<'a>(key: &str, item: &'a Item) -> Result<&'a str, String> {
+    item.as_str()
+        .ok_or_else(|| format!("'{}' must be a string", key))
+}
+
+fn boolean(key: &str, item: &Item) -> Result<bool, String> {
+    item.as_bool()
+        .ok_or_else(|| format!("'{}' must be true or false", key))
+}
+
+fn unsigned(key: &str, item: &Item) -> Result<u32, String> {
+    item.as_integer()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| format!("'{}' must be a non-negative integer", key))
+}
@@ -0,0 +1,97 @@
+use std::{iter::Peekable, str::Chars};
+
+/// One record of a CSV file, with the line it starts on for error messages.
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    /// The record's fields, or why it couldn't be read.
+    pub fields: Result<Vec<String>, String>,
+}
+
+/// Splits `input` into records as RFC 4180 describes them: comma-separated
+/// fields, which may be double-quoted to hold commas, line breaks and `""`
+/// for a quote. A malformed record is reported and reading resumes on the
+/// next line, so one bad row doesn't lose the rest of the file. Blank lines
+/// are left out.
+pub fn parse(input: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let fields = record(&mut chars, &mut line);
+
+        if fields.as_ref().is_ok_and(|fields| is_blank(fields)) {
+            continue;
+        }
+        records.push(Record {
+            line: start,
+            fields,
+        });
+    }
+
+    records
+}
+
+/// Reads one record up to and including its line break.
+fn record(chars: &mut Peekable<Chars>, line: &mut usize) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    loop {
+        let Some(c) = chars.next() else {
+            if quoted {
+                return Err("unterminated quoted field".to_string());
+            }
+            fields.push(field);
+            return Ok(fields);
+        };
+
+        match c {
+            '"' if quoted => match chars.peek() {
+                Some('"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                None | Some(',' | '\r' | '\n') => quoted = false,
+                Some(_) => {
+                    skip_line(chars, line);
+                    return Err("unexpected text after a closing quote".to_string());
+                }
+            },
+            '"' if field.is_empty() => quoted = true,
+            '"' => {
+                skip_line(chars, line);
+                return Err("quote in the middle of an unquoted field".to_string());
+            }
+            '\n' if quoted => {
+                *line += 1;
+                field.push(c);
+            }
+            _ if quoted => field.push(c),
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                *line += 1;
+                fields.push(field);
+                return Ok(fields);
+            }
+            _ => field.push(c),
+        }
+    }
+}
+
+fn skip_line(chars: &mut Peekable<Chars>, line: &mut usize) {
+    for c in chars.by_ref() {
+        if c == '\n' {
+            *line += 1;
+            return;
+        }
+    }
+}
+
+fn is_blank(fields: &[String]) -> bool {
+    fields.iter().all(|field| field.trim().is_empty())
+}
@@ -0,0 +1,838 @@
+use std::{fs, io::Read, path::Path, time::Duration};
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+#[cfg(feature = "encryption")]
+use rusqlite::DatabaseName;
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use tracing::{info, warn};
+
+use crate::{
+    error::NotifyMeError,
+    event::{parse_stored_date, parse_tags, partition_rows, Event, SkippedRow, EVENT_COLUMNS},
+};
+
+/// Environment variable holding the key of an encrypted database.
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+pub const KEY_ENV: &str = "NOTIFY_ME_DB_KEY";
+
+/// First bytes of every plaintext SQLite database.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// How long a connection waits on a lock held by the other process (the
+/// scheduler daemon or the interactive client) before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// A schema migration, as `migrate` and `pending_migrations` report it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub description: &'static str,
+}
+
+impl From<&Migration> for MigrationInfo {
+    fn from(migration: &Migration) -> Self {
+        Self {
+            version: migration.version,
+            description: migration.description,
+        }
+    }
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create events table",
+        up: create_events_table,
+    },
+    Migration {
+        version: 2,
+        description: "normalize dates to UTC and index them",
+        up: index_event_dates,
+    },
+    Migration {
+        version: 3,
+        description: "track when each event was last notified",
+        up: add_last_notified_at,
+    },
+    Migration {
+        version: 4,
+        description: "add created_at and updated_at to events",
+        up: add_event_timestamps,
+    },
+    Migration {
+        version: 5,
+        description: "add tags to events",
+        up: add_event_tags,
+    },
+    Migration {
+        version: 6,
+        description: "create meta table",
+        up: create_meta_table,
+    },
+    Migration {
+        version: 7,
+        description: "give events a uuid",
+        up: add_event_uuids,
+    },
+    Migration {
+        version: 8,
+        description: "create completed_events table",
+        up: create_completed_events_table,
+    },
+    Migration {
+        version: 9,
+        description: "add calendars to events",
+        up: add_event_calendars,
+    },
+    Migration {
+        version: 10,
+        description: "move tags into their own tables",
+        up: normalize_tags,
+    },
+    Migration {
+        version: 11,
+        description: "add priority to events",
+        up: add_event_priority,
+    },
+    Migration {
+        version: 12,
+        description: "add location to events",
+        up: add_event_location,
+    },
+    Migration {
+        version: 13,
+        description: "add url to events",
+        up: add_event_url,
+    },
+    Migration {
+        version: 14,
+        description: "add color labels to events",
+        up: add_event_color,
+    },
+    Migration {
+        version: 15,
+        description: "add paused flag to events",
+        up: add_event_paused,
+    },
+    Migration {
+        version: 16,
+        description: "add time zone to events",
+        up: add_event_timezone,
+    },
+    Migration {
+        version: 17,
+        description: "index event names and messages for full-text search",
+        up: create_events_fts,
+    },
+    Migration {
+        version: 18,
+        description: "track notifications held back by quiet hours",
+        up: add_event_last_deferred_at,
+    },
+];
+
+/// Calendar of events created without one.
+pub const DEFAULT_CALENDAR: &str = "default";
+
+/// SQL expression for a random (version 4) UUID in its usual hyphenated
+/// lowercase form.
+const UUID_SQL: &str = "lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) \
+    || '-4' || substr(lower(hex(randomblob(2))), 2) \
+    || '-' || substr('89ab', 1 + abs(random()) % 4, 1) || substr(lower(hex(randomblob(2))), 2) \
+    || '-' || lower(hex(randomblob(6)))";
+
+/// Opens the database and brings its schema up to date.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Connection, NotifyMeError> {
+    let mut conn = connect(path)?;
+    migrate(&mut conn)?;
+
+    Ok(conn)
+}
+
+/// Opens the database without running migrations, for callers that need to
+/// act (e.g. take a backup) before the schema changes.
+pub fn connect<P: AsRef<Path>>(path: P) -> Result<Connection, NotifyMeError> {
+    let path = path.as_ref();
+    let conn =
+        Connection::open(path).map_err(|err| NotifyMeError::DatabaseAccess(err.to_string()))?;
+
+    if is_encrypted(path) {
+        unlock(&conn, path)?;
+    }
+
+    configure(&conn)?;
+
+    Ok(conn)
+}
+
+/// Whether `path` holds something other than a plain SQLite database, which
+/// for us means a SQLCipher one. A missing or empty file is a new database.
+fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0; 16];
+    match fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Applies the key to an encrypted database before anything else touches
+/// it. The key comes from `KEY_ENV` or a prompt and is never stored.
+#[cfg(feature = "encryption")]
+fn unlock(conn: &Connection, path: &Path) -> Result<(), NotifyMeError> {
+    let key = read_key(&format!("Key for {}", path.display()), false)?;
+    conn.pragma_update(None, "key", &key)?;
+
+    // SQLCipher only checks the key once a page is read.
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(NotifyMeError::DatabaseAccess(format!(
+            "Wrong key for {}",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+fn unlock(_conn: &Connection, path: &Path) -> Result<(), NotifyMeError> {
+    Err(NotifyMeError::DatabaseAccess(format!(
+        "{} is encrypted, but this build has no encryption support (rebuild with --features encryption)",
+        path.display()
+    )))
+}
+
+#[cfg(feature = "encryption")]
+fn read_key(prompt: &str, confirm: bool) -> Result<String, NotifyMeError> {
+    if let Ok(key) = std::env::var(KEY_ENV) {
+        return Ok(key);
+    }
+
+    if !console::user_attended() {
+        return Err(NotifyMeError::DatabaseAccess(format!(
+            "The database is encrypted; set {} to its key",
+            KEY_ENV
+        )));
+    }
+
+    let mut password = dialoguer::Password::new().with_prompt(prompt);
+    if confirm {
+        password = password.with_confirmation("Repeat the key", "The keys don't match");
+    }
+
+    Ok(password.interact()?)
+}
+
+/// Rewrites the plaintext database at `path` encrypted with a key from
+/// `KEY_ENV` or a prompt. The caller must have closed its connections.
+#[cfg(feature = "encryption")]
+pub fn encrypt(path: &Path) -> Result<(), NotifyMeError> {
+    if is_encrypted(path) {
+        return Err(NotifyMeError::Other(format!(
+            "{} is already encrypted",
+            path.display()
+        )));
+    }
+
+    let key = read_key("New key", true)?;
+    rewrite(path, &key)
+}
+
+/// Rewrites the encrypted database at `path` as plaintext. The caller must
+/// have closed its connections.
+#[cfg(feature = "encryption")]
+pub fn decrypt(path: &Path) -> Result<(), NotifyMeError> {
+    if !is_encrypted(path) {
+        return Err(NotifyMeError::Other(format!(
+            "{} is not encrypted",
+            path.display()
+        )));
+    }
+
+    rewrite(path, "")
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(_path: &Path) -> Result<(), NotifyMeError> {
+    Err("This build has no encryption support (rebuild with --features encryption)".into())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(_path: &Path) -> Result<(), NotifyMeError> {
+    Err("This build has no encryption support (rebuild with --features encryption)".into())
+}
+
+/// Exports the database into a copy keyed with `key` (empty for plaintext)
+/// and swaps it in. `sqlcipher_export` leaves `user_version` behind, so it is
+/// carried over by hand.
+#[cfg(feature = "encryption")]
+fn rewrite(path: &Path, key: &str) -> Result<(), NotifyMeError> {
+    let mut target = path.as_os_str().to_owned();
+    target.push(".rewrite");
+    let target = std::path::PathBuf::from(target);
+    let _ = fs::remove_file(&target);
+
+    {
+        let conn = connect(path)?;
+        let version = user_version(&conn)?;
+
+        conn.execute(
+            "ATTACH DATABASE ?1 AS target KEY ?2",
+            (target.to_string_lossy(), key),
+        )?;
+        conn.query_row("SELECT sqlcipher_export('target')", [], |_| Ok(()))?;
+        conn.pragma_update(
+            Some(DatabaseName::Attached("target")),
+            "user_version",
+            version,
+        )?;
+        conn.execute("DETACH DATABASE target", ())?;
+    }
+
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = fs::remove_file(sidecar);
+    }
+
+    match fs::rename(&target, path) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(NotifyMeError::DatabaseAccess(format!(
+            "{}: {}",
+            path.display(),
+            err
+        ))),
+    }
+}
+
+/// The schema version the database is currently at; 0 for a new database.
+pub fn user_version(conn: &Connection) -> Result<u32, NotifyMeError> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// The schema version a fully migrated database is at.
+pub fn schema_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>, NotifyMeError> {
+    Ok(conn
+        .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<(), NotifyMeError> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2) \
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        [key, value],
+    )?;
+
+    Ok(())
+}
+
+/// Dates are stored as fixed-width UTC RFC3339 strings so that plain string
+/// comparison orders them chronologically and range queries can use the index.
+pub fn to_sql_date<Tz: TimeZone>(date: &DateTime<Tz>) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Brings the schema of an open connection up to date, e.g. for an
+/// in-memory database.
+pub fn migrate(conn: &mut Connection) -> Result<Vec<MigrationInfo>, NotifyMeError> {
+    let current = user_version(conn)?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+
+        if let Err(err) = (migration.up)(&tx) {
+            return Err(NotifyMeError::DatabaseAccess(format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, err
+            )));
+        }
+
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+
+        info!(
+            "Applied migration {}: {}",
+            migration.version, migration.description
+        );
+        applied.push(migration.into());
+    }
+
+    Ok(applied)
+}
+
+/// The migrations `migrate` would apply, oldest first.
+pub fn pending_migrations(conn: &Connection) -> Result<Vec<MigrationInfo>, NotifyMeError> {
+    let current = user_version(conn)?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current)
+        .map(MigrationInfo::from)
+        .collect())
+}
+
+/// Problems reported by `PRAGMA integrity_check`; empty when the file is
+/// sound.
+pub fn integrity_check(conn: &Connection) -> Result<Vec<String>, NotifyMeError> {
+    let problems = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter_map(|row| row.ok())
+        .filter(|row| row != "ok")
+        .collect();
+
+    Ok(problems)
+}
+
+/// Rows whose foreign key points at a row that no longer exists, grouped by
+/// table as `(table, rowids)`. These can only appear if rows were written
+/// while foreign keys were off, e.g. by an older build or by hand.
+pub fn orphaned_rows(conn: &Connection) -> Result<Vec<(String, Vec<i64>)>, NotifyMeError> {
+    let rows = conn
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+        })?
+        .filter_map(|row| row.ok())
+        .collect::<Vec<_>>();
+
+    let mut orphans: Vec<(String, Vec<i64>)> = Vec::new();
+    for (table, rowid) in rows {
+        let Some(rowid) = rowid else {
+            warn!("Orphaned row in {} has no rowid, delete it by hand", table);
+            continue;
+        };
+
+        match orphans.iter_mut().find(|(name, _)| *name == table) {
+            Some((_, rowids)) => rowids.push(rowid),
+            None => orphans.push((table, vec![rowid])),
+        }
+    }
+
+    Ok(orphans)
+}
+
+/// Events whose rows don't map to an `Event`, e.g. because of a hand-edited
+/// recurrence. Listings and the scheduler leave these out.
+pub fn unreadable_events(conn: &Connection) -> Result<Vec<SkippedRow>, NotifyMeError> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM events", EVENT_COLUMNS))?;
+    let (_, skipped) = partition_rows(stmt.query_map([], Event::try_from_row)?)?;
+
+    Ok(skipped)
+}
+
+/// Deletes the rows found by `orphaned_rows`, returning how many went.
+pub fn delete_orphaned_rows(
+    conn: &Connection,
+    orphans: &[(String, Vec<i64>)],
+) -> Result<usize, NotifyMeError> {
+    let tx = conn.unchecked_transaction()?;
+
+    let mut removed = 0;
+    for (table, rowids) in orphans {
+        for rowid in rowids {
+            removed += tx.execute(
+                &format!("DELETE FROM \"{}\" WHERE rowid = ?1", table),
+                [rowid],
+            )?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(removed)
+}
+
+/// Fails when the database can't be written to. A file on a read-only
+/// filesystem still opens fine and only errors on the first write, so this
+/// makes a throwaway write up front.
+pub fn check_db_writable(conn: &Connection) -> Result<(), NotifyMeError> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS __write_test (x INTEGER);
+        INSERT INTO __write_test VALUES (1);
+        DROP TABLE __write_test;",
+    )?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Drops every table, then migrates the empty database back up to the
+/// current schema. The caller takes the backup and asks for confirmation.
+pub fn reset(conn: &mut Connection) -> Result<(), NotifyMeError> {
+    // Dropping a table referencing one already gone fails its foreign key
+    // check, and the pragma is a no-op inside a transaction.
+    conn.pragma_update(None, "foreign_keys", "OFF")?;
+    let dropped = drop_all_tables(conn);
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    dropped?;
+
+    migrate(conn)?;
+
+    Ok(())
+}
+
+fn drop_all_tables(conn: &mut Connection) -> Result<(), NotifyMeError> {
+    let tx = conn.transaction()?;
+
+    // Virtual tables come first: dropping one drops its shadow tables, which
+    // is why the rest use IF EXISTS.
+    let tables = tx
+        .prepare(
+            "SELECT name FROM sqlite_master \
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%' \
+             ORDER BY sql LIKE 'CREATE VIRTUAL%' DESC",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for table in tables {
+        tx.execute_batch(&format!("DROP TABLE IF EXISTS \"{}\"", table))?;
+    }
+    tx.pragma_update(None, "user_version", 0)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Rebuilds the database file to hand pages freed by deletions back to the
+/// filesystem, returning the `(before, after)` size in bytes.
+pub fn vacuum(conn: &Connection) -> Result<(u64, u64), NotifyMeError> {
+    let before = database_size(conn)?;
+    conn.execute_batch("VACUUM")?;
+
+    Ok((before, database_size(conn)?))
+}
+
+fn database_size(conn: &Connection) -> Result<u64, NotifyMeError> {
+    Ok(conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+fn create_events_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            message TEXT NOT NULL,
+            recurrence_pattern TEXT NOT NULL,
+            date TEXT NOT NULL,
+            deleted_at TEXT DEFAULT NULL
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+fn index_event_dates(tx: &Transaction) -> rusqlite::Result<()> {
+    let rows = {
+        let mut stmt = tx.prepare("SELECT id, date FROM events")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    let mut update = tx.prepare("UPDATE events SET date = ?1 WHERE id = ?2")?;
+    for (id, date) in rows {
+        match parse_stored_date(&date) {
+            Ok(parsed) => {
+                update.execute((to_sql_date(&parsed), id))?;
+            }
+            Err(err) => warn!("Event {} has an unreadable date '{}': {}", id, date, err),
+        }
+    }
+
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_events_date ON events (date);
+         CREATE INDEX IF NOT EXISTS idx_events_deleted_at_date ON events (deleted_at, date);",
+    )
+}
+
+fn add_last_notified_at(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "ALTER TABLE events ADD COLUMN last_notified_at TEXT DEFAULT NULL",
+        (),
+    )?;
+
+    Ok(())
+}
+
+fn add_event_timestamps(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE events ADD COLUMN created_at TEXT;
+         ALTER TABLE events ADD COLUMN updated_at TEXT;",
+    )?;
+
+    tx.execute(
+        "UPDATE events SET created_at = ?1, updated_at = ?1",
+        [to_sql_date(&Utc::now())],
+    )?;
+
+    Ok(())
+}
+
+fn add_event_tags(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "ALTER TABLE events ADD COLUMN tags TEXT NOT NULL DEFAULT ''",
+        (),
+    )?;
+
+    Ok(())
+}
+
+fn create_meta_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Events are identified across machines by a uuid. A trigger fills it in
+/// for inserts that don't provide one, so no insert path can miss it.
+fn add_event_uuids(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(&format!(
+        "ALTER TABLE events ADD COLUMN uuid TEXT;
+        UPDATE events SET uuid = {uuid};
+        CREATE UNIQUE INDEX idx_events_uuid ON events(uuid);
+        CREATE TRIGGER events_default_uuid AFTER INSERT ON events
+        WHEN NEW.uuid IS NULL
+        BEGIN
+            UPDATE events SET uuid = {uuid} WHERE id = NEW.id;
+        END;",
+        uuid = UUID_SQL
+    ))
+}
+
+/// History of events marked done. It keeps the name so entries still read
+/// sensibly after the event itself is purged.
+fn create_completed_events_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE completed_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            event_id INTEGER REFERENCES events(id) ON DELETE SET NULL,
+            event_name TEXT NOT NULL,
+            completed_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_completed_events_event_id ON completed_events(event_id);",
+    )
+}
+
+/// Calendars group events so they can be listed and muted separately. They
+/// exist implicitly through their events; `calendar_settings` only holds
+/// the ones with non-default settings.
+fn add_event_calendars(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(&format!(
+        "ALTER TABLE events ADD COLUMN calendar TEXT NOT NULL DEFAULT '{default}';
+        CREATE INDEX idx_events_calendar ON events(calendar);
+        CREATE TABLE calendar_settings (
+            calendar TEXT PRIMARY KEY,
+            muted INTEGER NOT NULL DEFAULT 0
+        );",
+        default = DEFAULT_CALENDAR
+    ))
+}
+
+fn add_event_priority(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE events ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal' \
+            CHECK (priority IN ('low', 'normal', 'high'));",
+    )
+}
+
+fn add_event_location(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN location TEXT;")
+}
+
+fn add_event_url(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN url TEXT;")
+}
+
+fn add_event_color(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN color TEXT;")
+}
+
+fn add_event_paused(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN paused INTEGER NOT NULL DEFAULT 0;")
+}
+
+/// An IANA zone name such as "Europe/Berlin". `date` stays UTC; the zone
+/// only decides which wall-clock time recurrences keep.
+fn add_event_timezone(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN timezone TEXT;")
+}
+
+/// Whether this SQLite build has the FTS5 extension that `events_fts`
+/// needs.
+pub fn has_fts5(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT sqlite_compileoption_used('ENABLE_FTS5')",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+/// Whether the database has the full-text index, which it lacks when it
+/// was migrated by an SQLite build without FTS5.
+pub fn has_search_index(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'events_fts')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// An FTS5 index over event names and messages, kept in step with `events`
+/// by triggers and filled from the existing rows. Builds without FTS5 go
+/// without it, and search falls back to `LIKE`.
+fn create_events_fts(tx: &Transaction) -> rusqlite::Result<()> {
+    if !has_fts5(tx) {
+        warn!("This SQLite build lacks FTS5; search will match words with LIKE");
+        return Ok(());
+    }
+
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS events_fts
+            USING fts5(name, message, content='events', content_rowid='id');
+
+         CREATE TRIGGER events_fts_insert AFTER INSERT ON events BEGIN
+             INSERT INTO events_fts (rowid, name, message)
+             VALUES (new.id, new.name, new.message);
+         END;
+
+         CREATE TRIGGER events_fts_delete AFTER DELETE ON events BEGIN
+             INSERT INTO events_fts (events_fts, rowid, name, message)
+             VALUES ('delete', old.id, old.name, old.message);
+         END;
+
+         CREATE TRIGGER events_fts_update AFTER UPDATE OF name, message ON events BEGIN
+             INSERT INTO events_fts (events_fts, rowid, name, message)
+             VALUES ('delete', old.id, old.name, old.message);
+             INSERT INTO events_fts (rowid, name, message)
+             VALUES (new.id, new.name, new.message);
+         END;
+
+         INSERT INTO events_fts (events_fts) VALUES ('rebuild');",
+    )
+}
+
+/// When quiet hours last held back the event's notification. Set until the
+/// notification is finally shown, after the quiet hours end.
+fn add_event_last_deferred_at(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN last_deferred_at TEXT;")
+}
+
+/// Replaces the comma-separated `events.tags` column with a `tags` table of
+/// case-insensitive names and an `event_tags` join table. A trigger drops a
+/// tag once no event uses it.
+fn normalize_tags(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+        CREATE TABLE event_tags (
+            event_id INTEGER NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (event_id, tag_id)
+        );
+        CREATE INDEX idx_event_tags_tag_id ON event_tags(tag_id);
+        CREATE TRIGGER tags_garbage_collect AFTER DELETE ON event_tags
+        WHEN NOT EXISTS (SELECT 1 FROM event_tags WHERE tag_id = OLD.tag_id)
+        BEGIN
+            DELETE FROM tags WHERE id = OLD.tag_id;
+        END;",
+    )?;
+
+    let rows = {
+        let mut stmt = tx.prepare("SELECT id, tags FROM events WHERE tags != ''")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+
+    for (id, tags) in rows {
+        insert_event_tags(tx, id, &parse_tags(&tags))?;
+    }
+
+    tx.execute("ALTER TABLE events DROP COLUMN tags", ())?;
+
+    Ok(())
+}
+
+/// Replaces the tags of event `event_id`, creating tags as needed.
+pub fn set_event_tags(
+    conn: &Connection,
+    event_id: i64,
+    tags: &[String],
+) -> Result<(), NotifyMeError> {
+    conn.execute("DELETE FROM event_tags WHERE event_id = ?1", [event_id])?;
+    insert_event_tags(conn, event_id, tags)?;
+
+    Ok(())
+}
+
+fn insert_event_tags(conn: &Connection, event_id: i64, tags: &[String]) -> rusqlite::Result<()> {
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO tags (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+            [tag],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO event_tags (event_id, tag_id) \
+             SELECT ?1, id FROM tags WHERE name = ?2",
+            (event_id, tag),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// WAL lets readers and a writer proceed concurrently, and the busy timeout
+/// makes a writer wait for the other side instead of failing immediately with
+/// `database is locked`.
+fn configure(conn: &Connection) -> Result<(), NotifyMeError> {
+    // A read-only database can't switch journal mode; it stays readable, and
+    // `check_db_writable` reports it unless the check is skipped.
+    if let Err(err) =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+    {
+        warn!("Could not enable WAL journaling: {}", err);
+    }
+
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+    // Auxiliary tables reference events with ON DELETE CASCADE, so purging an
+    // event also removes its related rows.
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashMap, fmt};
+
+use console::style;
+
+use crate::{event::Event, json::Value};
+
+/// How one event differs between two databases, matched by uuid, which
+/// copies of a database share while ids may be reused.
+#[derive(Debug)]
+pub enum EventChange {
+    /// Only in the database being compared from.
+    Ours(Event),
+    /// Only in the other database.
+    Theirs(Event),
+    /// In both, with these fields as `(name, ours, theirs)`.
+    Changed {
+        ours: Event,
+        fields: Vec<(String, Value, Value)>,
+    },
+}
+
+/// The events that differ between two databases, by date.
+#[derive(Debug, Default)]
+pub struct EventDiff(pub Vec<EventChange>);
+
+impl EventDiff {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Compares every field of the events in `ours` and `theirs`, trashed ones
+/// included.
+pub fn diff_events(ours: Vec<Event>, theirs: Vec<Event>) -> EventDiff {
+    let mut theirs: HashMap<String, Event> = theirs
+        .into_iter()
+        .map(|event| (event.uuid.clone(), event))
+        .collect();
+
+    let mut changes = Vec::new();
+    for event in ours {
+        match theirs.remove(&event.uuid) {
+            Some(other) => {
+                let fields = changed_fields(&event, &other);
+                if !fields.is_empty() {
+                    changes.push(EventChange::Changed {
+                        ours: event,
+                        fields,
+                    });
+                }
+            }
+            None => changes.push(EventChange::Ours(event)),
+        }
+    }
+    changes.extend(theirs.into_values().map(EventChange::Theirs));
+
+    changes.sort_by(|a, b| a.event().cmp(b.event()));
+    EventDiff(changes)
+}
+
+impl EventChange {
+    fn event(&self) -> &Event {
+        match self {
+            EventChange::Ours(event)
+            | EventChange::Theirs(event)
+            | EventChange::Changed { ours: event, .. } => event,
+        }
+    }
+}
+
+/// The fields of `event` and `other` that differ, as their JSON exports
+/// have them.
+fn changed_fields(event: &Event, other: &Event) -> Vec<(String, Value, Value)> {
+    let (Value::Object(ours), Value::Object(theirs)) = (event.to_json(), other.to_json()) else {
+        return Vec::new();
+    };
+
+    ours.into_iter()
+        .zip(theirs)
+        .filter(|((_, ours), (_, theirs))| ours != theirs)
+        .map(|((name, ours), (_, theirs))| (name, ours, theirs))
+        .collect()
+}
+
+impl fmt::Display for EventDiff {
+    /// One line per event: `>` only here, `<` only in the other database
+    /// and `~` changed, followed by the changed fields.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .0
+            .iter()
+            .map(|change| {
+                let event = change.event();
+                let summary = format!(
+                    "{} {} ({})",
+                    event.id,
+                    event.name,
+                    event.date.format("%Y-%m-%d %H:%M")
+                );
+
+                match change {
+                    EventChange::Ours(_) => style(format!("> {}", summary)).green().to_string(),
+                    EventChange::Theirs(_) => style(format!("< {}", summary)).red().to_string(),
+                    EventChange::Changed { fields, .. } => {
+                        let mut lines = vec![style(format!("~ {}", summary)).yellow().to_string()];
+                        lines.extend(fields.iter().map(|(name, ours, theirs)| {
+                            format!("    {}: {} -> {}", name, ours, theirs)
+                        }));
+                        lines.join("\n")
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
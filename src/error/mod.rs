@@ -0,0 +1,95 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NotifyMeError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    /// The database file couldn't be opened or written, with a hint on
+    /// what to do about it.
+    #[error("{0}")]
+    DatabaseAccess(String),
+    #[error("{0}")]
+    DateParse(String),
+    #[error("No event with id {0}")]
+    NotFound(i32),
+    #[error("Failed to show notification: {0}")]
+    Notification(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error("{0}")]
+    Other(String),
+    /// The user backed out of a prompt with Esc or Ctrl-C.
+    #[error("Cancelled")]
+    Cancelled,
+    /// Bad command line arguments, printed by clap itself.
+    #[error("{0}")]
+    Usage(#[from] clap::Error),
+    /// `--migrate-db` found nothing to do; not a failure, but scripts can
+    /// tell it apart by the exit status.
+    #[error("No migrations pending.")]
+    NoMigrationsPending,
+}
+
+/// Every status the process exits with, for `--help` and the man page.
+pub const EXIT_STATUSES: &[(u8, &str)] = &[
+    (0, "Success"),
+    (1, "Usage error, or any error not listed below"),
+    (
+        2,
+        "Database error, including a database that can't be opened or written; \
+         for --migrate-db, no migrations were pending",
+    ),
+    (3, "Notification error"),
+];
+
+impl NotifyMeError {
+    /// Process exit status for the error: 2 for database errors, 3 for
+    /// notification errors and 1 for usage errors and everything else.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            NotifyMeError::Database(_)
+            | NotifyMeError::DatabaseAccess(_)
+            | NotifyMeError::NoMigrationsPending => 2,
+            NotifyMeError::Notification(_) => 3,
+            _ => 1,
+        }
+    }
+}
+
+impl From<String> for NotifyMeError {
+    fn from(err: String) -> Self {
+        NotifyMeError::Other(err)
+    }
+}
+
+impl From<&str> for NotifyMeError {
+    fn from(err: &str) -> Self {
+        NotifyMeError::Other(err.to_string())
+    }
+}
+
+impl From<chrono::ParseError> for NotifyMeError {
+    fn from(err: chrono::ParseError) -> Self {
+        NotifyMeError::DateParse(err.to_string())
+    }
+}
+
+impl From<dialoguer::Error> for NotifyMeError {
+    fn from(err: dialoguer::Error) -> Self {
+        match err {
+            // What Ctrl-C reads as while a prompt has the terminal in raw mode.
+            dialoguer::Error::IO(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                NotifyMeError::Cancelled
+            }
+            dialoguer::Error::IO(err) => NotifyMeError::Io(err),
+        }
+    }
+}
+
+impl From<notify_rust::error::Error> for NotifyMeError {
+    fn from(err: notify_rust::error::Error) -> Self {
+        NotifyMeError::Notification(err.to_string())
+    }
+}
@@ -1,51 +1,168 @@
-use core::fmt;
+use core::{cmp::Ordering, fmt, str::FromStr};
 
-use chrono::{DateTime, Local, Utc};
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, Local, LocalResult, Months, NaiveDate,
+    NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use console::style;
 use rusqlite::{
-    types::{FromSql, ToSqlOutput},
-    ToSql,
+    types::{FromSql, ToSqlOutput, Type},
+    Connection, Row, ToSql,
 };
+use tracing::warn;
 
-#[derive(Debug)]
+use crate::{
+    db::{to_sql_date, DEFAULT_CALENDAR},
+    error::NotifyMeError,
+    json::Value,
+    output,
+    zone::Zone,
+};
+
+/// Offset-less formats older databases stored dates in.
+const LEGACY_DATE_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// Column list matching the order `Event::from_row` reads them in. The last
+/// column tells whether the event's deletion was it being marked done.
+pub const EVENT_COLUMNS: &str = "id, name, message, recurrence_pattern, date, deleted_at, \
+    last_notified_at, created_at, updated_at, \
+    coalesce((SELECT group_concat(t.name, ',' ORDER BY t.name) FROM event_tags et \
+        JOIN tags t ON t.id = et.tag_id WHERE et.event_id = events.id), ''), \
+    uuid, calendar, \
+    EXISTS (SELECT 1 FROM completed_events \
+        WHERE event_id = events.id AND completed_at = events.deleted_at), \
+    priority, location, url, color, paused, timezone";
+
+/// Orders events high priority first; needs a tiebreaker such as `date`.
+pub const PRIORITY_ORDER: &str = "CASE priority WHEN 'high' THEN 0 WHEN 'normal' THEN 1 ELSE 2 END";
+
+/// How often an event repeats, stored as its `String` form.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum RecurrencePattern {
     Daily,
     Weekly,
     Monthly,
+    #[default]
     Once,
+    /// Every N weeks, N from 1 to `MAX_WEEKS`; stored as `every_n_weeks:<N>`.
+    EveryNWeeks(u32),
+    /// The nth weekday of every month, e.g. the 2nd Tuesday, nth from 1 to
+    /// 4 or `LAST_WEEKDAY` for the last; stored as `nth_weekday:<N>:<day>`
+    /// with the day abbreviated, e.g. `nth_weekday:2:tue`.
+    EveryNthWeekdayOfMonth(Weekday, u8),
+}
+
+/// The `nth` of `EveryNthWeekdayOfMonth` that means the month's last such
+/// weekday, whether that is its 4th or 5th.
+pub const LAST_WEEKDAY: u8 = 5;
+
+/// Longest interval `EveryNWeeks` accepts: a year.
+pub const MAX_WEEKS: u32 = 52;
+
+/// Parses a recurrence as stored, rejecting anything unknown.
+pub fn parse_recurrence(value: &str) -> Result<RecurrencePattern, String> {
+    let value = value.trim().to_lowercase();
+
+    match value.as_str() {
+        "once" => Ok(RecurrencePattern::Once),
+        "daily" => Ok(RecurrencePattern::Daily),
+        "weekly" => Ok(RecurrencePattern::Weekly),
+        "monthly" => Ok(RecurrencePattern::Monthly),
+        _ if value.starts_with("nth_weekday:") => parse_nth_weekday(&value),
+        _ => match value.strip_prefix("every_n_weeks:").map(str::parse::<u32>) {
+            Some(Ok(weeks)) if (1..=MAX_WEEKS).contains(&weeks) => {
+                Ok(RecurrencePattern::EveryNWeeks(weeks))
+            }
+            Some(_) => Err(format!("every_n_weeks takes 1 to {} weeks", MAX_WEEKS)),
+            None => Err(format!(
+                "unknown recurrence '{}', expected once, daily, weekly, monthly, every_n_weeks:N or nth_weekday:N:DAY",
+                value
+            )),
+        },
+    }
+}
+
+/// Parses `nth_weekday:<N>:<day>`, the day named in full or abbreviated.
+fn parse_nth_weekday(value: &str) -> Result<RecurrencePattern, String> {
+    let invalid = || {
+        format!(
+            "'{}' should be nth_weekday:N:DAY, N from 1 to 4 or {} for the last, e.g. nth_weekday:2:tue",
+            value, LAST_WEEKDAY
+        )
+    };
+
+    match value.split(':').collect::<Vec<_>>()[..] {
+        [_, nth, weekday] => {
+            let nth = nth
+                .parse::<u8>()
+                .ok()
+                .filter(|nth| (1..=LAST_WEEKDAY).contains(nth))
+                .ok_or_else(invalid)?;
+            let weekday = weekday.parse::<Weekday>().map_err(|_| invalid())?;
+
+            Ok(RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+impl FromStr for RecurrencePattern {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_recurrence(value)
+    }
 }
 
 impl From<&str> for RecurrencePattern {
     fn from(value: &str) -> Self {
-        match value.trim().to_lowercase().as_str() {
-            "daily" => RecurrencePattern::Daily,
-            "weekly" => RecurrencePattern::Weekly,
-            "monthly" => RecurrencePattern::Monthly,
-            _ => RecurrencePattern::Once,
-        }
+        parse_recurrence(value).unwrap_or(RecurrencePattern::Once)
     }
 }
 
-impl From<RecurrencePattern> for &str {
+impl From<RecurrencePattern> for String {
     fn from(value: RecurrencePattern) -> Self {
         match value {
-            RecurrencePattern::Daily => "daily",
-            RecurrencePattern::Weekly => "weekly",
-            RecurrencePattern::Monthly => "monthly",
-            RecurrencePattern::Once => "once",
+            RecurrencePattern::Daily => "daily".to_string(),
+            RecurrencePattern::Weekly => "weekly".to_string(),
+            RecurrencePattern::Monthly => "monthly".to_string(),
+            RecurrencePattern::Once => "once".to_string(),
+            RecurrencePattern::EveryNWeeks(weeks) => format!("every_n_weeks:{}", weeks),
+            RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) => {
+                format!("nth_weekday:{}:{}", nth, weekday.to_string().to_lowercase())
+            }
+        }
+    }
+}
+
+impl fmt::Display for RecurrencePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecurrencePattern::EveryNWeeks(weeks) => write!(f, "every {} weeks", weeks),
+            RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) => write!(
+                f,
+                "every {} {} of the month",
+                nth_name(*nth),
+                weekday_name(*weekday)
+            ),
+            pattern => write!(f, "{}", String::from(*pattern)),
         }
     }
 }
 
 impl FromSql for RecurrencePattern {
+    /// NULL reads as a one-time event. `events` declares the column NOT NULL,
+    /// but a table another tool created before migrating may not.
     fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        if let rusqlite::types::ValueRef::Null = value {
+            return Ok(RecurrencePattern::Once);
+        }
+
         match String::column_result(value) {
-            Ok(recurrence_pattern) => match recurrence_pattern.as_str() {
-                "once" => Ok(RecurrencePattern::Once),
-                "daily" => Ok(RecurrencePattern::Daily),
-                "weekly" => Ok(RecurrencePattern::Weekly),
-                "monthly" => Ok(RecurrencePattern::Monthly),
-                _ => Err(rusqlite::types::FromSqlError::Other(Box::new(
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected value"),
+            Ok(recurrence_pattern) => match parse_recurrence(&recurrence_pattern) {
+                Ok(recurrence_pattern) => Ok(recurrence_pattern),
+                Err(err) => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, err),
                 ))),
             },
             Err(err) => Err(rusqlite::types::FromSqlError::Other(Box::new(err))),
@@ -55,54 +172,925 @@ impl FromSql for RecurrencePattern {
 
 impl ToSql for RecurrencePattern {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        match *self {
-            RecurrencePattern::Daily => Ok(rusqlite::types::ToSqlOutput::Owned(
-                rusqlite::types::Value::Text(String::from("daily")),
-            )),
-            RecurrencePattern::Weekly => Ok(rusqlite::types::ToSqlOutput::Owned(
-                rusqlite::types::Value::Text(String::from("weekly")),
-            )),
-            RecurrencePattern::Monthly => Ok(rusqlite::types::ToSqlOutput::Owned(
-                rusqlite::types::Value::Text(String::from("monthly")),
-            )),
-            RecurrencePattern::Once => Ok(rusqlite::types::ToSqlOutput::Owned(
-                rusqlite::types::Value::Text(String::from("once")),
+        Ok(rusqlite::types::ToSqlOutput::Owned(
+            rusqlite::types::Value::Text(String::from(*self)),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// Prefix for the event name in listings, so urgent events stand out.
+    fn marker(self) -> &'static str {
+        match self {
+            Priority::High => "!! ",
+            Priority::Normal | Priority::Low => "",
+        }
+    }
+}
+
+impl From<&str> for Priority {
+    fn from(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "low" => Priority::Low,
+            "high" => Priority::High,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => Err(format!(
+                "unknown priority '{}', expected low, normal or high",
+                value.trim()
             )),
         }
     }
 }
 
-#[derive(Debug)]
+impl From<Priority> for &str {
+    fn from(value: Priority) -> Self {
+        match value {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+impl FromSql for Priority {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match String::column_result(value) {
+            Ok(priority) => match priority.as_str() {
+                "low" => Ok(Priority::Low),
+                "normal" => Ok(Priority::Normal),
+                "high" => Ok(Priority::High),
+                _ => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected value"),
+                ))),
+            },
+            Err(err) => Err(rusqlite::types::FromSqlError::Other(Box::new(err))),
+        }
+    }
+}
+
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let priority: &str = (*self).into();
+        Ok(rusqlite::types::ToSqlOutput::Owned(
+            rusqlite::types::Value::Text(priority.to_string()),
+        ))
+    }
+}
+
+/// The next occurrence of an event recurring with `pattern` after `date`.
+/// `Once` events don't recur, so their date is returned unchanged.
+///
+/// Recurrences advance in calendar days on the wall clock, so an event keeps
+/// its local time of day across DST transitions instead of drifting by an
+/// hour. A time that doesn't exist on the new day (spring-forward gap) is
+/// pushed past the gap. Monthly events past the end of a shorter month fall
+/// on its last day, and keep that day from then on.
+pub fn advance_date<Tz: TimeZone>(date: DateTime<Tz>, pattern: &RecurrencePattern) -> DateTime<Tz> {
+    let local = date.naive_local();
+
+    let next = match pattern {
+        RecurrencePattern::Daily => local + Days::new(1),
+        RecurrencePattern::Weekly => local + Days::new(7),
+        RecurrencePattern::EveryNWeeks(weeks) => local + Days::new(7 * u64::from(*weeks)),
+        RecurrencePattern::Monthly => {
+            let next_month = local.month() % 12 + 1; // wraps around after December
+            let next_year = if next_month == 1 {
+                local.year() + 1
+            } else {
+                local.year()
+            };
+
+            let day = (1..=local.day())
+                .rev()
+                .find_map(|day| NaiveDate::from_ymd_opt(next_year, next_month, day))
+                .unwrap();
+
+            day.and_time(local.time())
+        }
+        RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) => {
+            let next_month = local.date().checked_add_months(Months::new(1)).unwrap();
+            nth_weekday_of_month(next_month.year(), next_month.month(), *weekday, *nth)
+                .and_time(local.time())
+        }
+        RecurrencePattern::Once => return date,
+    };
+
+    let timezone = date.timezone();
+    match timezone.from_local_datetime(&next) {
+        LocalResult::Single(date) => date,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => timezone
+            .from_local_datetime(&(next + Duration::hours(1)))
+            .earliest()
+            .unwrap_or_else(|| timezone.from_utc_datetime(&next)),
+    }
+}
+
+/// The `nth` `weekday` of a month, or its last one when `nth` is past the
+/// weekdays it has, as with `LAST_WEEKDAY`.
+pub fn nth_weekday_of_month(year: i32, month: u32, weekday: Weekday, nth: u8) -> NaiveDate {
+    let matches: Vec<NaiveDate> = (1..=31)
+        .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .filter(|date| date.weekday() == weekday)
+        .collect();
+
+    matches
+        .get(usize::from(nth) - 1)
+        .or(matches.last())
+        .copied()
+        .unwrap()
+}
+
+/// `advance_date` in `zone`'s wall-clock time instead of local time, for
+/// events with a time zone of their own.
+pub fn advance_date_in(
+    date: DateTime<Local>,
+    pattern: &RecurrencePattern,
+    zone: Option<&Zone>,
+) -> DateTime<Local> {
+    match zone {
+        Some(zone) => advance_date(date.with_timezone(zone), pattern).with_timezone(&Local),
+        None => advance_date(date, pattern),
+    }
+}
+
+/// The `count` occurrences after `date` of an event recurring with
+/// `pattern`, in `zone`'s wall-clock time if given. A one-time event has
+/// none.
+pub fn occurrences(
+    date: DateTime<Local>,
+    pattern: &RecurrencePattern,
+    zone: Option<&Zone>,
+    count: u32,
+) -> Vec<DateTime<Local>> {
+    if *pattern == RecurrencePattern::Once {
+        return Vec::new();
+    }
+
+    std::iter::successors(Some(date), |date| {
+        Some(advance_date_in(*date, pattern, zone))
+    })
+    .skip(1)
+    .take(count as usize)
+    .collect()
+}
+
+/// Splits a comma-separated tag list, dropping blanks and duplicates. Tags
+/// are case-insensitive; the first spelling wins.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for tag in input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+    {
+        if !tags
+            .iter()
+            .any(|existing| existing.to_lowercase() == tag.to_lowercase())
+        {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+/// Trims optional text such as a location, treating blank as absent.
+pub fn parse_optional(input: &str) -> Option<String> {
+    Some(input.trim())
+        .filter(|location| !location.is_empty())
+        .map(str::to_string)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum EventSort {
+    #[default]
+    Date,
+    Created,
+    Priority,
+}
+
+impl EventSort {
+    pub fn order_by(self) -> &'static str {
+        match self {
+            EventSort::Date => "date",
+            EventSort::Created => "created_at",
+            EventSort::Priority => {
+                "CASE priority WHEN 'high' THEN 0 WHEN 'normal' THEN 1 ELSE 2 END, date"
+            }
+        }
+    }
+}
+
+impl From<&str> for EventSort {
+    fn from(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "created" => EventSort::Created,
+            "priority" => EventSort::Priority,
+            _ => EventSort::Date,
+        }
+    }
+}
+
+/// A field of an event that `Client::update_event_field` can change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventField {
+    Name,
+    Message,
+    Date,
+    RecurrencePattern,
+    Priority,
+    Location,
+    Url,
+    Color,
+    Tags,
+}
+
+impl EventField {
+    pub const ALL: [EventField; 9] = [
+        EventField::Name,
+        EventField::Message,
+        EventField::Date,
+        EventField::RecurrencePattern,
+        EventField::Priority,
+        EventField::Location,
+        EventField::Url,
+        EventField::Color,
+        EventField::Tags,
+    ];
+}
+
+impl From<EventField> for &str {
+    fn from(value: EventField) -> Self {
+        match value {
+            EventField::Name => "name",
+            EventField::Message => "message",
+            EventField::Date => "date",
+            EventField::RecurrencePattern => "recurrence",
+            EventField::Priority => "priority",
+            EventField::Location => "location",
+            EventField::Url => "link",
+            EventField::Color => "color",
+            EventField::Tags => "tags",
+        }
+    }
+}
+
+/// Longest event name accepted, in characters.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Longest event message accepted, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 1000;
+
+/// Event names show up as the notification summary, so they must have
+/// something to show and stay short.
+pub fn validate_event_name(name: &str) -> Result<(), &'static str> {
+    if name.trim().is_empty() {
+        return Err("The event name can't be blank");
+    }
+
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err("The event name can't be longer than 255 characters");
+    }
+
+    Ok(())
+}
+
+/// Messages may be empty, but are bounded like names.
+pub fn validate_event_message(message: &str) -> Result<(), &'static str> {
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err("The event message can't be longer than 1000 characters");
+    }
+
+    Ok(())
+}
+
+/// Event URLs are opened with the system opener, so only absolute web links
+/// are accepted. A blank URL means none.
+pub fn validate_event_url(url: &str) -> Result<(), &'static str> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Ok(());
+    }
+
+    let lowercase = url.to_lowercase();
+    let rest = match lowercase
+        .strip_prefix("https://")
+        .or_else(|| lowercase.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return Err("The URL must start with http:// or https://"),
+    };
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if host.is_empty() || url.chars().any(char::is_whitespace) {
+        return Err("The URL must be an absolute http(s) URL");
+    }
+
+    Ok(())
+}
+
+/// A reminder, as stored in a row of `events`.
+///
+/// Events compare chronologically by `date`, with `id` breaking ties
+/// between events at the same instant. Equality follows the same key, so
+/// two rows are only equal when they are the same event at the same date,
+/// whatever their other fields say.
+#[derive(Debug, Clone)]
 pub struct Event {
-    #[allow(unused)]
     pub id: i32,
     pub name: String,
     pub message: String,
     pub recurrence_pattern: RecurrencePattern,
     pub date: DateTime<Local>,
-    #[allow(unused)]
     pub deleted_at: Option<DateTime<Utc>>,
+    pub last_notified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub uuid: String,
+    pub calendar: String,
+    pub completed: bool,
+    pub priority: Priority,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    /// Label color, as normalized by `output::parse_color`.
+    pub color: Option<String>,
+    /// Paused events keep their schedule but don't notify.
+    pub paused: bool,
+    /// IANA zone whose wall-clock time the event keeps, rather than local
+    /// time.
+    pub timezone: Option<String>,
+}
+
+impl Event {
+    /// Reads an event from a row selected with `EVENT_COLUMNS`.
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let parse_utc = |dt: String| parse_stored_date(&dt).ok().map(|dt| dt.with_timezone(&Utc));
+        let date = |idx: usize| {
+            let raw = row.get::<_, String>(idx)?;
+            parse_stored_date(&raw).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, err.into())
+            })
+        };
+
+        Ok(Event {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            message: row.get(2)?,
+            recurrence_pattern: row.get(3)?,
+            date: date(4)?.with_timezone(&Local),
+            deleted_at: row.get::<_, Option<String>>(5)?.and_then(parse_utc),
+            last_notified_at: row.get::<_, Option<String>>(6)?.and_then(parse_utc),
+            created_at: date(7)?.with_timezone(&Utc),
+            updated_at: date(8)?.with_timezone(&Utc),
+            tags: parse_tags(&row.get::<_, String>(9)?),
+            uuid: row.get(10)?,
+            calendar: row.get(11)?,
+            completed: row.get(12)?,
+            priority: row.get(13)?,
+            location: row.get(14)?,
+            url: row.get(15)?,
+            color: row.get(16)?,
+            paused: row.get(17)?,
+            timezone: row.get(18)?,
+        })
+    }
+
+    /// Like `from_row`, but a row that doesn't map comes back as a
+    /// `SkippedRow` naming the event instead of failing the whole query.
+    pub fn try_from_row(row: &Row) -> rusqlite::Result<Result<Self, SkippedRow>> {
+        let id = row.get(0)?;
+
+        Ok(Event::from_row(row).map_err(|err| SkippedRow {
+            id,
+            error: err.to_string(),
+        }))
+    }
+
+    /// The event as exported by `notify_me export --json`; dates are UTC
+    /// RFC 3339, as stored.
+    pub fn to_json(&self) -> Value {
+        let recurrence = String::from(self.recurrence_pattern);
+        let priority: &str = self.priority.into();
+
+        Value::Object(vec![
+            ("id".to_string(), Value::Number(self.id.into())),
+            ("uuid".to_string(), self.uuid.as_str().into()),
+            ("calendar".to_string(), self.calendar.as_str().into()),
+            ("name".to_string(), self.name.as_str().into()),
+            ("message".to_string(), self.message.as_str().into()),
+            ("recurrence".to_string(), recurrence.into()),
+            ("priority".to_string(), priority.into()),
+            ("location".to_string(), self.location.as_deref().into()),
+            ("url".to_string(), self.url.as_deref().into()),
+            ("color".to_string(), self.color.as_deref().into()),
+            ("paused".to_string(), Value::Bool(self.paused)),
+            ("timezone".to_string(), self.timezone.as_deref().into()),
+            ("date".to_string(), to_sql_date(&self.date).into()),
+            (
+                "deleted_at".to_string(),
+                self.deleted_at.map(|date| to_sql_date(&date)).into(),
+            ),
+            (
+                "last_notified_at".to_string(),
+                self.last_notified_at.map(|date| to_sql_date(&date)).into(),
+            ),
+            (
+                "created_at".to_string(),
+                to_sql_date(&self.created_at).into(),
+            ),
+            (
+                "updated_at".to_string(),
+                to_sql_date(&self.updated_at).into(),
+            ),
+            (
+                "tags".to_string(),
+                Value::Array(self.tags.iter().map(|tag| tag.as_str().into()).collect()),
+            ),
+        ])
+    }
+
+    /// Reads an event written by `to_json`. Only `name` and `date` are
+    /// required; an absent id is returned as 0 and an absent uuid as "".
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let string = |key: &str| match value.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.as_str())),
+            Some(_) => Err(format!("'{}' must be a string", key)),
+        };
+        let date = |key: &str| match string(key)? {
+            Some(date) => match DateTime::parse_from_rfc3339(date) {
+                Ok(date) => Ok(Some(date.with_timezone(&Utc))),
+                Err(err) => Err(format!("'{}': {}", key, err)),
+            },
+            None => Ok(None),
+        };
+
+        let id = match value.get("id").map(|id| id.as_i64().map(i32::try_from)) {
+            None => 0,
+            Some(Some(Ok(id))) => id,
+            Some(_) => return Err("'id' must be an integer".to_string()),
+        };
+
+        let tags = match value.get("tags") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(tags)) => {
+                let tags = tags.iter().map(Value::as_str).collect::<Option<Vec<_>>>();
+                match tags {
+                    Some(tags) => parse_tags(&tags.join(",")),
+                    None => return Err("'tags' must be a list of strings".to_string()),
+                }
+            }
+            Some(_) => return Err("'tags' must be a list of strings".to_string()),
+        };
+
+        let url = string("url")?.and_then(parse_optional);
+        if let Some(url) = &url {
+            if let Err(err) = validate_event_url(url) {
+                return Err(format!("'url': {}", err));
+            }
+        }
+
+        let color = match output::parse_color(string("color")?.unwrap_or_default()) {
+            Ok(color) => color,
+            Err(err) => return Err(format!("'color': {}", err)),
+        };
+
+        let paused = match value.get("paused") {
+            None | Some(Value::Null) => false,
+            Some(Value::Bool(paused)) => *paused,
+            Some(_) => return Err("'paused' must be a boolean".to_string()),
+        };
+
+        let timezone = string("timezone")?.and_then(parse_optional);
+        if let Some(timezone) = &timezone {
+            if let Err(err) = Zone::load(timezone) {
+                return Err(format!("'timezone': {}", err));
+            }
+        }
+
+        let now = Utc::now();
+
+        Ok(Event {
+            id,
+            name: match string("name")? {
+                Some(name) => name.to_string(),
+                None => return Err("'name' is missing".to_string()),
+            },
+            message: string("message")?.unwrap_or_default().to_string(),
+            recurrence_pattern: RecurrencePattern::from(string("recurrence")?.unwrap_or("once")),
+            date: match date("date")? {
+                Some(date) => date.with_timezone(&Local),
+                None => return Err("'date' is missing".to_string()),
+            },
+            deleted_at: date("deleted_at")?,
+            last_notified_at: date("last_notified_at")?,
+            created_at: date("created_at")?.unwrap_or(now),
+            updated_at: date("updated_at")?.unwrap_or(now),
+            tags,
+            uuid: string("uuid")?.unwrap_or_default().to_string(),
+            calendar: string("calendar")?.unwrap_or(DEFAULT_CALENDAR).to_string(),
+            completed: false,
+            priority: Priority::from(string("priority")?.unwrap_or("normal")),
+            location: string("location")?.and_then(parse_optional),
+            url,
+            color,
+            paused,
+            timezone,
+        })
+    }
+
+    /// The event's own time zone, if it has one that this system knows.
+    pub fn zone(&self) -> Option<Zone> {
+        let name = self.timezone.as_deref()?;
+        match Zone::load(name) {
+            Ok(zone) => Some(zone),
+            Err(err) => {
+                warn!(id = self.id, name = %self.name, "{}, using local time", err);
+                None
+            }
+        }
+    }
+
+    /// The first occurrence at or after `now`. Recurring events that were
+    /// missed are stepped forward; a past one-time event has none.
+    pub fn next_occurrence(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        if self.recurrence_pattern == RecurrencePattern::Once {
+            return Some(self.date).filter(|date| *date >= now);
+        }
+
+        let zone = self.zone();
+        let mut date = self.date;
+        while date < now {
+            date = advance_date_in(date, &self.recurrence_pattern, zone.as_ref());
+        }
+
+        Some(date)
+    }
+
+    /// When the event is, as "2025-12-25 15:00", or with the local time
+    /// alongside for events in a zone of their own: "2025-12-25 15:00
+    /// Europe/Berlin (2025-12-25 09:00 local)".
+    pub fn date_description(&self) -> String {
+        let local = self.date.format("%Y-%m-%d %H:%M").to_string();
+
+        match self.zone() {
+            Some(zone) => format!(
+                "{} {} ({} local)",
+                self.date.with_timezone(&zone).format("%Y-%m-%d %H:%M"),
+                zone,
+                local
+            ),
+            None => local,
+        }
+    }
+
+    /// How the event repeats, as a sentence: "Repeats every week (next:
+    /// Monday 18 March at 09:00)". Occurrences within the week are named
+    /// relative to today.
+    pub fn recurrence_description(&self) -> String {
+        let now = Local::now();
+        let repeats = match self.recurrence_pattern {
+            RecurrencePattern::Once => return "One-time event".to_string(),
+            RecurrencePattern::Daily => "Repeats every day".to_string(),
+            RecurrencePattern::Weekly => "Repeats every week".to_string(),
+            RecurrencePattern::EveryNWeeks(_) | RecurrencePattern::EveryNthWeekdayOfMonth(..) => {
+                format!("Repeats {}", self.recurrence_pattern)
+            }
+            RecurrencePattern::Monthly => {
+                format!("Repeats every month on the {}", ordinal(self.date.day()))
+            }
+        };
+
+        match self.next_occurrence(now) {
+            Some(next) => format!(
+                "{} (next: {} at {})",
+                repeats,
+                relative_day(next.date_naive(), now.date_naive()),
+                next.format("%H:%M")
+            ),
+            None => repeats,
+        }
+    }
+
+    /// Takes the event back out of the trash.
+    pub fn restore(&self, conn: &Connection) -> Result<(), NotifyMeError> {
+        conn.execute(
+            "UPDATE events SET deleted_at = NULL, updated_at = ?1 WHERE id = ?2",
+            (to_sql_date(&Utc::now()), self.id),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Event {}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.date
+            .timestamp()
+            .cmp(&other.date.timestamp())
+            .then_with(|| self.id.cmp(&other.id))
+    }
 }
 
 impl fmt::Display for Event {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let recurrence = match self.recurrence_pattern {
-            RecurrencePattern::Daily => "daily",
-            RecurrencePattern::Weekly => "weekly",
-            RecurrencePattern::Monthly => "monthly",
-            RecurrencePattern::Once => "once",
+        let name = match &self.color {
+            Some(color) => output::paint(&self.name, color),
+            None => self.name.clone(),
         };
 
-        write!(
-            f,
-            "Event: {}\nAt: {}\nRecurrence: {}",
-            self.name,
-            self.date.format("%Y-%m-%d %H:%M"),
-            recurrence,
-        )
+        let mut details = format!(
+            "Event: {}{}{}\nAt: {}\n{}",
+            self.priority.marker(),
+            name,
+            if self.paused { " (paused)" } else { "" },
+            self.date_description(),
+            self.recurrence_description(),
+        );
+
+        if let Some(location) = &self.location {
+            details.push_str(&format!("\nWhere: {}", location));
+        }
+
+        if let Some(url) = &self.url {
+            details.push_str(&format!("\nLink: {}", url));
+        }
+
+        if !self.tags.is_empty() {
+            details.push_str(&format!("\nTags: {}", self.tags.join(", ")));
+        }
+
+        if self.priority != Priority::Normal {
+            let priority: &str = self.priority.into();
+            details.push_str(&format!("\nPriority: {}", priority));
+        }
+
+        if self.calendar != DEFAULT_CALENDAR {
+            details.push_str(&format!("\nCalendar: {}", self.calendar));
+        }
+
+        match self.deleted_at {
+            Some(deleted_at) => write!(
+                f,
+                "{}\n{}",
+                style(format!(
+                    "{}: {}",
+                    if self.completed { "Done" } else { "Deleted" },
+                    deleted_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                ))
+                .bold(),
+                style(details).dim()
+            ),
+            None if self.paused => write!(f, "{}", style(details).dim()),
+            None => write!(f, "{}", details),
+        }
+    }
+}
+
+/// `day` named relative to `today` when it is within the week, and by
+/// weekday and date otherwise.
+fn relative_day(day: NaiveDate, today: NaiveDate) -> String {
+    match (day - today).num_days() {
+        0 => "today".to_string(),
+        1 => "tomorrow".to_string(),
+        days @ 2..=6 => format!("in {} days", days),
+        _ if day.year() == today.year() => day.format("%A %-d %B").to_string(),
+        _ => day.format("%A %-d %B %Y").to_string(),
     }
 }
 
+/// `n` as an English ordinal: 1st, 2nd, 3rd, 4th, 11th, 21st...
+/// The days of the week from Monday, as calendars here lay them out.
+pub const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// `weekday` in full, e.g. "Tuesday".
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// An `EveryNthWeekdayOfMonth` position as "2nd", or "last".
+pub fn nth_name(nth: u8) -> String {
+    if nth == LAST_WEEKDAY {
+        "last".to_string()
+    } else {
+        ordinal(nth.into())
+    }
+}
+
+fn ordinal(n: u32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("{}{}", n, suffix)
+}
+
+/// Parses a date as stored in the database. Dates are written as RFC 3339,
+/// but rows from before that was enforced may lack an offset; those are read
+/// as local time.
+pub fn parse_stored_date(raw: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(date);
+    }
+
+    let naive = LEGACY_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+        .ok_or_else(|| format!("unreadable date '{}'", raw))?;
+
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(date) | LocalResult::Ambiguous(date, _) => Ok(date.fixed_offset()),
+        LocalResult::None => Err(format!("date '{}' doesn't exist in local time", raw)),
+    }
+}
+
+/// Builds an `Event` in code rather than through the prompts, for
+/// `Client::insert_event`. Only the name and date are required; the event
+/// goes into the default calendar unless given another.
+#[derive(Debug, Default)]
+pub struct EventBuilder {
+    name: Option<String>,
+    message: String,
+    date: Option<DateTime<Local>>,
+    recurrence: RecurrencePattern,
+    priority: Priority,
+    tags: Vec<String>,
+    calendar: Option<String>,
+}
+
+impl EventBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn date(mut self, date: DateTime<Local>) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    pub fn recurrence(mut self, recurrence: RecurrencePattern) -> Self {
+        self.recurrence = recurrence;
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn calendar(mut self, calendar: impl Into<String>) -> Self {
+        self.calendar = Some(calendar.into());
+        self
+    }
+
+    /// The event, validated like one entered at the prompts. Its id is 0
+    /// and its uuid empty until the database assigns them.
+    pub fn build(self) -> Result<Event, NotifyMeError> {
+        let Some(name) = self.name else {
+            return Err("An event needs a name".into());
+        };
+        let Some(date) = self.date else {
+            return Err("An event needs a date".into());
+        };
+        validate_event_name(&name)?;
+        validate_event_message(&self.message)?;
+
+        let now = Utc::now();
+
+        Ok(Event {
+            id: 0,
+            name: name.trim().to_string(),
+            message: self.message,
+            recurrence_pattern: self.recurrence,
+            date,
+            deleted_at: None,
+            last_notified_at: None,
+            created_at: now,
+            updated_at: now,
+            tags: self.tags,
+            uuid: String::new(),
+            calendar: self
+                .calendar
+                .unwrap_or_else(|| DEFAULT_CALENDAR.to_string()),
+            completed: false,
+            priority: self.priority,
+            location: None,
+            url: None,
+            color: None,
+            paused: false,
+            timezone: None,
+        })
+    }
+}
+
+/// A row of `events` that couldn't be read as an `Event`.
+#[derive(Debug)]
+pub struct SkippedRow {
+    pub id: i32,
+    pub error: String,
+}
+
+impl fmt::Display for SkippedRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "event {}: {}", self.id, self.error)
+    }
+}
+
+/// Splits rows mapped with `Event::try_from_row` into the events and the
+/// rows that didn't map, logging each of those.
+pub fn partition_rows<I>(rows: I) -> rusqlite::Result<(Vec<Event>, Vec<SkippedRow>)>
+where
+    I: Iterator<Item = rusqlite::Result<Result<Event, SkippedRow>>>,
+{
+    let mut events = Vec::new();
+    let mut skipped = Vec::new();
+
+    for row in rows {
+        match row? {
+            Ok(event) => events.push(event),
+            Err(row) => {
+                warn!(id = row.id, error = %row.error, "Skipping unreadable event");
+                skipped.push(row);
+            }
+        }
+    }
+
+    Ok((events, skipped))
+}
+
+/// Events printed one after another, as `notify_me list` shows them.
 pub struct EventList(pub Vec<Event>);
 
 impl fmt::Display for EventList {
@@ -121,3 +1109,73 @@ impl fmt::Display for EventList {
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Active,
+    Deleted,
+    Completed,
+    Past,
+}
+
+impl Status {
+    fn marker(self) -> &'static str {
+        match self {
+            Status::Completed => "[\u{2713}]",
+            Status::Deleted => "[\u{2717}]",
+            Status::Past => "[\u{23f8}]",
+            Status::Active => "[ ]",
+        }
+    }
+}
+
+impl From<Status> for &str {
+    fn from(value: Status) -> Self {
+        match value {
+            Status::Active => "active",
+            Status::Deleted => "deleted",
+            Status::Completed => "completed",
+            Status::Past => "past",
+        }
+    }
+}
+
+/// An event with where it stands as of `now`: done, deleted, active but
+/// already past, or still upcoming.
+#[derive(Debug)]
+pub struct EventWithStatus {
+    pub event: Event,
+    pub status: Status,
+}
+
+impl EventWithStatus {
+    pub fn new(event: Event, now: DateTime<Utc>) -> Self {
+        let status = if event.completed {
+            Status::Completed
+        } else if event.deleted_at.is_some() {
+            Status::Deleted
+        } else if event.date < now {
+            Status::Past
+        } else {
+            Status::Active
+        };
+
+        Self { event, status }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut json = self.event.to_json();
+        if let Value::Object(fields) = &mut json {
+            let status: &str = self.status.into();
+            fields.push(("status".to_string(), status.into()));
+        }
+
+        json
+    }
+}
+
+impl fmt::Display for EventWithStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status.marker(), self.event)
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::event::{Event, Priority, RecurrencePattern, LAST_WEEKDAY};
+
+/// Longest content line allowed by RFC 5545, in octets, before folding.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// An iCalendar document holding `event` alone, with an alarm `alarm_before`
+/// its start, for handing a single event to another calendar app.
+pub fn single_event_calendar(event: &Event, alarm_before: Duration) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        concat!("PRODID:-//", env!("CARGO_PKG_NAME"), "//EN").to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}@{}", event.uuid, env!("CARGO_PKG_NAME")),
+        format!("DTSTAMP:{}", format_date(&Utc::now())),
+        format!("DTSTART:{}", format_date(&event.date)),
+        format!("DTEND:{}", format_date(&event.date)),
+        format!("SUMMARY:{}", escape(&event.name)),
+    ];
+
+    if !event.message.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape(&event.message)));
+    }
+
+    if let Some(location) = &event.location {
+        lines.push(format!("LOCATION:{}", escape(location)));
+    }
+
+    if let Some(url) = &event.url {
+        lines.push(format!("URL:{}", url));
+    }
+
+    if !event.tags.is_empty() {
+        let tags = event.tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+
+    // RFC 5545 priorities run from 1 (highest) to 9 (lowest).
+    match event.priority {
+        Priority::High => lines.push("PRIORITY:1".to_string()),
+        Priority::Normal => (),
+        Priority::Low => lines.push("PRIORITY:9".to_string()),
+    }
+
+    match event.recurrence_pattern {
+        RecurrencePattern::Daily => lines.push("RRULE:FREQ=DAILY".to_string()),
+        RecurrencePattern::Weekly => lines.push("RRULE:FREQ=WEEKLY".to_string()),
+        RecurrencePattern::EveryNWeeks(weeks) => {
+            lines.push(format!("RRULE:FREQ=WEEKLY;INTERVAL={}", weeks))
+        }
+        RecurrencePattern::Monthly => lines.push("RRULE:FREQ=MONTHLY".to_string()),
+        RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) => {
+            let position = if nth == LAST_WEEKDAY {
+                "-1".to_string()
+            } else {
+                nth.to_string()
+            };
+            let day = weekday.to_string()[..2].to_uppercase();
+            lines.push(format!("RRULE:FREQ=MONTHLY;BYDAY={}{}", position, day))
+        }
+        RecurrencePattern::Once => (),
+    }
+
+    lines.extend([
+        "BEGIN:VALARM".to_string(),
+        "ACTION:DISPLAY".to_string(),
+        format!("DESCRIPTION:{}", escape(&event.name)),
+        format!("TRIGGER:-PT{}M", alarm_before.num_minutes()),
+        "END:VALARM".to_string(),
+        "END:VEVENT".to_string(),
+        "END:VCALENDAR".to_string(),
+    ]);
+
+    lines
+        .iter()
+        .map(|line| fold(line) + "\r\n")
+        .collect::<String>()
+}
+
+/// A UTC date-time in the basic format, e.g. `20300320T090000Z`.
+fn format_date<Tz: TimeZone>(date: &DateTime<Tz>) -> String {
+    date.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Escapes the characters with a meaning in TEXT values.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits a content line longer than `MAX_LINE_OCTETS` into continuation
+/// lines, which start with a space. Splits never fall inside a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+
+    for char in line.chars() {
+        if octets + char.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the continuation line.
+            octets = 1;
+        }
+
+        folded.push(char);
+        octets += char.len_utf8();
+    }
+
+    folded
+}
@@ -0,0 +1,35 @@
+//! Reminder engine behind the `notify-me` command: events stored in SQLite,
+//! the scheduler that turns them into desktop notifications, and the client
+//! operations the CLI is built from.
+//!
+//! ```no_run
+//! use notify_me::{client::Client, db};
+//!
+//! let conn = db::open("notify_me.db")?;
+//! println!("{}", Client::new(&conn).list_events()?);
+//! # Ok::<(), notify_me::error::NotifyMeError>(())
+//! ```
+
+pub mod backup;
+pub mod client;
+pub mod clock;
+pub mod completions;
+pub mod config;
+mod csv;
+pub mod db;
+pub mod diff;
+pub mod error;
+pub mod event;
+mod ics;
+pub mod json;
+pub mod lock;
+pub mod logging;
+pub mod manpage;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod opener;
+pub mod output;
+mod remind;
+pub mod repository;
+pub mod scheduler;
+pub mod zone;
@@ -0,0 +1,53 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+};
+
+/// An exclusive lock held by the running scheduler for as long as it lives,
+/// so maintenance commands can tell whether the database is in use and a
+/// second scheduler doesn't double-fire notifications.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    pub fn acquire(db_path: &Path) -> Result<Self, String> {
+        let path = lock_path(db_path);
+
+        let file = match OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(format!(
+                "The scheduler is running on {} (lock held on {})",
+                db_path.display(),
+                path.display()
+            )),
+            Err(TryLockError::Error(err)) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    /// Whether a scheduler is running on the database at `db_path`, i.e.
+    /// holding its lock. Errors reading the lock count as not running.
+    pub fn is_held(db_path: &Path) -> bool {
+        match File::open(lock_path(db_path)) {
+            Ok(file) => matches!(file.try_lock(), Err(TryLockError::WouldBlock)),
+            Err(_) => false,
+        }
+    }
+}
+
+fn lock_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(".lock");
+
+    PathBuf::from(path)
+}
@@ -0,0 +1,332 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Write as _},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+};
+
+use chrono::{SecondsFormat, Utc};
+use env_filter::{Builder, Filter};
+use log::LevelFilter;
+use tracing::{
+    field::{Field, Visit},
+    span,
+    subscriber::Interest,
+    Event, Level, Metadata, Subscriber,
+};
+use tracing_core::span::Current;
+
+/// Sets the level, e.g. `info` or `notify_me::scheduler=debug`, as with
+/// env_logger.
+pub const LOG_ENV: &str = "RUST_LOG";
+
+static LOGGER: OnceLock<Arc<Logger>> = OnceLock::new();
+
+thread_local! {
+    /// The spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<span::Id>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes tracing events, and the `log` records of dependencies, to stderr
+/// and optionally a file, one line each with the enclosing spans' fields:
+///
+/// ```text
+/// [2030-03-20T09:00:00Z INFO  notify_me::scheduler] tick{at=2030-03-20T09:00:00Z}: Notifying id=3 name="Dentist"
+/// ```
+struct Logger {
+    stderr: Filter,
+    file: Mutex<Option<File>>,
+    /// `RUST_LOG` when set, `info` otherwise: a log file is where to look
+    /// when an event didn't fire, so it keeps more than the terminal does.
+    file_filter: Filter,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    next_id: AtomicU64,
+}
+
+struct SpanData {
+    metadata: &'static Metadata<'static>,
+    fields: String,
+    refs: usize,
+}
+
+/// Installs the logger, writing to stderr at the level `RUST_LOG` sets,
+/// errors only by default. A `verbose` count of 1, 2 or more sets that
+/// default to info, debug or trace; modules `RUST_LOG` names keep theirs. A second call keeps the first logger.
+pub fn init(verbose: u8) {
+    let file_filter = match std::env::var(LOG_ENV) {
+        Ok(filters) => Builder::new().parse(&filters).build(),
+        Err(_) => Builder::new().filter_level(LevelFilter::Info).build(),
+    };
+    let mut stderr = Builder::from_env(LOG_ENV);
+    if verbose > 0 {
+        stderr.filter_level(match verbose {
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        });
+    }
+    let logger = Arc::new(Logger {
+        stderr: stderr.build(),
+        file: Mutex::new(None),
+        file_filter,
+        spans: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+    });
+
+    if LOGGER.set(Arc::clone(&logger)).is_err() {
+        return;
+    }
+    let _ = tracing::subscriber::set_global_default(Arc::clone(&logger));
+    if log::set_boxed_logger(Box::new(LogBridge(logger))).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+}
+
+/// Also appends everything to `path` from now on, creating the file and
+/// its directory as needed.
+pub fn log_to_file(path: &Path) -> io::Result<()> {
+    let Some(logger) = LOGGER.get() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *lock(&logger.file) = Some(file);
+
+    Ok(())
+}
+
+impl Logger {
+    fn enabled_for(&self, level: log::Level, target: &str) -> (bool, bool) {
+        let metadata = log::Metadata::builder().level(level).target(target).build();
+        let to_file = self.file_filter.enabled(&metadata) && lock(&self.file).is_some();
+
+        (self.stderr.enabled(&metadata), to_file)
+    }
+
+    fn write(&self, level: log::Level, target: &str, message: fmt::Arguments<'_>) {
+        let (to_stderr, to_file) = self.enabled_for(level, target);
+        if !to_stderr && !to_file {
+            return;
+        }
+
+        let line = format!(
+            "[{} {:<5} {}] {}{}\n",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            level,
+            target,
+            self.context(),
+            message
+        );
+
+        // Nowhere left to report a failed write to.
+        if to_stderr {
+            let _ = io::stderr().lock().write_all(line.as_bytes());
+        }
+        if to_file {
+            if let Some(file) = lock(&self.file).as_mut() {
+                let _ = file.write_all(line.as_bytes());
+            }
+        }
+    }
+
+    /// The spans entered on this thread, as `outer{a=1}:inner{b=2}: `.
+    fn context(&self) -> String {
+        let spans = lock(&self.spans);
+        let mut context = String::new();
+
+        ENTERED.with(|entered| {
+            for id in entered.borrow().iter() {
+                if let Some(span) = spans.get(&id.into_u64()) {
+                    context.push_str(span.metadata.name());
+                    if !span.fields.is_empty() {
+                        write!(context, "{{{}}}", span.fields.trim_start()).unwrap();
+                    }
+                    context.push(':');
+                }
+            }
+        });
+        if !context.is_empty() {
+            context.push(' ');
+        }
+
+        context
+    }
+}
+
+impl Subscriber for Logger {
+    // Whether a file is attached changes at runtime, so nothing is cached.
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let (to_stderr, to_file) = self.enabled_for(log_level(metadata.level()), metadata.target());
+        to_stderr || to_file
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.spans).insert(
+            id,
+            SpanData {
+                metadata: attrs.metadata(),
+                fields: fields.all(),
+                refs: 1,
+            },
+        );
+
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+
+        if let Some(span) = lock(&self.spans).get_mut(&span.into_u64()) {
+            span.fields.push_str(&fields.all());
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let metadata = event.metadata();
+        self.write(
+            log_level(metadata.level()),
+            metadata.target(),
+            format_args!("{}{}", fields.message, fields.rest),
+        );
+    }
+
+    fn enter(&self, span: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.clone()));
+    }
+
+    fn exit(&self, span: &span::Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|id| id == span) {
+                entered.remove(position);
+            }
+        });
+    }
+
+    fn current_span(&self) -> Current {
+        let Some(id) = ENTERED.with(|entered| entered.borrow().last().cloned()) else {
+            return Current::none();
+        };
+
+        match lock(&self.spans).get(&id.into_u64()) {
+            Some(span) => Current::new(id, span.metadata),
+            None => Current::none(),
+        }
+    }
+
+    fn clone_span(&self, span: &span::Id) -> span::Id {
+        if let Some(data) = lock(&self.spans).get_mut(&span.into_u64()) {
+            data.refs += 1;
+        }
+
+        span.clone()
+    }
+
+    fn try_close(&self, span: span::Id) -> bool {
+        let mut spans = lock(&self.spans);
+        let Some(data) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+
+        data.refs -= 1;
+        if data.refs == 0 {
+            spans.remove(&span.into_u64());
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Routes the `log` records of dependencies through the same filters and
+/// outputs.
+struct LogBridge(Arc<Logger>);
+
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        let (to_stderr, to_file) = self.0.enabled_for(metadata.level(), metadata.target());
+        to_stderr || to_file
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        self.0
+            .write(record.level(), record.target(), *record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+/// An event's message, and its other fields as ` key=value`.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Fields {
+    fn all(self) -> String {
+        if self.message.is_empty() {
+            self.rest
+        } else {
+            format!(" message={:?}{}", self.message, self.rest)
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            write!(self.rest, " {}={:?}", field.name(), value).unwrap();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.message, "{:?}", value).unwrap();
+        } else {
+            write!(self.rest, " {}={:?}", field.name(), value).unwrap();
+        }
+    }
+}
+
+fn log_level(level: &Level) -> log::Level {
+    match *level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+/// Logging carries on past a panic elsewhere that poisoned a lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
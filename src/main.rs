@@ -1,52 +1,2183 @@
-mod client;
-mod event;
-mod scheduler;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind, IsTerminal, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+};
 
-use clap::{Arg, ArgAction, Command};
-use client::Client;
-use log::info;
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use clap::{parser::ValueSource, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use notify_me::{
+    backup::{self, PreOpBackup, Prefer},
+    client::{self, Client, MonthCalendar, OnConflict, OverdueEvents, PurgeTarget, WeekCalendar},
+    completions::{self, Shell},
+    config::{self, Config, Setting, Source},
+    db,
+    error::{self, NotifyMeError},
+    event::{self, EventSort, EventWithStatus, Priority, RecurrencePattern},
+    json::Value,
+    lock::InstanceLock,
+    logging, manpage, opener, output,
+    repository::NewEvent,
+    scheduler::{self, DesktopNotifier, Scheduler, SchedulerBuilder},
+    zone::Zone,
+};
 use rusqlite::Connection;
-use scheduler::Scheduler;
+use tracing::{info, warn};
+
+/// `println!` for informational messages, which `--quiet` leaves out.
+/// Results, such as listings, are printed either way.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !output::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// The installed binary, as completion scripts refer to it.
+const BIN_NAME: &str = env!("CARGO_BIN_NAME");
+
+/// How often `--watch` redraws the screen.
+const WATCH_REFRESH_SECS: u64 = 30;
+
+/// Width `cal --week` lays out for when stdout isn't a terminal.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Version plus the commit, build date and host triple, for bug reports.
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("NOTIFY_ME_GIT_SHA"),
+    " ",
+    env!("NOTIFY_ME_BUILD_DATE"),
+    " ",
+    env!("NOTIFY_ME_HOST_TRIPLE"),
+    ")"
+);
 
 #[tokio::main]
-async fn main() -> Result<(), String> {
-    env_logger::init();
-
-    let args = Command::new("NotifyMe")
-        .version("1.0")
-        .arg(
-            Arg::new("client")
-                .short('c')
-                .long("client")
-                .help("Execute as client")
-                .action(ArgAction::SetTrue),
-        )
-        .get_matches();
+async fn main() -> ExitCode {
+    restore_sigpipe();
 
-    let conn = Connection::open("notify_me.db").unwrap();
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(NotifyMeError::Usage(err)) => {
+            // clap formats its own message, help and version included.
+            let _ = err.print();
+            if err.use_stderr() {
+                ExitCode::from(NotifyMeError::Usage(err).exit_code())
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(err @ NotifyMeError::NoMigrationsPending) => {
+            status!("{}", err);
+            ExitCode::from(err.exit_code())
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS events (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            message TEXT NOT NULL,
-            recurrence_pattern TEXT NOT NULL,
-            date TEXT NOT NULL,
-            deleted_at TEXT DEFAULT NULL
-        )",
-        (),
+/// Rust ignores SIGPIPE, turning a closed pipe into a panic on the next
+/// `println!`. Dying of the signal instead lets `notify-me list | head`
+/// exit quietly, like any other command line tool.
+#[cfg(unix)]
+fn restore_sigpipe() {
+    // SAFETY: runs before any other thread starts writing output.
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+fn restore_sigpipe() {}
+
+/// The command line, also walked by `completions` and `manpage`.
+fn cli() -> Command {
+    Command::new("NotifyMe")
+    .version(VERSION)
+    .about("Desktop notifications for reminders kept in a local database")
+    .after_help(exit_status_help())
+    .arg(
+        Arg::new("config")
+            .long("config")
+            .value_name("PATH")
+            .help("Configuration file [default: ~/.config/notify_me/config.toml]")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("log-file")
+            .long("log-file")
+            .value_name("PATH")
+            .help("Append log lines to this file as well as stderr [default: log_file from the config file]")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .help("Print only results, such as listings and the ids of created events, and leave success to the exit status")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("verbose")
+            .global(true),
+    )
+    .arg(
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Log more to stderr: info with -v, debug with -vv, trace with -vvv")
+            .action(ArgAction::Count)
+            .global(true),
+    )
+    .arg(
+        Arg::new("no-backup")
+            .long("no-backup")
+            .help("Skip the automatic backup before migrations, purges, restores and bulk updates")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("import-remind")
+            .long("import-remind")
+            .value_name("PATH")
+            .help("Import events from a remind(1) file")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("import-csv")
+            .long("import-csv")
+            .value_name("PATH")
+            .help("Import events from a CSV file with name and date columns")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("csv-has-header")
+            .long("csv-has-header")
+            .value_name("BOOL")
+            .help(format!(
+                "Whether the CSV file's first row names its columns; without one they are {}",
+                client::CSV_COLUMNS.join(", ")
+            ))
+            .value_parser(value_parser!(bool))
+            .default_value("true")
+            .num_args(0..=1)
+            .default_missing_value("true")
+            .requires("import-csv"),
+    )
+    .arg(
+        Arg::new("max-notifications-per-tick")
+            .long("max-notifications-per-tick")
+            .value_name("N")
+            .help("Maximum number of notifications the scheduler fires per tick [default: 5]")
+            .value_parser(value_parser!(u32).range(1..)),
+    )
+    .arg(
+        Arg::new("notify-missed")
+            .long("notify-missed")
+            .help("On startup, notify recurring events once for occurrences missed while the scheduler wasn't running")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("skip-db-check")
+            .long("skip-db-check")
+            .help("Don't check that the database is writable, for read-only audits")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("no-color")
+            .long("no-color")
+            .help("Disable colored output, as does setting NO_COLOR")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("generate-config")
+            .long("generate-config")
+            .value_name("PATH")
+            .help("Write an example config file with every setting and what it does to PATH, or to stdout")
+            .value_parser(value_parser!(PathBuf))
+            .num_args(0..=1),
+    )
+    .arg(
+        Arg::new("overwrite")
+            .long("overwrite")
+            .help("Let --generate-config replace an existing file without asking")
+            .requires("generate-config")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("check-notify")
+            .long("check-notify")
+            .help("Send a test notification and report what is missing if it fails")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("ansi-test")
+            .long("ansi-test")
+            .help("Print the styles notify_me uses, to check terminal support")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("list-config")
+            .long("list-config")
+            .help("Print the settings in effect and where each one comes from")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("reset")
+            .long("reset")
+            .help("Delete every event and recreate an empty database, after confirmation")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("bulk-set-recurrence")
+            .long("bulk-set-recurrence")
+            .help("Change the recurrence of every event repeating --from to --to, after confirmation")
+            .requires_all(["from", "to"])
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("from")
+            .long("from")
+            .value_name("PATTERN")
+            .help("Recurrence the events to change have now")
+            .requires("bulk-set-recurrence")
+            .value_parser(event::parse_recurrence),
+    )
+    .arg(
+        Arg::new("to")
+            .long("to")
+            .value_name("PATTERN")
+            .help("Recurrence to give them")
+            .requires("bulk-set-recurrence")
+            .value_parser(event::parse_recurrence),
+    )
+    .arg(
+        Arg::new("force")
+            .long("force")
+            .help("Skip the --reset or --bulk-set-recurrence confirmation, for scripts")
+            .requires("confirmed")
+            .action(ArgAction::SetTrue),
+    )
+    .group(ArgGroup::new("confirmed").args(["reset", "bulk-set-recurrence"]))
+    .arg(
+        Arg::new("migrate-db")
+            .long("migrate-db")
+            .help("Apply pending schema migrations, listing each; exits 2 when there were none")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("dry-run-migrate")
+            .long("dry-run-migrate")
+            .help("List the schema migrations --migrate-db would apply; exits 2 when there are none")
+            .conflicts_with("migrate-db")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("force-check")
+            .long("force-check")
+            .help("Run one scheduler check now, catching up on missed events, and exit")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("interval")
+            .long("interval")
+            .value_name("SECONDS")
+            .help("Seconds between scheduler ticks [default: 60]")
+            .value_parser(value_parser!(u64).range(10..)),
+    )
+    .arg(
+        Arg::new("export-prometheus-metrics")
+            .long("export-prometheus-metrics")
+            .value_name("ADDRESS")
+            .help("While the scheduler runs, serve Prometheus metrics at http://ADDRESS/metrics, e.g. 127.0.0.1:9898 (needs a build with --features metrics)")
+            .value_parser(value_parser!(SocketAddr)),
+    )
+    .arg(
+        Arg::new("diff-db")
+            .long("diff-db")
+            .value_name("PATH")
+            .help("Compare every event with another database: > only here, < only there, ~ changed")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("export-remind")
+            .long("export-remind")
+            .value_name("PATH")
+            .help("Write every event to a remind file")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("export-shell-script")
+            .long("export-shell-script")
+            .value_name("PATH")
+            .help("Write a shell script that recreates every event")
+            .value_parser(value_parser!(PathBuf)),
+    )
+    .arg(
+        Arg::new("create")
+            .long("create")
+            .help("Create an event without prompting")
+            .action(ArgAction::SetTrue)
+            .requires_all(["name", "date"]),
+    )
+    .arg(
+        Arg::new("preview-recurrence")
+            .long("preview-recurrence")
+            .help("Print the dates an event at --date repeating by --recurrence would move to")
+            .action(ArgAction::SetTrue)
+            .requires("date"),
+    )
+    .arg(
+        Arg::new("count")
+            .long("count")
+            .help("How many dates --preview-recurrence prints")
+            .value_parser(value_parser!(u32).range(1..))
+            .default_value("5")
+            .requires("preview-recurrence"),
+    )
+    .group(ArgGroup::new("dated").args(["create", "preview-recurrence"]))
+    .arg(
+        Arg::new("name")
+            .long("name")
+            .help("Name of the event to create")
+            .requires("create"),
+    )
+    .arg(
+        Arg::new("message")
+            .long("message")
+            .help("Message of the event to create")
+            .requires("create")
+            .default_value(""),
+    )
+    .arg(
+        Arg::new("date")
+            .long("date")
+            .value_name("dd/mm/yyyy hh:mm")
+            .help("Date of the event to create")
+            .requires("dated"),
+    )
+    .arg(
+        Arg::new("recurrence")
+            .long("recurrence")
+            .help("Recurrence of the event to create: once, daily, weekly, monthly, every_n_weeks:N or nth_weekday:N:DAY")
+            .value_parser(event::parse_recurrence)
+            .requires("dated")
+            .default_value("once"),
+    )
+    .arg(
+        Arg::new("priority")
+            .long("priority")
+            .help("Priority of the event to create")
+            .value_parser(["low", "normal", "high"])
+            .requires("create")
+            .default_value("normal"),
+    )
+    .arg(
+        Arg::new("location")
+            .long("location")
+            .help("Where the event to create takes place")
+            .requires("create")
+            .default_value(""),
+    )
+    .arg(
+        Arg::new("url")
+            .long("url")
+            .help("Link of the event to create, opened from its notification")
+            .requires("create")
+            .default_value(""),
+    )
+    .arg(
+        Arg::new("color")
+            .long("color")
+            .value_name("COLOR")
+            .help("Color label of the event to create: a color name or #rrggbb")
+            .requires("create")
+            .default_value(""),
+    )
+    .arg(
+        Arg::new("tz")
+            .long("tz")
+            .value_name("ZONE")
+            .help("IANA time zone of the event to create, e.g. Europe/Berlin [default: local time]")
+            .requires("create")
+            .default_value("")
+            .hide_default_value(true),
+    )
+    .arg(
+        Arg::new("tags")
+            .long("tags")
+            .help("Comma-separated tags of the event to create")
+            .requires("create")
+            .default_value(""),
+    )
+    .arg(
+        Arg::new("list-all")
+            .long("list-all")
+            .help("List every event, past, deleted and done ones included, with its status")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("json")
+            .long("json")
+            .help("Print --list-all or --count-by-recurrence as JSON")
+            .requires("json-output")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("count-by-recurrence")
+            .long("count-by-recurrence")
+            .help("Count events outside the trash by how they repeat")
+            .action(ArgAction::SetTrue),
+    )
+    .group(ArgGroup::new("json-output").args(["list-all", "count-by-recurrence"]))
+    .arg(
+        Arg::new("mark-done")
+            .long("mark-done")
+            .value_name("ID|UUID")
+            .help("Move an event to the trash as done"),
+    )
+    .arg(
+        Arg::new("list-tags")
+            .long("list-tags")
+            .help("List every tag with the number of events using it")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("list-upcoming-N")
+            .long("list-upcoming-N")
+            .visible_alias("next")
+            .value_name("N")
+            .help("List the N nearest upcoming events, however far ahead")
+            .value_parser(value_parser!(u32)),
+    )
+    .arg(
+        Arg::new("watch")
+            .long("watch")
+            .help("Keep today's events on screen with a countdown to the next one")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("month")
+            .long("month")
+            .value_name("yyyy-mm")
+            .help("Show a month's events as a calendar [default: this month]")
+            .value_parser(client::parse_month)
+            .num_args(0..=1),
+    )
+    .arg(
+        Arg::new("trash")
+            .long("trash")
+            .help("List the most recently deleted events")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("since")
+            .long("since")
+            .value_name("dd/mm/yyyy")
+            .help("Only list or export events on or after this day; recurring events match on their next occurrence")
+            .value_parser(client::parse_day)
+            .global(true),
+    )
+    .arg(
+        Arg::new("until")
+            .long("until")
+            .value_name("dd/mm/yyyy")
+            .help("Only list or export events on or before this day; recurring events match on their next occurrence")
+            .value_parser(client::parse_day)
+            .global(true),
+    )
+    .arg(
+        Arg::new("calendar")
+            .long("calendar")
+            .value_name("NAME")
+            .help("Calendar to create events in, or to list and delete them from [default: all, creating in 'default']")
+            .global(true),
+    )
+    .arg(
+        Arg::new("sort")
+            .long("sort")
+            .help("Order of listed events")
+            .value_parser(["date", "created", "priority"])
+            .default_value("date"),
+    )
+    .subcommand(
+        Command::new("list")
+            .about("List upcoming events")
+            .arg(
+                Arg::new("tag")
+                    .long("tag")
+                    .value_name("NAME")
+                    .help("Only list events with this tag"),
+            )
+            .arg(
+                Arg::new("filter-tags")
+                    .long("filter-tags")
+                    .value_name("TAG")
+                    .help("Only list events with all of these tags")
+                    .num_args(1..)
+                    .conflicts_with("tag"),
+            )
+            .arg(
+                Arg::new("any-tag")
+                    .long("any-tag")
+                    .help("With --filter-tags, list events with any of the tags instead")
+                    .action(ArgAction::SetTrue)
+                    .requires("filter-tags"),
+            )
+            .arg(
+                Arg::new("overdue")
+                    .long("overdue")
+                    .help("List one-time events whose time passed without a notification, oldest first, offering to acknowledge or reschedule them")
+                    .conflicts_with_all(["tag", "filter-tags"])
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("count")
+            .about("Print how many events match, for scripts")
+            .arg(
+                Arg::new("today")
+                    .long("today")
+                    .help("Only count events today")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["from", "to"]),
+            )
+            .arg(
+                Arg::new("from")
+                    .long("from")
+                    .value_name("dd/mm/yyyy")
+                    .help("Only count events on or after this day")
+                    .value_parser(client::parse_day),
+            )
+            .arg(
+                Arg::new("to")
+                    .long("to")
+                    .value_name("dd/mm/yyyy")
+                    .help("Only count events on or before this day")
+                    .value_parser(client::parse_day),
+            )
+            .arg(
+                Arg::new("recurrence")
+                    .long("recurrence")
+                    .value_name("PATTERN")
+                    .help("Only count events repeating this way: once, daily, weekly, monthly, every_n_weeks:N or nth_weekday:N:DAY")
+                    .value_parser(event::parse_recurrence),
+            )
+            .arg(
+                Arg::new("tag")
+                    .long("tag")
+                    .value_name("NAME")
+                    .help("Only count events with this tag"),
+            )
+            .arg(
+                Arg::new("fail-if-empty")
+                    .long("fail-if-empty")
+                    .help("Exit with status 1 when nothing matches")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("cal")
+            .about("Show this month's events as a calendar, or with --week this week's by hour")
+            .arg(
+                Arg::new("week")
+                    .long("week")
+                    .help("Show a week, Monday to Sunday, with events at their hour")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("next")
+                    .long("next")
+                    .help("Show the following month or week; repeat to go further")
+                    .action(ArgAction::Count)
+                    .conflicts_with("prev"),
+            )
+            .arg(
+                Arg::new("prev")
+                    .long("prev")
+                    .help("Show the previous month or week; repeat to go further")
+                    .action(ArgAction::Count),
+            ),
+    )
+    .subcommand(Command::new("trash").about("List deleted events"))
+    .subcommand(
+        Command::new("next")
+            .about("Print the next event with a countdown on one line, e.g. for a status bar")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .help("json prints an object with text, tooltip and class, as waybar reads")
+                    .value_parser(["text", "json"])
+                    .default_value("text"),
+            ),
+    )
+    .subcommand(
+        Command::new("stats")
+            .about("Summarize the database: events by recurrence, the coming week, the busiest weekday")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .value_parser(["text", "json"])
+                    .default_value("text"),
+            ),
+    )
+    .subcommand(
+        Command::new("search")
+            .about("Search event names and messages")
+            .arg(
+                Arg::new("query")
+                    .value_name("QUERY")
+                    .help("FTS5 query, e.g. `dentist` or `pay*`; without FTS5, words to find")
+                    .required(true),
+            )
+            .arg(
+                Arg::new("snippets")
+                    .long("snippets")
+                    .help("Show the text each event matched on, with the matched words highlighted")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("pause")
+            .about("Stop an event from notifying while keeping its schedule")
+            .arg(
+                Arg::new("id")
+                    .value_name("ID|UUID")
+                    .help("Id, uuid or unique uuid prefix of the event")
+                    .required(true),
+            ),
+    )
+    .subcommand(
+        Command::new("resume").about("Resume a paused event").arg(
+            Arg::new("id")
+                .value_name("ID|UUID")
+                .help("Id, uuid or unique uuid prefix of the event")
+                .required(true),
+        ),
+    )
+    .subcommand(
+        Command::new("open").about("Open the link of an event").arg(
+            Arg::new("id")
+                .value_name("ID|UUID")
+                .help("Id, uuid or unique uuid prefix of the event")
+                .required(true),
+        ),
+    )
+    .subcommand(
+        Command::new("restore")
+            .about("Restore a deleted event")
+            .arg(
+                Arg::new("id")
+                    .value_name("ID|UUID")
+                    .help("Id, uuid or unique uuid prefix of the event")
+                    .required(true),
+            ),
+    )
+    .subcommand(
+        Command::new("purge")
+            .about("Permanently remove deleted events")
+            .arg(
+                Arg::new("id")
+                    .value_name("ID|UUID")
+                    .help("Id, uuid or unique uuid prefix of the deleted event to purge"),
+            )
+            .arg(
+                Arg::new("all-deleted")
+                    .long("all-deleted")
+                    .help("Purge every deleted event")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("older-than")
+                    .long("older-than")
+                    .value_name("AGE")
+                    .help("Purge events deleted longer ago than AGE (e.g. 30d)")
+                    .value_parser(client::parse_age),
+            )
+            .group(
+                ArgGroup::new("target")
+                    .args(["id", "all-deleted", "older-than"])
+                    .required(true),
+            )
+            .arg(
+                Arg::new("yes")
+                    .long("yes")
+                    .short('y')
+                    .help("Don't ask for confirmation")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("backup")
+            .about("Write a snapshot of the database")
+            .arg(
+                Arg::new("path")
+                    .help("Where to write the snapshot [default: a timestamped file in the backups directory]")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+    .subcommand(
+        Command::new("restore-backup")
+            .about("Replace the database with a backup")
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("merge")
+                    .long("merge")
+                    .help("Import the backup's missing events instead of replacing the database")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("export")
+            .about("Export every event, deleted ones included, or a single one")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Write a JSON document")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("ics")
+                    .long("ics")
+                    .value_name("ID|UUID")
+                    .help("Write one event as an iCalendar file, to the given path"),
+            )
+            .group(
+                ArgGroup::new("format")
+                    .args(["json", "ics"])
+                    .required(true),
+            )
+            .arg(
+                Arg::new("pretty")
+                    .long("pretty")
+                    .help("Indent the output")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("path")
+                    .help("Where to write the export [default: stdout]")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+    .subcommand(
+        Command::new("import")
+            .about("Import events from an export")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .value_name("PATH")
+                    .help("Document written by export --json")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("on-conflict")
+                    .long("on-conflict")
+                    .help("What to do with events matching an existing one by name and date")
+                    .value_parser(value_parser!(OnConflict))
+                    .default_value("skip"),
+            )
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .help("Print what would change without importing anything")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("merge")
+            .about("Combine events from another notify_me database, matching them by uuid")
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("prefer")
+                    .long("prefer")
+                    .help("Which side wins when both edited an event")
+                    .value_parser(["local", "other", "newest"])
+                    .default_value("newest"),
+            ),
     )
-    .unwrap();
+    .subcommand(
+        Command::new("calendars")
+            .about("List, rename and mute calendars")
+            .subcommand(Command::new("list").about("List calendars with their event counts"))
+            .subcommand(
+                Command::new("rename")
+                    .about("Rename a calendar, merging it into an existing one of that name")
+                    .arg(Arg::new("from").required(true))
+                    .arg(Arg::new("to").required(true)),
+            )
+            .subcommand(
+                Command::new("mute")
+                    .about("Stop notifying events of a calendar")
+                    .arg(Arg::new("name").required(true)),
+            )
+            .subcommand(
+                Command::new("unmute")
+                    .about("Notify events of a calendar again")
+                    .arg(Arg::new("name").required(true)),
+            ),
+    )
+    .subcommand(
+        Command::new("completions")
+            .about("Print a shell completion script")
+            .arg(
+                Arg::new("shell")
+                    .required(true)
+                    .value_parser(value_parser!(Shell)),
+            ),
+    )
+    .subcommand(
+        Command::new("serve")
+            .about("Run the scheduler, showing notifications as events come due (the default in a terminal; elsewhere, today's events are printed)"),
+    )
+    .subcommand(Command::new("interactive").about(
+        "Browse and edit events from a menu, without running the scheduler",
+    ))
+    .subcommand(Command::new("run").about(
+        "Run the scheduler in the background and the interactive menu in the foreground",
+    ))
+    .subcommand(Command::new(completions::COMPLETE_IDS_COMMAND).hide(true))
+    .subcommand(Command::new("version").about(
+        "Print the version; with --verbose also the build, SQLite and schema versions and features, for bug reports",
+    ))
+    .subcommand(
+        Command::new("man")
+            .about("Print the man page")
+            .arg(
+                Arg::new("dir")
+                    .help("Directory to write the page into instead of stdout")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+    .subcommand(
+        Command::new("db")
+            .about("Database maintenance")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("check")
+                    .about("Check the database's integrity and look for orphaned or unreadable rows")
+                    .arg(
+                        Arg::new("fix")
+                            .long("fix")
+                            .help("Delete orphaned rows")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(Command::new("vacuum").about("Reclaim space left by deleted events"))
+            .subcommand(Command::new("encrypt").about(
+                "Encrypt the database with a key from NOTIFY_ME_DB_KEY or a prompt",
+            ))
+            .subcommand(Command::new("decrypt").about("Rewrite an encrypted database as plaintext")),
+    )
+}
+
+async fn run() -> Result<(), NotifyMeError> {
+    let args = cli().try_get_matches()?;
+
+    logging::init(args.get_count("verbose"));
+    output::configure(args.get_flag("no-color"));
+    output::set_quiet(args.get_flag("quiet"));
+
+    if let Some(("completions", sub_args)) = args.subcommand() {
+        let shell = *sub_args.get_one::<Shell>("shell").unwrap();
+        print!("{}", completions::generate(shell, &mut cli(), BIN_NAME));
+        return Ok(());
+    }
+
+    if let Some(("man", sub_args)) = args.subcommand() {
+        let page = manpage::render(&mut cli(), BIN_NAME);
+        match sub_args.get_one::<PathBuf>("dir") {
+            Some(dir) => {
+                let path = dir.join(format!("{}.1", BIN_NAME));
+                std::fs::write(&path, page)?;
+                status!("Wrote {}", path.display());
+            }
+            None => print!("{}", page),
+        }
+        return Ok(());
+    }
+
+    if args.get_flag("ansi-test") {
+        print_ansi_test();
+        return Ok(());
+    }
+
+    if args.get_flag("check-notify") {
+        return check_notify();
+    }
+
+    if args.contains_id("generate-config") {
+        return generate_config(
+            args.get_one::<PathBuf>("generate-config"),
+            args.get_flag("overwrite"),
+        );
+    }
+
+    let config = Config::load(args.get_one::<PathBuf>("config").map(PathBuf::as_path))
+        .map_err(NotifyMeError::Config)?;
+
+    if args.get_flag("list-config") {
+        print_config(&config, &args);
+        return Ok(());
+    }
 
-    if !args.get_flag("client") {
-        info!("Starting scheduler");
-        let scheduler = Scheduler::new(&conn);
+    if let Some(("version", _)) = args.subcommand() {
+        print_version(&config, args.get_count("verbose") > 0);
+        return Ok(());
+    }
+
+    let log_file = args
+        .get_one::<PathBuf>("log-file")
+        .or(config.log_file.as_ref());
+    if let Some(path) = log_file {
+        logging::log_to_file(path).map_err(|err| {
+            NotifyMeError::Config(format!("Cannot open log file {}: {}", path.display(), err))
+        })?;
+    }
+
+    let range = client::date_range(
+        args.get_one::<NaiveDate>("since").copied(),
+        args.get_one::<NaiveDate>("until").copied(),
+    )?;
+
+    let calendar = args.get_one::<String>("calendar").cloned();
+
+    let no_backup = args.get_flag("no-backup");
+    let mut conn = db::connect(&config.db_path).map_err(|err| {
+        NotifyMeError::DatabaseAccess(format!(
+            "Cannot open database at {}: {}{}",
+            config.db_path.display(),
+            err,
+            db_path_hint(&config)
+        ))
+    })?;
+
+    if !args.get_flag("skip-db-check") {
+        if let Err(err) = db::check_db_writable(&conn) {
+            return Err(NotifyMeError::DatabaseAccess(format!(
+                "Cannot write to database at {}: {}{} Pass --skip-db-check to only read it.",
+                config.db_path.display(),
+                err,
+                db_path_hint(&config)
+            )));
+        }
+    }
+
+    if args.get_flag("reset") {
+        let _lock = InstanceLock::acquire(&config.db_path)?;
+
+        if !args.get_flag("force") && !confirm_reset()? {
+            status!("Reset cancelled");
+            return Ok(());
+        }
+
+        pre_op_backup(&conn, &config, no_backup, "reset")?;
+        db::reset(&mut conn)?;
+        status!(
+            "Database reset. {} events remain.",
+            Client::new(&conn).list_events()?.0.len()
+        );
 
-        scheduler.start().await;
+        return Ok(());
     }
 
-    let client = Client::new(&conn);
-    client.start();
+    if args.get_flag("migrate-db") || args.get_flag("dry-run-migrate") {
+        let pending = db::pending_migrations(&conn)?;
+        if pending.is_empty() {
+            return Err(NotifyMeError::NoMigrationsPending);
+        }
+
+        if args.get_flag("dry-run-migrate") {
+            for migration in pending {
+                println!(
+                    "Would apply migration {}: {}",
+                    migration.version, migration.description
+                );
+            }
+            return Ok(());
+        }
+
+        if db::user_version(&conn)? > 0 {
+            pre_op_backup(&conn, &config, no_backup, "migrate")?;
+        }
+        for migration in db::migrate(&mut conn)? {
+            println!(
+                "Applied migration {}: {}",
+                migration.version, migration.description
+            );
+        }
+
+        return Ok(());
+    }
+
+    let version = db::user_version(&conn)?;
+    if version > 0 && version < db::schema_version() {
+        pre_op_backup(&conn, &config, no_backup, "migrate")?;
+    }
+    db::migrate(&mut conn)?;
+
+    if let Some(("restore-backup", sub_args)) = args.subcommand() {
+        let path = sub_args.get_one::<PathBuf>("path").unwrap();
+
+        if sub_args.get_flag("merge") {
+            pre_op_backup(&conn, &config, no_backup, "merge")?;
+            let merged = backup::merge(&conn, path)?;
+            status!("Merged {} events from {}", merged, path.display());
+        } else {
+            let _lock = InstanceLock::acquire(&config.db_path)?;
+            backup::validate(path)?;
+            pre_op_backup(&conn, &config, no_backup, "restore")?;
+            drop(conn);
+
+            let aside = backup::restore(&config.db_path, path)?;
+            db::open(&config.db_path)?;
+            status!(
+                "Restored {} (previous database kept at {})",
+                path.display(),
+                aside.display()
+            );
+        }
+
+        return Ok(());
+    }
+
+    match args.subcommand() {
+        Some(("list", sub_args)) if sub_args.get_flag("overdue") => {
+            let client = Client::new(&conn);
+            let events = client.fetch_overdue_events()?;
+            let ids: Vec<i32> = events.0.iter().map(|event| event.id).collect();
+            if ids.is_empty() {
+                status!("No overdue events");
+                return Ok(());
+            }
+            println!(
+                "{}",
+                OverdueEvents {
+                    events,
+                    now: Local::now(),
+                }
+            );
+
+            if io::stdin().is_terminal() && io::stdout().is_terminal() {
+                client.resolve_overdue(&ids)?;
+            }
+
+            return Ok(());
+        }
+        Some(("list", sub_args)) => {
+            let events = Client::new(&conn)
+                .with_sort(EventSort::from(
+                    args.get_one::<String>("sort").unwrap().as_str(),
+                ))
+                .with_range(range)
+                .with_calendar(calendar)
+                .with_tags(
+                    &sub_args
+                        .get_many::<String>("filter-tags")
+                        .or(sub_args.get_many::<String>("tag"))
+                        .unwrap_or_default()
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    sub_args.get_flag("any-tag"),
+                )
+                .list_events()?;
+            if events.0.is_empty() {
+                status!("No events");
+            } else {
+                println!("{}", events);
+            }
+
+            return Ok(());
+        }
+        Some(("count", sub_args)) => {
+            let range = if sub_args.get_flag("today") {
+                let today = Local::now().date_naive();
+                client::date_range(Some(today), Some(today))?
+            } else {
+                client::date_range(
+                    sub_args
+                        .get_one::<NaiveDate>("from")
+                        .or(args.get_one::<NaiveDate>("since"))
+                        .copied(),
+                    sub_args
+                        .get_one::<NaiveDate>("to")
+                        .or(args.get_one::<NaiveDate>("until"))
+                        .copied(),
+                )?
+            };
+            let count = Client::new(&conn)
+                .with_range(range)
+                .with_calendar(calendar)
+                .with_tag(sub_args.get_one::<String>("tag").cloned())
+                .with_recurrence(sub_args.get_one::<RecurrencePattern>("recurrence").copied())
+                .count_events()?;
+            println!("{}", count);
+
+            if count == 0 && sub_args.get_flag("fail-if-empty") {
+                return Err("no events".into());
+            }
+
+            return Ok(());
+        }
+        Some(("cal", sub_args)) => {
+            let client = Client::new(&conn).with_calendar(calendar);
+            let shift =
+                i64::from(sub_args.get_count("next")) - i64::from(sub_args.get_count("prev"));
+            let today = Local::now().date_naive();
+
+            if sub_args.get_flag("week") {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                let start = monday + Duration::weeks(shift);
+                let width = console::Term::stdout()
+                    .size_checked()
+                    .map_or(DEFAULT_WIDTH, |(_, columns)| columns.into());
+                println!(
+                    "{}",
+                    WeekCalendar {
+                        start,
+                        events: client.fetch_events_in_week(start)?,
+                        width,
+                    }
+                );
+            } else {
+                let months = today.year() * 12 + today.month0() as i32 + shift as i32;
+                let (year, month) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+                println!(
+                    "{}",
+                    MonthCalendar {
+                        year,
+                        month,
+                        events: client.fetch_events_in_month(year, month)?,
+                    }
+                );
+            }
+
+            return Ok(());
+        }
+        Some(("next", sub_args)) => {
+            let client = Client::new(&conn).with_calendar(calendar);
+            return print_next(
+                &client,
+                sub_args.get_one::<String>("format").unwrap() == "json",
+            );
+        }
+        Some(("stats", sub_args)) => {
+            let stats = Client::new(&conn).stats()?;
+            if sub_args.get_one::<String>("format").unwrap() == "json" {
+                println!("{}", stats.to_json());
+            } else {
+                println!("{}", stats);
+            }
+
+            return Ok(());
+        }
+        Some(("search", sub_args)) if sub_args.get_flag("snippets") => {
+            let hits = Client::new(&conn)
+                .with_calendar(calendar)
+                .search_with_snippets(sub_args.get_one::<String>("query").unwrap())?;
+            if hits.0.is_empty() {
+                status!("No events");
+            } else {
+                println!("{}", hits);
+            }
+
+            return Ok(());
+        }
+        Some(("search", sub_args)) => {
+            let events = Client::new(&conn)
+                .with_calendar(calendar)
+                .full_text_search(sub_args.get_one::<String>("query").unwrap())?;
+            if events.0.is_empty() {
+                status!("No events");
+            } else {
+                println!("{}", events);
+            }
+
+            return Ok(());
+        }
+        Some((completions::COMPLETE_IDS_COMMAND, _)) => {
+            for event in Client::new(&conn).list_events()?.0 {
+                println!("{}\t{}", event.id, event.name);
+            }
+
+            return Ok(());
+        }
+        Some(("trash", _)) => {
+            return print_trash(&Client::new(&conn).with_range(range).with_calendar(calendar))
+        }
+        Some((command @ ("pause" | "resume"), sub_args)) => {
+            let client = Client::new(&conn);
+            let id = client.resolve_event_id(sub_args.get_one::<String>("id").unwrap())?;
+            let paused = command == "pause";
+            client.set_paused(id, paused)?;
+            status!("{} event {}", if paused { "Paused" } else { "Resumed" }, id);
+
+            return Ok(());
+        }
+        Some(("open", sub_args)) => {
+            let client = Client::new(&conn);
+            let id = client.resolve_event_id(sub_args.get_one::<String>("id").unwrap())?;
+            return Ok(opener::open(&client.event_url(id)?)?);
+        }
+        Some(("restore", sub_args)) => {
+            let client = Client::new(&conn);
+            let id = client.resolve_event_id(sub_args.get_one::<String>("id").unwrap())?;
+            client.restore_event(id)?;
+            status!("Restored event {}", id);
+
+            return Ok(());
+        }
+        Some(("purge", sub_args)) => {
+            let target = if let Some(reference) = sub_args.get_one::<String>("id") {
+                PurgeTarget::Event(Client::new(&conn).resolve_event_id(reference)?)
+            } else if let Some(age) = sub_args.get_one::<Duration>("older-than") {
+                PurgeTarget::OlderThan(*age)
+            } else {
+                PurgeTarget::AllDeleted
+            };
+
+            let removed = Client::new(&conn)
+                .with_pre_op_backup(pre_op_backup_settings(&config, no_backup))
+                .purge(target, sub_args.get_flag("yes"))?;
+            status!("Purged {} events", removed);
+
+            return Ok(());
+        }
+        Some(("export", sub_args)) => {
+            if let Some(reference) = sub_args.get_one::<String>("ics") {
+                let Some(path) = sub_args.get_one::<PathBuf>("path") else {
+                    return Err("export --ics needs a path to write to".into());
+                };
+
+                let client = Client::new(&conn);
+                let id = client.resolve_event_id(reference)?;
+                client.export_ics_single(id, path)?;
+                status!("Exported event {} to {}", id, path.display());
+
+                return Ok(());
+            }
+
+            let document = Client::new(&conn)
+                .with_range(range)
+                .with_calendar(calendar)
+                .export_json()?;
+            let output = if sub_args.get_flag("pretty") {
+                document.to_pretty_string()
+            } else {
+                document.to_string()
+            };
+
+            return match sub_args.get_one::<PathBuf>("path") {
+                Some(path) => match std::fs::write(path, output + "\n") {
+                    Ok(()) => Ok(()),
+                    Err(err) => Err(format!("{}: {}", path.display(), err).into()),
+                },
+                None => match writeln!(std::io::stdout(), "{}", output) {
+                    Ok(()) => Ok(()),
+                    Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+                    Err(err) => Err(err.into()),
+                },
+            };
+        }
+        Some(("import", sub_args)) => {
+            let dry_run = sub_args.get_flag("dry-run");
+            let stats = Client::new(&conn).import_json(
+                sub_args.get_one::<PathBuf>("json").unwrap(),
+                *sub_args.get_one::<OnConflict>("on-conflict").unwrap(),
+                dry_run,
+            )?;
+            status!(
+                "{} {} events ({} overwritten, {} skipped)",
+                if dry_run { "Would import" } else { "Imported" },
+                stats.inserted,
+                stats.overwritten,
+                stats.skipped
+            );
+
+            return Ok(());
+        }
+        Some(("merge", sub_args)) => {
+            let path = sub_args.get_one::<PathBuf>("path").unwrap();
+            let prefer = Prefer::from(sub_args.get_one::<String>("prefer").unwrap().as_str());
+
+            pre_op_backup(&conn, &config, no_backup, "merge")?;
+            let stats = backup::merge_database(&conn, path, prefer)?;
+            for conflict in &stats.conflicts {
+                status!("Conflict: {}", conflict);
+            }
+            status!(
+                "Merged {}: {} inserted, {} updated, {} skipped",
+                path.display(),
+                stats.inserted,
+                stats.updated,
+                stats.skipped
+            );
+
+            return Ok(());
+        }
+        Some(("calendars", sub_args)) => {
+            let client = Client::new(&conn);
+
+            match sub_args.subcommand() {
+                Some(("rename", rename_args)) => {
+                    let from = rename_args.get_one::<String>("from").unwrap();
+                    let to = rename_args.get_one::<String>("to").unwrap();
+                    let moved = client.rename_calendar(from, to)?;
+                    status!("Moved {} events from {} to {}", moved, from, to);
+                }
+                Some((action @ ("mute" | "unmute"), mute_args)) => {
+                    let name = mute_args.get_one::<String>("name").unwrap();
+                    client.set_calendar_muted(name, action == "mute")?;
+                    status!("{}d {}", action, name);
+                }
+                _ => {
+                    for calendar in client.list_calendars()? {
+                        println!(
+                            "{}: {} events{}",
+                            calendar.name,
+                            calendar.events,
+                            if calendar.muted { " (muted)" } else { "" }
+                        );
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+        Some(("db", sub_args)) => {
+            let _lock = InstanceLock::acquire(&config.db_path)?;
+
+            return match sub_args.subcommand() {
+                Some(("check", check_args)) => {
+                    check_db(&conn, &config, no_backup, check_args.get_flag("fix"))
+                }
+                Some(("encrypt", _)) => {
+                    pre_op_backup(&conn, &config, no_backup, "encrypt")?;
+                    drop(conn);
+                    db::encrypt(&config.db_path)?;
+                    status!("Encrypted {}", config.db_path.display());
+                    Ok(())
+                }
+                Some(("decrypt", _)) => {
+                    drop(conn);
+                    db::decrypt(&config.db_path)?;
+                    status!("Decrypted {}", config.db_path.display());
+                    Ok(())
+                }
+                Some(("vacuum", _)) => {
+                    let (before, after) = db::vacuum(&conn)?;
+                    status!(
+                        "Vacuumed the database: {} KiB -> {} KiB",
+                        before / 1024,
+                        after / 1024
+                    );
+                    Ok(())
+                }
+                _ => unreachable!(),
+            };
+        }
+        Some(("backup", sub_args)) => {
+            let path = match sub_args.get_one::<PathBuf>("path") {
+                Some(path) => {
+                    backup::backup_to(&conn, path)?;
+                    path.clone()
+                }
+                None => {
+                    backup::snapshot(&conn, &config.backup_dir(), None, config.backup_retention)?
+                }
+            };
+            println!("{}", path.display());
+
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    if args.get_flag("trash") {
+        return print_trash(&Client::new(&conn).with_range(range).with_calendar(calendar));
+    }
+
+    if args.contains_id("month") {
+        let client = Client::new(&conn).with_calendar(calendar);
+        let (year, month) = match args.get_one::<(i32, u32)>("month") {
+            Some(month) => *month,
+            None => {
+                let today = Local::now().date_naive();
+                (today.year(), today.month())
+            }
+        };
+
+        println!(
+            "{}",
+            MonthCalendar {
+                year,
+                month,
+                events: client.fetch_events_in_month(year, month)?,
+            }
+        );
+
+        return Ok(());
+    }
+
+    if args.get_flag("watch") {
+        return Client::new(&conn)
+            .with_calendar(calendar)
+            .watch(std::time::Duration::from_secs(WATCH_REFRESH_SECS));
+    }
+
+    if args.get_flag("list-all") {
+        let events = Client::new(&conn)
+            .with_range(range)
+            .with_calendar(calendar)
+            .fetch_all_events_unfiltered()?;
+
+        if args.get_flag("json") {
+            let json = Value::Array(events.iter().map(EventWithStatus::to_json).collect());
+            println!("{}", json);
+        } else {
+            for (i, event) in events.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{}", event);
+            }
+        }
+
+        return Ok(());
+    }
+
+    if let Some(reference) = args.get_one::<String>("mark-done") {
+        let client = Client::new(&conn);
+        let id = client.resolve_event_id(reference)?;
+        client.mark_done(id)?;
+        status!("Marked event {} done", id);
+
+        return Ok(());
+    }
+
+    if args.get_flag("count-by-recurrence") {
+        let counts = Client::new(&conn).count_by_recurrence()?;
+        print_recurrence_counts(counts, args.get_flag("json"));
+
+        return Ok(());
+    }
+
+    if args.get_flag("list-tags") {
+        for (tag, count) in Client::new(&conn).with_range(range).list_all_tags()? {
+            println!("{}: {} events", tag, count);
+        }
+
+        return Ok(());
+    }
+
+    if let Some(&n) = args.get_one::<u32>("list-upcoming-N") {
+        let events = Client::new(&conn)
+            .with_range(range)
+            .with_calendar(calendar)
+            .fetch_next_n_events(n)?;
+        if events.0.is_empty() {
+            status!("No upcoming events");
+        } else {
+            println!("{}", events);
+        }
+
+        return Ok(());
+    }
+
+    if args.get_flag("bulk-set-recurrence") {
+        let from = *args.get_one::<RecurrencePattern>("from").unwrap();
+        let to = *args.get_one::<RecurrencePattern>("to").unwrap();
+        let client = Client::new(&conn);
+
+        let count = client.count_events_with_recurrence(from)?;
+        if count == 0 {
+            status!("No {} events", from);
+            return Ok(());
+        }
+        if !args.get_flag("force")
+            && !confirm(&format!("Change {} {} events to {}?", count, from, to))?
+        {
+            status!("Nothing changed");
+            return Ok(());
+        }
+
+        pre_op_backup(&conn, &config, no_backup, "bulk-recurrence")?;
+        let updated = client.bulk_update_recurrence(from, to)?;
+        status!("Changed {} events to {}", updated, to);
+
+        return Ok(());
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("import-remind") {
+        let stats = Client::new(&conn)
+            .with_calendar(calendar)
+            .import_remind(path)?;
+        status!(
+            "Imported {} events ({} skipped, {} failed)",
+            stats.inserted,
+            stats.skipped,
+            stats.failed
+        );
+
+        return Ok(());
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("import-csv") {
+        let stats = Client::new(&conn)
+            .with_calendar(calendar)
+            .import_csv(path, *args.get_one::<bool>("csv-has-header").unwrap())?;
+        status!(
+            "Imported {} events ({} skipped, {} failed)",
+            stats.inserted,
+            stats.skipped,
+            stats.failed
+        );
+
+        return Ok(());
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("diff-db") {
+        return diff_db(&conn, path);
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("export-remind") {
+        Client::new(&conn)
+            .with_range(range)
+            .with_calendar(calendar)
+            .export_remind(path)?;
+        status!("Exported events to {}", path.display());
+
+        return Ok(());
+    }
+
+    if let Some(path) = args.get_one::<PathBuf>("export-shell-script") {
+        Client::new(&conn)
+            .with_range(range)
+            .with_calendar(calendar)
+            .export_shell_script(path)?;
+        status!("Exported events to {}", path.display());
+
+        return Ok(());
+    }
+
+    if args.get_flag("preview-recurrence") {
+        let (date, zone) = client::parse_date(args.get_one::<String>("date").unwrap(), None)?;
+        let dates = event::occurrences(
+            date.with_timezone(&Local),
+            args.get_one::<RecurrencePattern>("recurrence").unwrap(),
+            zone.as_ref(),
+            *args.get_one::<u32>("count").unwrap(),
+        );
+        if dates.is_empty() {
+            status!("One-time events don't repeat");
+        }
+        for date in dates {
+            println!("{}", date.format("%Y-%m-%d %H:%M"));
+        }
+
+        return Ok(());
+    }
+
+    if args.get_flag("create") {
+        let name = args.get_one::<String>("name").unwrap();
+        let message = args.get_one::<String>("message").unwrap();
+        event::validate_event_name(name)?;
+        event::validate_event_message(message)?;
+        event::validate_event_url(args.get_one::<String>("url").unwrap())?;
+        let color = output::parse_color(args.get_one::<String>("color").unwrap())?;
+        let zone = match event::parse_optional(args.get_one::<String>("tz").unwrap()) {
+            Some(name) => Some(Zone::load(&name)?),
+            None => None,
+        };
+        let (date, zone) = client::parse_date(args.get_one::<String>("date").unwrap(), zone)?;
+
+        let event = Client::new(&conn)
+            .with_calendar(calendar)
+            .save_event(NewEvent {
+                message: message.clone(),
+                recurrence: *args.get_one::<RecurrencePattern>("recurrence").unwrap(),
+                priority: Priority::from(args.get_one::<String>("priority").unwrap().as_str()),
+                location: event::parse_optional(args.get_one::<String>("location").unwrap()),
+                url: event::parse_optional(args.get_one::<String>("url").unwrap()),
+                color,
+                timezone: zone.as_ref().map(|zone| zone.name().to_string()),
+                tags: event::parse_tags(args.get_one::<String>("tags").unwrap()),
+                ..NewEvent::new(name.clone(), date)
+            })?;
+        if output::is_quiet() {
+            println!("{}", event.id);
+        } else {
+            println!("Created event {}: {}", event.id, event.name);
+        }
+
+        return Ok(());
+    }
+
+    if args.get_flag("force-check") {
+        let fired = scheduler_builder(conn, &config, &args)
+            .build()?
+            .check_now()?;
+        status!("Fired {} notifications", fired);
+
+        return Ok(());
+    }
+
+    let client = |conn| {
+        Client::new(conn)
+            .with_sort(EventSort::from(
+                args.get_one::<String>("sort").unwrap().as_str(),
+            ))
+            .with_calendar(calendar.clone())
+            .with_config_path(config.path.clone())
+            .with_pre_op_backup(pre_op_backup_settings(&config, no_backup))
+    };
+
+    match args.subcommand_name() {
+        Some("interactive") => {
+            if !InstanceLock::is_held(&config.db_path) {
+                eprintln!(
+                    "Warning: no scheduler is running on {}, so reminders won't fire; \
+                     start one with `{} serve` or use `{} run`",
+                    config.db_path.display(),
+                    BIN_NAME,
+                    BIN_NAME
+                );
+            }
+
+            client(&conn).start()
+        }
+        Some("run") => {
+            let _lock = InstanceLock::acquire(&config.db_path)?;
+            if log_file.is_none() {
+                log_to_data_dir();
+            }
+
+            // The client borrows its connection for the whole session, so
+            // the scheduler gets one of its own; WAL journaling and the busy
+            // timeout let the two take turns writing.
+            let scheduler = scheduler_builder(db::connect(&config.db_path)?, &config, &args);
+            serve_metrics(&args, &config).await?;
+            info!("Starting scheduler");
+            tokio::spawn(Arc::new(scheduler.build()?).start());
+
+            client(&conn).start()
+        }
+        // From a keybinding or a status bar there is no one to show a menu
+        // to, and a scheduler would never exit.
+        None if !io::stdout().is_terminal() => {
+            let events = client(&conn).fetch_current_day_events()?;
+            if events.0.is_empty() && config.fail_on_empty_today {
+                return Err("no events".into());
+            }
+            println!("{}", events);
+
+            Ok(())
+        }
+        _ => {
+            let _lock = InstanceLock::acquire(&config.db_path)?;
+            if log_file.is_none() {
+                log_to_data_dir();
+            }
+
+            let scheduler = scheduler_builder(conn, &config, &args).build()?;
+            serve_metrics(&args, &config).await?;
+            info!("Starting scheduler");
+            Arc::new(scheduler).start().await;
+
+            Ok(())
+        }
+    }
+}
+
+/// Starts the metrics endpoint `--export-prometheus-metrics` asks for, in
+/// the background.
+#[cfg(feature = "metrics")]
+async fn serve_metrics(args: &ArgMatches, config: &Config) -> Result<(), NotifyMeError> {
+    let Some(address) = args.get_one::<SocketAddr>("export-prometheus-metrics") else {
+        return Ok(());
+    };
+
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|err| {
+            NotifyMeError::Config(format!("Cannot serve metrics on {}: {}", address, err))
+        })?;
+    info!("Serving metrics at http://{}/metrics", address);
+    tokio::spawn(notify_me::metrics::serve(listener, config.db_path.clone()));
+
+    Ok(())
+}
+
+#[cfg(not(feature = "metrics"))]
+async fn serve_metrics(args: &ArgMatches, _config: &Config) -> Result<(), NotifyMeError> {
+    if args.contains_id("export-prometheus-metrics") {
+        return Err(NotifyMeError::Config(
+            "this build has no metrics support (rebuild with --features metrics)".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Sends the daemon's log to `notify_me.log` under the data directory, so
+/// there is a record of what it did when nobody is watching its stderr.
+fn log_to_data_dir() {
+    let Some(path) = config::default_data_dir().map(|dir| dir.join("notify_me.log")) else {
+        return;
+    };
+
+    if let Err(err) = logging::log_to_file(&path) {
+        warn!("Cannot open log file {}: {}", path.display(), err);
+    }
+}
+
+/// The scheduler as the command line and config file set it up.
+fn scheduler_builder(conn: Connection, config: &Config, args: &ArgMatches) -> SchedulerBuilder {
+    let interval = args
+        .get_one::<u64>("interval")
+        .copied()
+        .unwrap_or(scheduler::DEFAULT_INTERVAL_SECS);
+    let mut builder = Scheduler::builder()
+        .connection(Arc::new(Mutex::new(conn)))
+        .notifier(Box::new(DesktopNotifier))
+        .interval_secs(interval)
+        .trash_retention_days(config.trash_retention_days)
+        .pre_op_backup(pre_op_backup_settings(config, args.get_flag("no-backup")))
+        .skip_catchup_notifications(!args.get_flag("notify-missed"));
+    if let Some(max) = args.get_one::<u32>("max-notifications-per-tick") {
+        builder = builder.max_notifications_per_tick(*max);
+    }
+
+    builder
+}
+
+/// The exit statuses, listed under `--help`.
+fn exit_status_help() -> String {
+    let mut help = String::from("Exit status:");
+    for (code, description) in error::EXIT_STATUSES {
+        help.push_str(&format!("\n  {}  {}", code, description));
+    }
+
+    help
+}
+
+/// One line per style used in listings and the interactive prompts.
+fn print_ansi_test() {
+    if !output::colors_enabled() {
+        println!("Colors are disabled");
+    }
+
+    let styles = [
+        ("bold", console::Style::new().bold()),
+        ("dim", console::Style::new().dim()),
+        ("cyan", console::Style::new().cyan()),
+        ("green", console::Style::new().green()),
+        ("red", console::Style::new().red()),
+        ("yellow", console::Style::new().yellow()),
+    ];
+
+    for (name, style) in styles {
+        println!("{}", style.apply_to(name));
+    }
+}
+
+/// What `version` prints. The database is only opened if it exists, so
+/// asking for the version never creates one.
+fn print_version(config: &Config, verbose: bool) {
+    if !verbose {
+        println!("{} {}", BIN_NAME, VERSION);
+        return;
+    }
+
+    let schema = if config.db_path.exists() {
+        match db::connect(&config.db_path).and_then(|conn| db::user_version(&conn)) {
+            Ok(version) => format!("schema {}", version),
+            Err(err) => format!("unreadable: {}", err),
+        }
+    } else {
+        "not created yet".to_string()
+    };
+    let features = env!("NOTIFY_ME_FEATURES");
+
+    println!("{} {}", BIN_NAME, env!("CARGO_PKG_VERSION"));
+    println!("commit: {}", env!("NOTIFY_ME_GIT_SHA"));
+    println!("built: {}", env!("NOTIFY_ME_BUILD_TIMESTAMP"));
+    println!("host: {}", env!("NOTIFY_ME_HOST_TRIPLE"));
+    println!("sqlite: {}", rusqlite::version());
+    println!(
+        "database: {} ({}, this build migrates to schema {})",
+        config.db_path.display(),
+        schema,
+        db::schema_version()
+    );
+    println!(
+        "features: {}",
+        if features.is_empty() {
+            "none"
+        } else {
+            features
+        }
+    );
+}
+
+/// `--count-by-recurrence`'s table, patterns by name with the counts right
+/// aligned and a total at the end, or the same as a JSON object.
+fn print_recurrence_counts(counts: HashMap<String, usize>, json: bool) {
+    let total = counts.values().sum();
+    let mut rows: Vec<_> = counts.into_iter().collect();
+    rows.sort();
+    rows.push(("total".to_string(), total));
+
+    if json {
+        let object = rows
+            .into_iter()
+            .map(|(pattern, count)| (pattern, Value::Number(count as i64)))
+            .collect();
+        println!("{}", Value::Object(object));
+        return;
+    }
+
+    let name_width = rows.iter().map(|(pattern, _)| pattern.len()).max().unwrap();
+    let count_width = total.to_string().len();
+    for (pattern, count) in rows {
+        println!(
+            "{:<name_width$}  {:>count_width$}",
+            format!("{}:", pattern),
+            count,
+            name_width = name_width + 1,
+        );
+    }
+}
+
+/// The settings a run with the same flags would use, as TOML annotated with
+/// where each value comes from.
+fn print_config(config: &Config, args: &ArgMatches) {
+    match &config.path {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# No config file, using defaults"),
+    }
+    for setting in config.settings() {
+        println!("{}", setting);
+    }
+
+    let source = |id: &str| match args.value_source(id) {
+        Some(ValueSource::CommandLine) => Source::CommandLine,
+        _ => Source::Default,
+    };
+    let interval = args
+        .get_one::<u64>("interval")
+        .copied()
+        .unwrap_or(scheduler::DEFAULT_INTERVAL_SECS);
+    let max_notifications = args
+        .get_one::<u32>("max-notifications-per-tick")
+        .copied()
+        .unwrap_or(scheduler::DEFAULT_MAX_NOTIFICATIONS_PER_TICK);
+
+    println!();
+    println!("# Scheduler settings, only set on the command line");
+    println!("[scheduler]");
+    for setting in [
+        Setting::new("interval_secs", Some(interval as i64), source("interval")),
+        Setting::new(
+            "advance_minutes",
+            Some(i64::from(scheduler::DEFAULT_ADVANCE_MINUTES)),
+            Source::Default,
+        ),
+        Setting::new(
+            "max_notifications_per_tick",
+            Some(i64::from(max_notifications)),
+            source("max-notifications-per-tick"),
+        ),
+        Setting::new(
+            "notify_missed",
+            Some(args.get_flag("notify-missed")),
+            source("notify-missed"),
+        ),
+    ] {
+        println!("{}", setting);
+    }
+}
+
+/// Writes `config::example` to `path`, asking before replacing a file
+/// unless `overwrite`, or prints it.
+fn generate_config(path: Option<&PathBuf>, overwrite: bool) -> Result<(), NotifyMeError> {
+    let example = config::example();
+    let Some(path) = path else {
+        print!("{}", example);
+        return Ok(());
+    };
+
+    if path.exists() && !overwrite {
+        if !io::stdin().is_terminal() {
+            return Err(format!(
+                "{} already exists; pass --overwrite to replace it",
+                path.display()
+            )
+            .into());
+        }
+        if !confirm(&format!("{} already exists. Overwrite it?", path.display()))? {
+            status!("Left {} as it was", path.display());
+            return Ok(());
+        }
+    }
+
+    fs::write(path, example)?;
+    status!("Wrote {}", path.display());
+
+    Ok(())
+}
+
+fn check_notify() -> Result<(), NotifyMeError> {
+    let checks = scheduler::check_notifications();
+    for check in &checks {
+        match &check.outcome {
+            Ok(found) => println!("{}: {}", check.label, found),
+            Err(err) => println!("{}: failed: {}", check.label, err),
+        }
+    }
+
+    if checks.iter().any(|check| check.outcome.is_err()) {
+        return Err(NotifyMeError::Notification(
+            "notifications can't be shown".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// One line such as `Standup in 42m (09:30)`, or a waybar object holding it.
+fn print_next(client: &Client, json: bool) -> Result<(), NotifyMeError> {
+    let now = Local::now();
+    let next = client.next_event()?;
+
+    let text = match &next {
+        Some(event) => {
+            let format = if event.date.date_naive() == now.date_naive() {
+                "%H:%M"
+            } else {
+                "%d/%m %H:%M"
+            };
+            format!(
+                "{} in {} ({})",
+                event.name,
+                client::format_duration_human(event.date - now),
+                event.date.format(format)
+            )
+        }
+        None => "nothing scheduled".to_string(),
+    };
+
+    if !json {
+        println!("{}", text);
+        return Ok(());
+    }
+
+    let (tooltip, class) = match &next {
+        Some(event) if event.message.is_empty() => (event.name.clone(), "upcoming"),
+        Some(event) => (format!("{}\n{}", event.name, event.message), "upcoming"),
+        None => (text.clone(), "empty"),
+    };
+    let json = Value::Object(vec![
+        ("text".to_string(), text.into()),
+        ("tooltip".to_string(), tooltip.into()),
+        ("class".to_string(), class.into()),
+        (
+            "event".to_string(),
+            next.as_ref().map_or(Value::Null, |event| event.to_json()),
+        ),
+    ]);
+    println!("{}", json);
+
+    Ok(())
+}
+
+fn diff_db(conn: &Connection, path: &Path) -> Result<(), NotifyMeError> {
+    let not_a_db =
+        |err: NotifyMeError| NotifyMeError::DatabaseAccess(format!("{}: {}", path.display(), err));
+
+    backup::validate(path).map_err(NotifyMeError::DatabaseAccess)?;
+    let other = db::connect(path).map_err(not_a_db)?;
+    let version = db::user_version(&other).map_err(not_a_db)?;
+    if version != db::schema_version() {
+        return Err(not_a_db(format!(
+            "schema version {} differs from this database's {}; open it with this version of {} first",
+            version,
+            db::schema_version(),
+            BIN_NAME
+        )
+        .into()));
+    }
+
+    let diff = Client::new(conn).diff_database(&other)?;
+    if diff.is_empty() {
+        status!("No differences");
+    } else {
+        println!("{}", diff);
+    }
 
     Ok(())
 }
+
+fn print_trash(client: &Client) -> Result<(), NotifyMeError> {
+    let trash = client.fetch_deleted_events(client::TRASH_LIMIT)?;
+    if trash.0.is_empty() {
+        status!("Trash is empty");
+    } else {
+        println!("{}", trash);
+    }
+
+    Ok(())
+}
+
+/// Prints the integrity check, orphaned rows per table and unreadable
+/// events. Problems make the command fail unless `fix` deleted the orphans;
+/// a corrupt file can't be fixed here and always fails.
+fn check_db(
+    conn: &Connection,
+    config: &Config,
+    no_backup: bool,
+    fix: bool,
+) -> Result<(), NotifyMeError> {
+    let corruption = db::integrity_check(conn)?;
+    if corruption.is_empty() {
+        println!("integrity: ok");
+    } else {
+        println!("integrity: {} problems", corruption.len());
+        for problem in &corruption {
+            println!("  {}", problem);
+        }
+    }
+
+    let orphans = db::orphaned_rows(conn)?;
+    if orphans.is_empty() {
+        println!("orphaned rows: none");
+    }
+    for (table, rowids) in &orphans {
+        println!("{}: {} orphaned rows", table, rowids.len());
+    }
+
+    let unreadable = db::unreadable_events(conn)?;
+    if unreadable.is_empty() {
+        println!("unreadable events: none");
+    } else {
+        println!("unreadable events: {}", unreadable.len());
+        for row in &unreadable {
+            println!("  {}", row);
+        }
+    }
+
+    if fix && !orphans.is_empty() {
+        pre_op_backup(conn, config, no_backup, "fix")?;
+        let removed = db::delete_orphaned_rows(conn, &orphans)?;
+        status!("Deleted {} orphaned rows", removed);
+    }
+
+    if !corruption.is_empty() {
+        return Err("The database is corrupt, restore it from a backup".into());
+    }
+
+    if !fix && !orphans.is_empty() {
+        return Err("Found orphaned rows (run with --fix to delete them)".into());
+    }
+
+    if !unreadable.is_empty() {
+        return Err("Found unreadable events (fix or delete those rows by hand)".into());
+    }
+
+    Ok(())
+}
+
+/// What to try when the database can't be opened or written: the usual
+/// culprits are a missing directory and missing write permission on the
+/// file or the directory SQLite keeps its journal in.
+fn db_path_hint(config: &Config) -> String {
+    let path = &config.db_path;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let setting = match &config.path {
+        Some(file) => format!("set db_path in {}", file.display()),
+        None => "set db_path in a config file".to_string(),
+    };
+
+    let problem = match (fs::metadata(dir), fs::metadata(path)) {
+        (Err(_), _) => format!("{} doesn't exist", dir.display()),
+        (_, Ok(file)) if file.permissions().readonly() => {
+            format!("{} is read-only", path.display())
+        }
+        (Ok(dir_meta), _) if dir_meta.permissions().readonly() => {
+            format!("{} is read-only", dir.display())
+        }
+        _ => format!(
+            "check that you can write to {} and {}",
+            path.display(),
+            dir.display()
+        ),
+    };
+
+    format!(
+        "\nHint: {}; fix that or {} to somewhere writable.",
+        problem, setting
+    )
+}
+
+/// Asks for a typed "yes" on a plain stdin line, so a stray Enter or arrow
+/// key can't wipe the database the way it could pick a dialoguer option.
+fn confirm_reset() -> Result<bool, NotifyMeError> {
+    print!("WARNING: This will delete ALL events permanently. Type 'yes' to confirm: ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim() == "yes")
+}
+
+/// Asks a yes/no question on stdin, defaulting to no.
+fn confirm(prompt: &str) -> Result<bool, NotifyMeError> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Snapshots the database before a destructive operation. A failed backup
+/// aborts the operation; `--no-backup` skips the snapshot altogether.
+fn pre_op_backup(
+    conn: &Connection,
+    config: &Config,
+    no_backup: bool,
+    operation: &str,
+) -> Result<(), NotifyMeError> {
+    match pre_op_backup_settings(config, no_backup) {
+        Some(backup) => Ok(backup.take(conn, operation)?),
+        None => Ok(()),
+    }
+}
+
+/// Where destructive operations snapshot the database first; `None` with
+/// `--no-backup`.
+fn pre_op_backup_settings(config: &Config, no_backup: bool) -> Option<PreOpBackup> {
+    (!no_backup).then(|| PreOpBackup {
+        dir: config.pre_op_backup_dir(),
+        retention: config.pre_op_backup_retention,
+    })
+}
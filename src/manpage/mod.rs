@@ -0,0 +1,211 @@
+use std::fmt::Write;
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::{config::Config, db, error::EXIT_STATUSES};
+
+/// Environment variables the program reads, for the ENVIRONMENT section.
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        db::KEY_ENV,
+        "Key for an encrypted database. Prompted for when unset.",
+    ),
+    (
+        "XDG_CONFIG_HOME",
+        "Base directory of the configuration file, ~/.config when unset.",
+    ),
+    (
+        "XDG_DATA_HOME",
+        "Base directory of the scheduler's default log file, ~/.local/share when unset.",
+    ),
+    (
+        "NO_COLOR",
+        "Disables colored output when set and non-empty.",
+    ),
+    (
+        "TZDIR",
+        "Directory of the time zone database, /usr/share/zoneinfo when unset.",
+    ),
+    ("RUST_LOG", "Log level, e.g. info or debug."),
+];
+
+/// The `bin(1)` man page for `cmd`, rendered from the clap definitions so it
+/// can't drift from what the program accepts.
+pub fn render(cmd: &mut Command, bin: &str) -> String {
+    cmd.build();
+    let mut page = String::new();
+
+    writeln!(page, ".TH {} 1", escape(&bin.to_uppercase())).unwrap();
+    writeln!(page, ".SH NAME").unwrap();
+    match cmd.get_about() {
+        Some(about) => writeln!(page, "{} \\- {}", escape(bin), escape(&about.to_string())),
+        None => writeln!(page, "{}", escape(bin)),
+    }
+    .unwrap();
+
+    writeln!(page, ".SH SYNOPSIS").unwrap();
+    synopsis(&mut page, cmd, bin);
+
+    writeln!(page, ".SH OPTIONS").unwrap();
+    options(&mut page, cmd.get_arguments());
+
+    writeln!(page, ".SH COMMANDS").unwrap();
+    for sub in visible_subcommands(cmd) {
+        subcommand(&mut page, sub, bin);
+    }
+
+    writeln!(page, ".SH ENVIRONMENT").unwrap();
+    for (name, description) in ENVIRONMENT {
+        writeln!(page, ".TP\n.B {}\n{}", escape(name), escape(description)).unwrap();
+    }
+
+    writeln!(page, ".SH EXIT STATUS").unwrap();
+    for (code, description) in EXIT_STATUSES {
+        writeln!(page, ".TP\n.B {}\n{}", code, escape(description)).unwrap();
+    }
+
+    writeln!(page, ".SH FILES").unwrap();
+    writeln!(
+        page,
+        ".TP\n.I $XDG_CONFIG_HOME/notify_me/config.toml\n\
+         Configuration file, read when present unless \\fB\\-\\-config\\fR names \
+         another. It takes these settings, shown with their defaults:"
+    )
+    .unwrap();
+    writeln!(page, ".RS\n.nf").unwrap();
+    for setting in Config::default().settings() {
+        writeln!(page, "{}", escape(&setting.to_string())).unwrap();
+    }
+    writeln!(page, ".fi\n.RE").unwrap();
+
+    if let Some(version) = cmd.get_version() {
+        writeln!(page, ".SH VERSION\n{}", escape(version)).unwrap();
+    }
+
+    page
+}
+
+/// `cmd` and its nested subcommands, each under its own heading.
+fn subcommand(page: &mut String, cmd: &Command, parent: &str) {
+    let name = format!("{} {}", parent, cmd.get_name());
+
+    writeln!(page, ".SS \"{}\"", escape(&name)).unwrap();
+    if let Some(about) = cmd.get_about() {
+        writeln!(page, "{}", escape(&about.to_string())).unwrap();
+    }
+    writeln!(page, ".PP").unwrap();
+    synopsis(page, cmd, &name);
+    // Global options and --help are documented once, at the top.
+    options(
+        page,
+        cmd.get_arguments()
+            .filter(|arg| !arg.is_global_set() && arg.get_id() != "help"),
+    );
+
+    for sub in visible_subcommands(cmd) {
+        subcommand(page, sub, &name);
+    }
+}
+
+/// Subcommands worth documenting: clap's own `help` only repeats the page.
+fn visible_subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn synopsis(page: &mut String, cmd: &Command, name: &str) {
+    let mut line = format!("\\fB{}\\fR", escape(name));
+
+    if cmd.get_arguments().any(|arg| !arg.is_positional()) {
+        line.push_str(" [\\fIOPTIONS\\fR]");
+    }
+    for arg in cmd.get_positionals().filter(|arg| !arg.is_hide_set()) {
+        let value = format!("\\fI{}\\fR", escape(arg.get_id().as_str()));
+        if arg.is_required_set() {
+            write!(line, " {}", value).unwrap();
+        } else {
+            write!(line, " [{}]", value).unwrap();
+        }
+    }
+    if visible_subcommands(cmd).next().is_some() {
+        if cmd.is_subcommand_required_set() {
+            line.push_str(" \\fICOMMAND\\fR");
+        } else {
+            line.push_str(" [\\fICOMMAND\\fR]");
+        }
+    }
+
+    writeln!(page, "{}", line).unwrap();
+}
+
+fn options<'a>(page: &mut String, args: impl Iterator<Item = &'a Arg>) {
+    for arg in args.filter(|arg| !arg.is_hide_set()) {
+        writeln!(page, ".TP\n{}", flags(arg)).unwrap();
+
+        let mut help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+        let values: Vec<_> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        if !values.is_empty() {
+            write!(help, " [possible values: {}]", values.join(", ")).unwrap();
+        }
+        let defaults: Vec<_> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy())
+            .collect();
+        if !defaults.is_empty() && takes_values(arg) {
+            write!(help, " [default: {}]", defaults.join(", ")).unwrap();
+        }
+        if !help.is_empty() {
+            writeln!(page, "{}", escape(help.trim_start())).unwrap();
+        }
+    }
+}
+
+/// How `arg` is written on the command line, e.g. `-c, --calendar NAME`.
+fn flags(arg: &Arg) -> String {
+    if arg.is_positional() {
+        return format!("\\fI{}\\fR", escape(arg.get_id().as_str()));
+    }
+
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{}\\fR", escape(&short.to_string())));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let mut flags = names.join(", ");
+
+    if takes_values(arg) {
+        let value = match arg.get_value_names() {
+            Some(names) => names
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => arg.get_id().as_str().to_uppercase(),
+        };
+        write!(flags, " \\fI{}\\fR", escape(&value)).unwrap();
+    }
+
+    flags
+}
+
+fn takes_values(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Set | ArgAction::Append)
+}
+
+/// `text` with roff's special characters escaped, so it prints as written.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+
+    match escaped.chars().next() {
+        Some('.' | '\'') => format!("\\&{}", escaped),
+        _ => escaped,
+    }
+}
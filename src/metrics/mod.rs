@@ -0,0 +1,178 @@
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+};
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{error, warn};
+
+use crate::{db, error::NotifyMeError};
+
+/// How much of a request is read looking for the end of its headers.
+const MAX_REQUEST_BYTES: usize = 8192;
+
+static NOTIFICATIONS_FIRED: AtomicU64 = AtomicU64::new(0);
+static NOTIFICATIONS_FAILED: AtomicU64 = AtomicU64::new(0);
+/// Unix time of the last tick that finished, 0 before the first.
+static LAST_TICK: AtomicI64 = AtomicI64::new(0);
+
+/// Counts a notification the scheduler showed.
+pub fn notification_fired() {
+    NOTIFICATIONS_FIRED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a notification the scheduler failed to show.
+pub fn notification_failed() {
+    NOTIFICATIONS_FAILED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records that the scheduler finished the tick it started at `at`.
+pub fn tick_finished(at: DateTime<Utc>) {
+    LAST_TICK.store(at.timestamp(), Ordering::Relaxed);
+}
+
+/// The metrics in Prometheus' text format. Counters are this process's;
+/// the events and database size are read from `conn` at each call.
+pub fn render(conn: &Connection) -> Result<String, NotifyMeError> {
+    let mut out = String::new();
+
+    out.push_str("# HELP notify_me_events_total Events outside the trash.\n");
+    out.push_str("# TYPE notify_me_events_total gauge\n");
+    let mut stmt = conn.prepare(
+        "SELECT recurrence_pattern, priority, COUNT(*) FROM events \
+         WHERE deleted_at IS NULL GROUP BY 1, 2 ORDER BY 1, 2",
+    )?;
+    let counts = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (recurrence, priority, count) in counts {
+        writeln!(
+            out,
+            "notify_me_events_total{{recurrence=\"{}\",priority=\"{}\"}} {}",
+            label(&recurrence),
+            label(&priority),
+            count
+        )
+        .unwrap();
+    }
+
+    let size: i64 = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?;
+
+    for (name, kind, help, value) in [
+        (
+            "notify_me_notifications_fired_total",
+            "counter",
+            "Notifications shown since the scheduler started.",
+            NOTIFICATIONS_FIRED.load(Ordering::Relaxed) as i64,
+        ),
+        (
+            "notify_me_notifications_failed_total",
+            "counter",
+            "Notifications that failed to show since the scheduler started.",
+            NOTIFICATIONS_FAILED.load(Ordering::Relaxed) as i64,
+        ),
+        (
+            "notify_me_db_size_bytes",
+            "gauge",
+            "Size of the database file.",
+            size,
+        ),
+        (
+            "notify_me_scheduler_last_tick_seconds",
+            "gauge",
+            "Unix time of the last scheduler tick that finished, 0 before the first.",
+            LAST_TICK.load(Ordering::Relaxed),
+        ),
+    ] {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+        writeln!(out, "{} {}", name, value).unwrap();
+    }
+
+    Ok(out)
+}
+
+/// Answers `GET /metrics` on `listener` until the process exits, reading
+/// the database at `db_path` for each request.
+pub async fn serve(listener: TcpListener, db_path: PathBuf) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(respond(stream, db_path.clone()));
+            }
+            Err(err) => warn!("Accepting a metrics connection failed: {}", err),
+        }
+    }
+}
+
+async fn respond(mut stream: TcpStream, db_path: PathBuf) {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => request.extend_from_slice(&buf[..read]),
+        }
+        if request.len() > MAX_REQUEST_BYTES {
+            break;
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let rendered =
+                tokio::task::spawn_blocking(move || render(&db::connect(&db_path)?)).await;
+
+            match rendered {
+                Ok(Ok(body)) => ("200 OK", body),
+                Ok(Err(err)) => {
+                    error!("Rendering metrics failed: {}", err);
+                    ("500 Internal Server Error", format!("{}\n", err))
+                }
+                Err(err) => ("500 Internal Server Error", format!("{}\n", err)),
+            }
+        }
+        (Some("GET"), _) => ("404 Not Found", "Metrics are at /metrics\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "Only GET is supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // The scraper retries on its next interval.
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// `value` escaped for a label: backslashes, quotes and line breaks.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
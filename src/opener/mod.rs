@@ -0,0 +1,23 @@
+use std::process::Command;
+
+/// Launches `url` with the platform's default handler, usually the browser.
+pub fn open(url: &str) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    match command.arg(url).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Failed to open {}: {}", url, status)),
+        Err(err) => Err(format!("Failed to open {}: {}", url, err)),
+    }
+}
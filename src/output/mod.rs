@@ -0,0 +1,98 @@
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use console::{style, Color};
+
+/// Color names accepted for event labels, besides `#rrggbb` hex.
+pub const NAMED_COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Switches to plain output for the rest of the run when `no_color` is set
+/// or NO_COLOR has any non-empty value (https://no-color.org). Output that
+/// isn't a terminal is plain already.
+pub fn configure(no_color: bool) {
+    if no_color || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+pub fn colors_enabled() -> bool {
+    console::colors_enabled()
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Leaves out informational messages for the rest of the run, for scripts
+/// that only want results and the exit status.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Normalizes a color label to a lowercase name or `#rrggbb`. A blank label
+/// means none.
+pub fn parse_color(input: &str) -> Result<Option<String>, &'static str> {
+    let color = input.trim().to_lowercase();
+    if color.is_empty() {
+        return Ok(None);
+    }
+
+    if NAMED_COLORS.contains(&color.as_str()) || hex_rgb(&color).is_some() {
+        Ok(Some(color))
+    } else {
+        Err("Colors are one of black, red, green, yellow, blue, magenta, cyan, white or #rrggbb")
+    }
+}
+
+/// `text` in `color`, a label accepted by `parse_color`, when colors are on.
+pub fn paint(text: &str, color: &str) -> String {
+    match terminal_color(color) {
+        Some(color) => style(text).fg(color).to_string(),
+        None => text.to_string(),
+    }
+}
+
+fn terminal_color(color: &str) -> Option<Color> {
+    match color {
+        "black" => Some(Color::Black),
+        "red" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" => Some(Color::Yellow),
+        "blue" => Some(Color::Blue),
+        "magenta" => Some(Color::Magenta),
+        "cyan" => Some(Color::Cyan),
+        "white" => Some(Color::White),
+        _ => hex_rgb(color).map(|(r, g, b)| Color::Color256(cube_index(r, g, b))),
+    }
+}
+
+fn hex_rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// The closest entry of the 6x6x6 color cube of 256-color terminals, which
+/// is as precise as the terminals we can count on get.
+fn cube_index(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    let level = |channel: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| LEVELS[i].abs_diff(channel))
+            .unwrap() as u8
+    };
+
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
@@ -0,0 +1,381 @@
+use chrono::{Datelike, Local, Months, NaiveDate, NaiveTime};
+use tracing::warn;
+
+use crate::event::{Event, RecurrencePattern, LAST_WEEKDAY, MAX_WEEKS};
+
+/// `remind` reminders without an `AT` clause are shown all day; we need a
+/// concrete time to notify at, so they land at the start of the working day.
+const UNTIMED_REMINDER_TIME: (u32, u32) = (9, 0);
+
+#[derive(Debug)]
+pub struct Reminder {
+    pub date: NaiveDate,
+    pub time: NaiveTime,
+    pub through: Option<NaiveDate>,
+    pub repeat: Repeat,
+    pub message: String,
+}
+
+/// How a reminder repeats: every N days (`*N`), or every month when it only
+/// names a day of the month.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    None,
+    Days(u32),
+    Monthly,
+}
+
+impl Reminder {
+    /// The recurrence of the events a reminder becomes. With `THROUGH` every
+    /// occurrence is imported as a one-time event instead.
+    pub fn recurrence(&self) -> RecurrencePattern {
+        match (self.through, self.repeat) {
+            (None, Repeat::Days(1)) => RecurrencePattern::Daily,
+            (None, Repeat::Days(7)) => RecurrencePattern::Weekly,
+            (None, Repeat::Days(days)) if days % 7 == 0 => RecurrencePattern::EveryNWeeks(days / 7),
+            (None, Repeat::Monthly) => RecurrencePattern::Monthly,
+            _ => RecurrencePattern::Once,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RemindFile {
+    pub reminders: Vec<Reminder>,
+    pub omits: Vec<NaiveDate>,
+    pub unsupported: usize,
+}
+
+enum LineState {
+    Normal,
+    Continuation(String),
+    SkippingIf(usize),
+}
+
+enum ClauseState {
+    Date,
+    At,
+    Through,
+}
+
+/// Parses the subset of the `remind` format we can map onto events:
+/// `REM <date> [*N] [AT <time>] [THROUGH <date>] MSG <message>`, where a bare
+/// day of the month repeats monthly, and `OMIT <date>`. Anything else is
+/// logged and skipped.
+pub fn parse(input: &str) -> RemindFile {
+    let mut file = RemindFile::default();
+    let mut state = LineState::Normal;
+
+    for (index, raw_line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+
+        state = match state {
+            LineState::SkippingIf(depth) => match first_word(line).as_str() {
+                "IF" | "IFTRIG" => LineState::SkippingIf(depth + 1),
+                "ENDIF" if depth == 1 => LineState::Normal,
+                "ENDIF" => LineState::SkippingIf(depth - 1),
+                _ => LineState::SkippingIf(depth),
+            },
+            LineState::Continuation(mut pending) => {
+                pending.push(' ');
+                pending.push_str(line);
+                parse_logical_line(pending, line_number, &mut file)
+            }
+            LineState::Normal => parse_logical_line(line.to_string(), line_number, &mut file),
+        };
+    }
+
+    if let LineState::Continuation(pending) = state {
+        parse_line(
+            pending.trim_end_matches('\\'),
+            input.lines().count(),
+            &mut file,
+        );
+    }
+
+    file
+}
+
+fn parse_logical_line(line: String, line_number: usize, file: &mut RemindFile) -> LineState {
+    if let Some(stripped) = line.strip_suffix('\\') {
+        return LineState::Continuation(stripped.to_string());
+    }
+
+    match first_word(&line).as_str() {
+        "IF" | "IFTRIG" => {
+            warn!(
+                "line {}: conditional blocks are not supported, skipping",
+                line_number
+            );
+            file.unsupported += 1;
+            LineState::SkippingIf(1)
+        }
+        _ => {
+            parse_line(&line, line_number, file);
+            LineState::Normal
+        }
+    }
+}
+
+fn parse_line(line: &str, line_number: usize, file: &mut RemindFile) {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+        return;
+    }
+
+    let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    let result = match directive.to_uppercase().as_str() {
+        "REM" => parse_rem(rest).map(|reminder| file.reminders.push(reminder)),
+        "OMIT" => parse_omit(rest).map(|date| file.omits.push(date)),
+        other => Err(format!("unsupported directive '{}'", other)),
+    };
+
+    if let Err(err) = result {
+        warn!("line {}: {}, skipping", line_number, err);
+        file.unsupported += 1;
+    }
+}
+
+fn parse_rem(rest: &str) -> Result<Reminder, String> {
+    let (spec, message) = match split_keyword(rest, "MSG") {
+        Some(parts) => parts,
+        None => return Err("REM without MSG".to_string()),
+    };
+
+    let mut state = ClauseState::Date;
+    let mut date_tokens = Vec::new();
+    let mut through_tokens = Vec::new();
+    let mut time = None;
+    let mut repeat = Repeat::None;
+
+    for token in spec.split_whitespace() {
+        match token.to_uppercase().as_str() {
+            "AT" => state = ClauseState::At,
+            "THROUGH" => state = ClauseState::Through,
+            _ if token.starts_with('*') => match token[1..].parse::<u32>() {
+                Ok(days) if days > 0 => repeat = Repeat::Days(days),
+                _ => return Err(format!("invalid repeat '{}'", token)),
+            },
+            _ => match state {
+                ClauseState::Date => date_tokens.push(token),
+                ClauseState::Through => through_tokens.push(token),
+                ClauseState::At => {
+                    if time.is_some() {
+                        return Err(format!("unsupported clause '{}'", token));
+                    }
+                    time = Some(parse_time(token)?);
+                }
+            },
+        }
+    }
+
+    let through = if through_tokens.is_empty() {
+        None
+    } else {
+        Some(parse_date(&through_tokens)?)
+    };
+
+    let date = match date_tokens.as_slice() {
+        [day] if day.parse::<u32>().is_ok_and(|day| (1..=31).contains(&day)) => {
+            if repeat != Repeat::None || through.is_some() {
+                return Err("a day of the month can't be combined with *N or THROUGH".to_string());
+            }
+            repeat = Repeat::Monthly;
+            next_day_of_month(day.parse().unwrap(), Local::now().date_naive())
+        }
+        _ => parse_date(&date_tokens)?,
+    };
+
+    if through.is_some_and(|through| through < date) {
+        return Err("THROUGH date is before the start date".to_string());
+    }
+
+    if let (None, Repeat::Days(days)) = (through, repeat) {
+        if days != 1 && (days % 7 != 0 || days / 7 > MAX_WEEKS) {
+            return Err(format!("repeating every {} days needs THROUGH", days));
+        }
+    }
+
+    let (hour, minute) = UNTIMED_REMINDER_TIME;
+
+    Ok(Reminder {
+        date,
+        time: time.unwrap_or_else(|| NaiveTime::from_hms_opt(hour, minute, 0).unwrap()),
+        through,
+        repeat,
+        message: strip_substitutions(message.trim()),
+    })
+}
+
+/// The first date on or after `today` falling on `day` of its month,
+/// skipping months too short to have it.
+fn next_day_of_month(day: u32, today: NaiveDate) -> NaiveDate {
+    let first = today.with_day(1).unwrap();
+
+    (0..)
+        .filter_map(|months| first.checked_add_months(Months::new(months))?.with_day(day))
+        .find(|date| *date >= today)
+        .unwrap()
+}
+
+fn parse_omit(rest: &str) -> Result<NaiveDate, String> {
+    let spec = match split_keyword(rest, "MSG") {
+        Some((spec, _)) => spec,
+        None => rest,
+    };
+
+    parse_date(&spec.split_whitespace().collect::<Vec<_>>())
+}
+
+/// Accepts ISO dates (`2024-03-15`) or a day, month name and year in any
+/// order (`15 Mar 2024`, `Mar 15 2024`).
+fn parse_date(tokens: &[&str]) -> Result<NaiveDate, String> {
+    if let [token] = tokens {
+        if let Ok(date) = NaiveDate::parse_from_str(token, "%Y-%m-%d") {
+            return Ok(date);
+        }
+    }
+
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in tokens {
+        if let Ok(number) = token.parse::<u32>() {
+            if number > 31 {
+                year = Some(number as i32);
+            } else {
+                day = Some(number);
+            }
+        } else if let Some(parsed) = parse_month(token) {
+            month = Some(parsed);
+        } else {
+            return Err(format!("unsupported date token '{}'", token));
+        }
+    }
+
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => NaiveDate::from_ymd_opt(year, month, day)
+            .ok_or_else(|| format!("invalid date '{}'", tokens.join(" "))),
+        _ => Err(format!("incomplete date '{}'", tokens.join(" "))),
+    }
+}
+
+fn parse_month(token: &str) -> Option<u32> {
+    let token = token.to_lowercase();
+    if token.len() < 3 {
+        return None;
+    }
+
+    (1..=12).find(|month| {
+        let name = NaiveDate::from_ymd_opt(2000, *month, 1)
+            .unwrap()
+            .format("%B")
+            .to_string()
+            .to_lowercase();
+
+        name.starts_with(&token)
+    })
+}
+
+fn parse_time(token: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(token, "%H:%M").map_err(|_| format!("invalid time '{}'", token))
+}
+
+fn split_keyword<'a>(input: &'a str, keyword: &str) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+
+    for token in input.split_whitespace() {
+        let start = offset + input[offset..].find(token).unwrap();
+        offset = start + token.len();
+
+        if token.eq_ignore_ascii_case(keyword) {
+            return Some((&input[..start], &input[offset..]));
+        }
+    }
+
+    None
+}
+
+/// Drops `remind` substitution sequences such as `%a` and `%"` so the
+/// message reads naturally in a desktop notification.
+fn strip_substitutions(message: &str) -> String {
+    let mut result = String::with_capacity(message.len());
+    let mut chars = message.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        if let Some('%') = chars.next() {
+            result.push('%');
+        }
+    }
+
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn first_word(line: &str) -> String {
+    line.split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+/// Every day covered by a reminder, honouring `THROUGH` and `*N`. Without
+/// `THROUGH` that is just its first date; the repeat becomes the event's
+/// recurrence instead.
+pub fn occurrences(reminder: &Reminder) -> impl Iterator<Item = NaiveDate> + '_ {
+    let end = reminder.through.unwrap_or(reminder.date);
+    let step = match reminder.repeat {
+        Repeat::Days(days) => days as usize,
+        Repeat::None | Repeat::Monthly => 1,
+    };
+
+    reminder
+        .date
+        .iter_days()
+        .take_while(move |date| *date <= end)
+        .step_by(step)
+}
+
+/// `event` as a `REM` line that `parse` reads back into the same event, with
+/// its message, or its name when it has none, as the `MSG`. Monthly events
+/// use a bare day of the month, which `remind` repeats every month. Events on the nth weekday of the month use remind's weekday form,
+/// e.g. `Tue 8` for the 2nd Tuesday, which `parse` leaves unsupported.
+pub fn reminder_line(event: &Event) -> String {
+    let date = match event.recurrence_pattern {
+        RecurrencePattern::Once => event.date.format("%-d %b %Y").to_string(),
+        RecurrencePattern::Daily => event.date.format("%-d %b %Y *1").to_string(),
+        RecurrencePattern::Weekly => event.date.format("%-d %b %Y *7").to_string(),
+        RecurrencePattern::EveryNWeeks(weeks) => {
+            format!("{} *{}", event.date.format("%-d %b %Y"), 7 * weeks)
+        }
+        RecurrencePattern::Monthly => event.date.day().to_string(),
+        // The first such weekday on or after the nth week's first day; the
+        // last is a week before the next month's first.
+        RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) if nth == LAST_WEEKDAY => {
+            format!("{} 1 --7", weekday)
+        }
+        RecurrencePattern::EveryNthWeekdayOfMonth(weekday, nth) => {
+            format!("{} {}", weekday, 7 * u32::from(nth - 1) + 1)
+        }
+    };
+
+    let text = if event.message.is_empty() {
+        &event.name
+    } else {
+        &event.message
+    };
+
+    format!(
+        "REM {} AT {} MSG {} %a",
+        date,
+        event.date.format("%H:%M"),
+        text.replace('%', "%%")
+    )
+}
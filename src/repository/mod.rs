@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, Duration, DurationRound, Local, NaiveDate, TimeZone, Utc};
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    db::{self, to_sql_date, DEFAULT_CALENDAR},
+    error::NotifyMeError,
+    event::{
+        parse_stored_date, partition_rows, Event, Priority, RecurrencePattern, SkippedRow,
+        EVENT_COLUMNS, PRIORITY_ORDER,
+    },
+    json::Value,
+};
+
+/// The `WHERE` clause of an `EventFilter`, bound by `filter_params`.
+const FILTER_CONDITIONS: &str = "(?1 OR deleted_at IS NULL) \
+     AND date >= ?2 AND date < ?3 \
+     AND (?4 IS NULL OR calendar = ?4) \
+     AND (?5 IS NULL OR (SELECT COUNT(*) FROM event_tags et \
+         JOIN tags t ON t.id = et.tag_id \
+         WHERE et.event_id = events.id \
+         AND t.name IN (SELECT value FROM json_each(?5))) \
+         >= CASE WHEN ?6 THEN 1 ELSE json_array_length(?5) END) \
+     AND (?7 IS NULL OR recurrence_pattern = ?7)";
+
+fn list_query(filter: &EventFilter) -> String {
+    format!(
+        "SELECT {} FROM events WHERE {} ORDER BY {} LIMIT COALESCE(?8, -1);",
+        EVENT_COLUMNS, FILTER_CONDITIONS, filter.order_by
+    )
+}
+
+fn filter_params(
+    filter: &EventFilter,
+) -> (
+    bool,
+    String,
+    String,
+    Option<&str>,
+    Option<String>,
+    bool,
+    Option<String>,
+) {
+    // The tags go in as a JSON array for `json_each`; `parse_tags` has
+    // already dropped duplicates, so matching them all means matching as
+    // many as there are.
+    let tags = (!filter.tags.is_empty()).then(|| {
+        Value::Array(filter.tags.iter().map(|tag| tag.as_str().into()).collect()).to_string()
+    });
+
+    (
+        filter.include_deleted,
+        to_sql_date(&filter.range.0),
+        to_sql_date(&filter.range.1),
+        filter.calendar.as_deref(),
+        tags,
+        filter.any_tag,
+        filter.recurrence.map(String::from),
+    )
+}
+
+/// An event to insert; the database assigns its id, uuid and timestamps.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub name: String,
+    pub message: String,
+    pub recurrence: RecurrencePattern,
+    pub date: DateTime<Utc>,
+    pub priority: Priority,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub color: Option<String>,
+    pub timezone: Option<String>,
+    pub tags: Vec<String>,
+    /// The default calendar when `None`.
+    pub calendar: Option<String>,
+}
+
+impl NewEvent {
+    /// A one-time, normal priority event with no message.
+    pub fn new(name: impl Into<String>, date: DateTime<Utc>) -> Self {
+        Self {
+            name: name.into(),
+            message: String::new(),
+            recurrence: RecurrencePattern::Once,
+            date,
+            priority: Priority::default(),
+            location: None,
+            url: None,
+            color: None,
+            timezone: None,
+            tags: Vec::new(),
+            calendar: None,
+        }
+    }
+}
+
+impl From<Event> for NewEvent {
+    /// The fields of `event` the database doesn't assign itself.
+    fn from(event: Event) -> Self {
+        Self {
+            name: event.name,
+            message: event.message,
+            recurrence: event.recurrence_pattern,
+            date: event.date.with_timezone(&Utc),
+            priority: event.priority,
+            location: event.location,
+            url: event.url,
+            color: event.color,
+            timezone: event.timezone,
+            tags: event.tags,
+            calendar: Some(event.calendar),
+        }
+    }
+}
+
+/// Which events `EventRepository::list` returns, and in what order;
+/// `EventRepository::count` counts the same events.
+#[derive(Debug, Clone)]
+pub struct EventFilter {
+    /// Events dated from the first instant up to the second.
+    pub range: (DateTime<Utc>, DateTime<Utc>),
+    pub calendar: Option<String>,
+    /// Events carrying every one of these tags, or any of them with
+    /// `any_tag`, compared case-insensitively; all events when empty. No
+    /// two may differ only in case.
+    pub tags: Vec<String>,
+    pub any_tag: bool,
+    pub recurrence: Option<RecurrencePattern>,
+    pub include_deleted: bool,
+    /// SQL ordering, e.g. from `EventSort::order_by`.
+    pub order_by: String,
+    /// At most this many events, or all of them when `None`.
+    pub limit: Option<u32>,
+}
+
+impl Default for EventFilter {
+    /// Every event outside the trash, by date.
+    fn default() -> Self {
+        Self {
+            range: unbounded_range(),
+            calendar: None,
+            tags: Vec::new(),
+            any_tag: false,
+            recurrence: None,
+            include_deleted: false,
+            order_by: "date".to_string(),
+            limit: None,
+        }
+    }
+}
+
+/// Typed queries over the `events` table, so `Client` and `Scheduler` share
+/// one copy of each instead of hand-rolling their own SQL.
+#[derive(Clone, Copy)]
+pub struct EventRepository<'a> {
+    conn: &'a Connection,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> EventRepository<'a> {
+    /// A repository over a migrated database, timestamping writes with the
+    /// system clock.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self {
+            conn,
+            clock: &SystemClock,
+        }
+    }
+
+    /// Timestamps writes with `clock` instead.
+    pub fn with_clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The underlying connection, for the tables other than `events`.
+    pub fn conn(&self) -> &'a Connection {
+        self.conn
+    }
+
+    pub fn clock(&self) -> &'a dyn Clock {
+        self.clock
+    }
+
+    /// Runs `f` against the same connection inside an immediate
+    /// transaction, committing only if it succeeds.
+    pub fn transaction<T, F>(&self, f: F) -> Result<T, NotifyMeError>
+    where
+        F: FnOnce(EventRepository<'_>) -> Result<T, NotifyMeError>,
+    {
+        let tx = Transaction::new_unchecked(self.conn, TransactionBehavior::Immediate)?;
+        let result = f(EventRepository::new(&tx).with_clock(self.clock))?;
+        tx.commit()?;
+
+        Ok(result)
+    }
+
+    /// Inserts `event` with its tags, returning it as stored.
+    pub fn insert(&self, event: NewEvent) -> Result<Event, NotifyMeError> {
+        let now = to_sql_date(&self.clock.now_utc());
+        self.conn.execute(
+            "INSERT INTO events (name, message, recurrence_pattern, date, created_at, \
+             updated_at, calendar, priority, location, url, color, timezone) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            (
+                &event.name,
+                &event.message,
+                event.recurrence,
+                to_sql_date(&event.date),
+                now,
+                event.calendar.as_deref().unwrap_or(DEFAULT_CALENDAR),
+                event.priority,
+                &event.location,
+                &event.url,
+                &event.color,
+                &event.timezone,
+            ),
+        )?;
+
+        let id = self.conn.last_insert_rowid();
+        db::set_event_tags(self.conn, id, &event.tags)?;
+
+        self.get(id as i32)
+    }
+
+    /// Event `id`, unless it is in the trash.
+    pub fn get(&self, id: i32) -> Result<Event, NotifyMeError> {
+        self.query_event(id, "deleted_at IS NULL")
+    }
+
+    /// Event `id`, in the trash or not.
+    pub fn get_including_deleted(&self, id: i32) -> Result<Event, NotifyMeError> {
+        self.query_event(id, "TRUE")
+    }
+
+    fn query_event(&self, id: i32, filter: &str) -> Result<Event, NotifyMeError> {
+        match self.conn.query_row(
+            &format!(
+                "SELECT {} FROM events WHERE id = ?1 AND {}",
+                EVENT_COLUMNS, filter
+            ),
+            [id],
+            Event::from_row,
+        ) {
+            Ok(event) => Ok(event),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(NotifyMeError::NotFound(id)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Events matching `filter`, along with the rows that couldn't be read.
+    pub fn list(
+        &self,
+        filter: &EventFilter,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let mut stmt = self.conn.prepare(&list_query(filter))?;
+
+        let (deleted, start, end, calendar, tags, any_tag, recurrence) = filter_params(filter);
+        let params = (
+            deleted,
+            start,
+            end,
+            calendar,
+            tags,
+            any_tag,
+            recurrence,
+            filter.limit,
+        );
+        let rows = stmt
+            .query_map(params, Event::try_from_row)
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// How SQLite runs `list` for `filter`: the `detail` of each step of
+    /// its query plan, e.g. `SEARCH events USING INDEX idx_events_date
+    /// (date>? AND date<?)`.
+    pub fn query_plan(&self, filter: &EventFilter) -> Result<Vec<String>, NotifyMeError> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", list_query(filter)))?;
+
+        let (deleted, start, end, calendar, tags, any_tag, recurrence) = filter_params(filter);
+        let params = (
+            deleted,
+            start,
+            end,
+            calendar,
+            tags,
+            any_tag,
+            recurrence,
+            filter.limit,
+        );
+        let plan = stmt
+            .query_map(params, |row| row.get("detail"))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(plan)
+    }
+
+    /// How many events `list` would return for `filter`, ignoring its
+    /// limit.
+    pub fn count(&self, filter: &EventFilter) -> Result<usize, NotifyMeError> {
+        Ok(self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM events WHERE {}", FILTER_CONDITIONS),
+            filter_params(filter),
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Events matching `filter` on the local day `day`.
+    pub fn find_on_day(
+        &self,
+        day: NaiveDate,
+        filter: &EventFilter,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        self.list(&EventFilter {
+            range: local_day_bounds(day),
+            ..filter.clone()
+        })
+    }
+
+    /// Events outside the trash owed a notification at `now`, by date: those
+    /// dated up to the minute `now` falls in that weren't notified since,
+    /// however long ago, and those within `lead` after it, owed an advance
+    /// warning. Events held back by a tick's limit are so still found on
+    /// the next. Those quiet hours deferred are left to `find_deferred`.
+    pub fn find_due(
+        &self,
+        now: DateTime<Utc>,
+        lead: Duration,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let next_minute = minute_of(now) + Duration::minutes(1);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE deleted_at IS NULL \
+             AND ((date < ?1 \
+                     AND (last_notified_at IS NULL OR last_notified_at < date) \
+                     AND (last_deferred_at IS NULL OR last_deferred_at < date)) \
+                 OR (date >= ?1 AND date < ?2)) \
+             ORDER BY date;",
+            EVENT_COLUMNS
+        ))?;
+
+        let rows = stmt
+            .query_map(
+                (
+                    to_sql_date(&next_minute),
+                    to_sql_date(&(next_minute + lead)),
+                ),
+                Event::try_from_row,
+            )
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// Events matching `filter` dated from the minute `now` falls in to
+    /// `window` after it, as the ahead half of `find_due` finds them.
+    pub fn find_due_soon(
+        &self,
+        now: DateTime<Utc>,
+        window: Duration,
+        filter: &EventFilter,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let minute = minute_of(now);
+
+        self.list(&EventFilter {
+            range: (minute, minute + window + Duration::minutes(1)),
+            ..filter.clone()
+        })
+    }
+
+    /// Recurring events outside the trash dated before `cutoff`, which a
+    /// stopped scheduler never moved on.
+    pub fn find_overdue_recurring(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE date < ?1 AND deleted_at IS NULL AND recurrence_pattern != 'once' \
+             ORDER BY date;",
+            EVENT_COLUMNS
+        ))?;
+
+        let rows = stmt
+            .query_map([to_sql_date(&cutoff)], Event::try_from_row)
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// One-time events outside the trash dated before `cutoff` that were
+    /// never notified, oldest first.
+    pub fn find_overdue_once(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE date < ?1 AND deleted_at IS NULL AND recurrence_pattern = 'once' \
+             AND last_notified_at IS NULL \
+             ORDER BY date;",
+            EVENT_COLUMNS
+        ))?;
+
+        let rows = stmt
+            .query_map([to_sql_date(&cutoff)], Event::try_from_row)
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// Events outside the trash dated from `since` up to `until` whose
+    /// notification never fired: not paused, and not notified at or after
+    /// their current date, which for recurring events would have moved them on.
+    pub fn find_missed(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE date >= ?1 AND date < ?2 AND deleted_at IS NULL AND NOT paused \
+             AND (last_notified_at IS NULL OR last_notified_at < date) \
+             ORDER BY date;",
+            EVENT_COLUMNS
+        ))?;
+
+        let rows = stmt
+            .query_map(
+                [to_sql_date(&since), to_sql_date(&until)],
+                Event::try_from_row,
+            )
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// Events outside the trash whose notification quiet hours held back,
+    /// high priority first, then by date.
+    pub fn find_deferred(&self) -> Result<(Vec<Event>, Vec<SkippedRow>), NotifyMeError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events \
+             WHERE last_deferred_at IS NOT NULL AND deleted_at IS NULL \
+             ORDER BY {}, date;",
+            EVENT_COLUMNS, PRIORITY_ORDER
+        ))?;
+
+        let rows = stmt
+            .query_map([], Event::try_from_row)
+            .and_then(partition_rows)?;
+
+        Ok(rows)
+    }
+
+    /// Moves event `id` to `date`.
+    pub fn update_date<Tz: TimeZone>(
+        &self,
+        id: i32,
+        date: &DateTime<Tz>,
+    ) -> Result<(), NotifyMeError> {
+        self.conn.execute(
+            "UPDATE events SET date = ?1, updated_at = ?2 WHERE id = ?3",
+            (to_sql_date(date), to_sql_date(&self.clock.now_utc()), id),
+        )?;
+
+        Ok(())
+    }
+
+    /// How many events outside the trash are dated from `start` up to `end`.
+    pub fn count_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<usize, NotifyMeError> {
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*) FROM events WHERE date >= ?1 AND date < ?2 AND deleted_at IS NULL",
+            (to_sql_date(&start), to_sql_date(&end)),
+            |row| row.get(0),
+        )?)
+    }
+
+    /// How many events are in the trash.
+    pub fn count_deleted(&self) -> Result<usize, NotifyMeError> {
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*) FROM events WHERE deleted_at IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// How many events outside the trash repeat by each pattern in use,
+    /// keyed by the pattern as stored, e.g. `every_n_weeks:2`.
+    pub fn count_by_recurrence(&self) -> Result<HashMap<String, usize>, NotifyMeError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT recurrence_pattern, COUNT(*) FROM events \
+             WHERE deleted_at IS NULL GROUP BY recurrence_pattern",
+        )?;
+
+        let counts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<String, usize>>>()?;
+
+        Ok(counts)
+    }
+
+    /// How many events outside the trash fall on each local weekday, from
+    /// Monday. Recurring events count once, on their next occurrence.
+    pub fn count_by_weekday(&self) -> Result<[usize; 7], NotifyMeError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date FROM events WHERE deleted_at IS NULL")?;
+        let dates = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        // Rows with unreadable dates are reported wherever events are listed.
+        let mut counts = [0; 7];
+        for date in dates.iter().filter_map(|raw| parse_stored_date(raw).ok()) {
+            let weekday = date.with_timezone(&Local).weekday();
+            counts[weekday.num_days_from_monday() as usize] += 1;
+        }
+
+        Ok(counts)
+    }
+
+    /// Records that event `id` was notified at `at`, which settles any
+    /// notification quiet hours held back.
+    pub fn mark_notified(&self, id: i32, at: DateTime<Utc>) -> Result<(), NotifyMeError> {
+        self.conn.execute(
+            "UPDATE events SET last_notified_at = ?1, last_deferred_at = NULL WHERE id = ?2",
+            (to_sql_date(&at), id),
+        )?;
+
+        Ok(())
+    }
+
+    /// Records that quiet hours held back event `id`'s notification at `at`,
+    /// for `find_deferred`.
+    pub fn mark_deferred(&self, id: i32, at: DateTime<Utc>) -> Result<(), NotifyMeError> {
+        self.conn.execute(
+            "UPDATE events SET last_deferred_at = ?1 WHERE id = ?2",
+            (to_sql_date(&at), id),
+        )?;
+
+        Ok(())
+    }
+
+    /// Drops the notification quiet hours held back for event `id`, if any.
+    pub fn clear_deferred(&self, id: i32) -> Result<(), NotifyMeError> {
+        self.conn.execute(
+            "UPDATE events SET last_deferred_at = NULL WHERE id = ?1",
+            [id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Moves event `id` to the trash. Timestamps are written with
+    /// `to_sql_date` rather than `datetime('now')` so they sort and compare
+    /// like every other date column.
+    pub fn soft_delete(&self, id: i32) -> Result<(), NotifyMeError> {
+        self.conn.execute(
+            "UPDATE events SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2",
+            (to_sql_date(&self.clock.now_utc()), id),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The start of the minute `now` falls in, so a window matches the same
+/// events whichever second it is computed at.
+fn minute_of(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.duration_trunc(Duration::minutes(1)).unwrap()
+}
+
+/// Bounds covering every date: the epoch and the end of the year 9999,
+/// which still compares correctly as stored text.
+pub fn unbounded_range() -> (DateTime<Utc>, DateTime<Utc>) {
+    (
+        DateTime::UNIX_EPOCH,
+        Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap(),
+    )
+}
+
+/// The UTC instants bounding a local calendar day, for indexed range queries.
+pub(crate) fn local_day_bounds(day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start_of = |day: NaiveDate| {
+        Local
+            .from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .unwrap_or_else(|| Local.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap()))
+            .with_timezone(&Utc)
+    };
+
+    (start_of(day), start_of(day + Days::new(1)))
+}
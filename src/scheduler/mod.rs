@@ -1,9 +1,33 @@
-use chrono::{DateTime, Datelike, Duration, Local, Utc};
-use log::{error, info};
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration as StdDuration, Instant},
+};
+
+use chrono::{DateTime, Duration, DurationRound, Local, Timelike, Utc};
 use notify_rust::Notification;
 use rusqlite::Connection;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::thread;
 
-use crate::event::{Event, RecurrencePattern};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::opener;
+use crate::{
+    backup::PreOpBackup,
+    client::{Client, PurgeTarget},
+    clock::{Clock, SystemClock},
+    db::{get_meta, set_meta, to_sql_date},
+    error::NotifyMeError,
+    event::{advance_date_in, Event, Priority, RecurrencePattern},
+    repository::EventRepository,
+};
+
+/// How long before an event its advance notification fires.
+pub const DEFAULT_ADVANCE_MINUTES: u32 = 10;
+
+/// Seconds between scheduler ticks unless `--interval` says otherwise.
+pub const DEFAULT_INTERVAL_SECS: u64 = 60;
 
 #[cfg(target_os = "macos")]
 static SOUND: &str = "Submarine";
@@ -14,121 +38,847 @@ static SOUND: &str = "message-new-instant";
 #[cfg(target_os = "windows")]
 static SOUND: &str = "Mail";
 
-pub struct Scheduler<'a> {
-    conn: &'a Connection,
+/// Upper bound on notifications fired in a single tick, so a backlog of due
+/// events (e.g. after the machine was suspended) doesn't flood the desktop.
+pub const DEFAULT_MAX_NOTIFICATIONS_PER_TICK: u32 = 5;
+
+/// Which of its notifications an event is getting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationKind {
+    /// The advance warning, `advance_minutes` before the event.
+    Upcoming,
+    /// At the event's time, or once it has passed.
+    Due,
 }
 
-impl<'a> Scheduler<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+/// Shows the scheduler's notifications, so the scheduling logic can be run
+/// without a desktop. Notifications are shown off the tick loop, on the
+/// blocking thread pool.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, event: &Event, kind: NotificationKind) -> Result<(), NotifyMeError>;
+}
+
+/// Desktop notifications through notify-rust.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, event: &Event, _kind: NotificationKind) -> Result<(), NotifyMeError> {
+        show_notification(event)
     }
+}
 
-    fn check_and_notify(&self) -> Result<(), String> {
-        let mut stmt = match self.conn.prepare(
-            "SELECT id, name, message, recurrence_pattern, date, deleted_at FROM events \
-           WHERE (strftime('%Y-%m-%d %H:%M', date) = strftime('%Y-%m-%d %H:%M', 'now') \
-           OR strftime('%Y-%m-%d %H:%M', date) = strftime('%Y-%m-%d %H:%M', datetime('now', '+10 minutes')))
-           AND deleted_at IS NULL;",
-        ) {
-            Ok(stmt) => stmt,
-            Err(err) => return Err(err.to_string()),
-        };
+/// Meta key holding the local date the trash was last cleaned up.
+const LAST_TRASH_CLEANUP_KEY: &str = "last_trash_cleanup";
 
-        let events = match stmt.query_map([], |row| {
-            Ok(Event {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                message: row.get(2)?,
-                recurrence_pattern: row.get(3)?,
-                date: DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
-                    .unwrap()
-                    .with_timezone(&Local),
-                deleted_at: row.get::<_, Option<String>>(5)?.and_then(|dt| {
-                    DateTime::parse_from_rfc3339(&dt)
-                        .ok()
-                        .map(|dt| dt.with_timezone(&Utc))
-                }),
-            })
-        }) {
-            Ok(events) => events
-                .filter_map(|event| event.ok())
-                .collect::<Vec<Event>>(),
-            Err(err) => return Err(err.to_string()),
+/// Meta key holding when the scheduler last finished a tick.
+const LAST_RUN_KEY: &str = "last_run_at";
+
+/// Polls the events table and shows a notification for each event as it
+/// comes due, advancing recurring events to their next occurrence. Built
+/// with `Scheduler::builder`.
+pub struct Scheduler {
+    conn: Arc<Mutex<Connection>>,
+    notifier: Box<dyn Notifier>,
+    clock: Arc<dyn Clock>,
+    interval_secs: u64,
+    advance_minutes: i64,
+    max_notifications_per_tick: u32,
+    trash_retention_days: Option<u32>,
+    pre_op_backup: Option<PreOpBackup>,
+    skip_catchup_notifications: bool,
+    quiet_hours: Option<(u8, u8)>,
+    dry_run: bool,
+}
+
+/// Configures a `Scheduler`. Only the connection is required; notifications
+/// go to the desktop unless another `Notifier` is given.
+pub struct SchedulerBuilder {
+    conn: Option<Arc<Mutex<Connection>>>,
+    notifier: Option<Box<dyn Notifier>>,
+    clock: Option<Arc<dyn Clock>>,
+    interval_secs: u64,
+    advance_minutes: u32,
+    max_notifications_per_tick: u32,
+    trash_retention_days: Option<u32>,
+    pre_op_backup: Option<PreOpBackup>,
+    skip_catchup_notifications: bool,
+    quiet_hours: Option<(u8, u8)>,
+    dry_run: bool,
+}
+
+impl Default for SchedulerBuilder {
+    fn default() -> Self {
+        Self {
+            conn: None,
+            notifier: None,
+            clock: None,
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            advance_minutes: DEFAULT_ADVANCE_MINUTES,
+            max_notifications_per_tick: DEFAULT_MAX_NOTIFICATIONS_PER_TICK,
+            trash_retention_days: None,
+            pre_op_backup: None,
+            skip_catchup_notifications: true,
+            quiet_hours: None,
+            dry_run: false,
+        }
+    }
+}
+
+impl SchedulerBuilder {
+    /// The migrated database to schedule from.
+    pub fn connection(mut self, conn: Arc<Mutex<Connection>>) -> Self {
+        self.conn = Some(conn);
+        self
+    }
+
+    pub fn notifier(mut self, notifier: Box<dyn Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Where ticks read the time from; the system clock by default.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn interval_secs(mut self, interval_secs: u64) -> Self {
+        self.interval_secs = interval_secs;
+        self
+    }
+
+    /// How long before an event its advance notification fires.
+    pub fn advance_minutes(mut self, advance_minutes: u32) -> Self {
+        self.advance_minutes = advance_minutes;
+        self
+    }
+
+    pub fn max_notifications_per_tick(mut self, max_notifications_per_tick: u32) -> Self {
+        self.max_notifications_per_tick = max_notifications_per_tick;
+        self
+    }
+
+    pub fn trash_retention_days(mut self, trash_retention_days: Option<u32>) -> Self {
+        self.trash_retention_days = trash_retention_days;
+        self
+    }
+
+    /// Where expiring the trash snapshots the database first; no snapshot
+    /// is taken without one.
+    pub fn pre_op_backup(mut self, pre_op_backup: Option<PreOpBackup>) -> Self {
+        self.pre_op_backup = pre_op_backup;
+        self
+    }
+
+    /// Whether `catch_up` stays quiet about occurrences missed while the
+    /// scheduler wasn't running (the default) or notifies each event once.
+    pub fn skip_catchup_notifications(mut self, skip_catchup_notifications: bool) -> Self {
+        self.skip_catchup_notifications = skip_catchup_notifications;
+        self
+    }
+
+    /// Local hours, from `start` up to `end`, during which only high
+    /// priority events notify. Other events coming due are held back until
+    /// the first tick after the quiet hours, and their advance notifications
+    /// are skipped. The range may wrap past midnight, e.g. 22 to 7.
+    pub fn quiet_hours(mut self, start: u8, end: u8) -> Self {
+        self.quiet_hours = Some((start, end));
+        self
+    }
+
+    /// Logs the notifications that would fire without showing them or
+    /// touching the database.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn build(self) -> Result<Scheduler, NotifyMeError> {
+        let Some(conn) = self.conn else {
+            return Err(NotifyMeError::Config(
+                "the scheduler needs a connection".to_string(),
+            ));
         };
 
-        for event in events {
-            match Notification::new()
-                .summary(&event.name)
-                .sound_name(SOUND)
-                .body(&event.message)
-                .icon("computer")
-                .show()
-            {
-                Ok(_) => (),
-                Err(err) => return Err(err.to_string()),
+        if self.interval_secs == 0 {
+            return Err(NotifyMeError::Config(
+                "the scheduler interval must be at least a second".to_string(),
+            ));
+        }
+
+        if self.max_notifications_per_tick == 0 {
+            return Err(NotifyMeError::Config(
+                "the scheduler must fire at least one notification per tick".to_string(),
+            ));
+        }
+
+        if let Some((start, end)) = self.quiet_hours {
+            if start > 23 || end > 23 || start == end {
+                return Err(NotifyMeError::Config(format!(
+                    "quiet hours {}-{} must be two different hours from 0 to 23",
+                    start, end
+                )));
             }
+        }
+
+        if self.interval_secs > u64::from(self.advance_minutes) * 60 {
+            warn!(
+                "Ticking every {}s is longer than the {} minute advance window, \
+                 some advance notifications may be missed",
+                self.interval_secs, self.advance_minutes
+            );
+        }
+
+        Ok(Scheduler {
+            conn,
+            notifier: self.notifier.unwrap_or_else(|| Box::new(DesktopNotifier)),
+            interval_secs: self.interval_secs,
+            advance_minutes: self.advance_minutes.into(),
+            max_notifications_per_tick: self.max_notifications_per_tick,
+            trash_retention_days: self.trash_retention_days,
+            pre_op_backup: self.pre_op_backup,
+            skip_catchup_notifications: self.skip_catchup_notifications,
+            quiet_hours: self.quiet_hours,
+            dry_run: self.dry_run,
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+        })
+    }
+}
 
-            match event.recurrence_pattern {
-                RecurrencePattern::Once => (),
-                _ => {
-                    match self.update_event_date(event) {
-                        Ok(_) => return Ok(()),
-                        Err(err) => return Err(err.to_string()),
-                    };
+impl Scheduler {
+    pub fn builder() -> SchedulerBuilder {
+        SchedulerBuilder::default()
+    }
+
+    /// The scheduler's connection. Transactions roll back when a panic
+    /// unwinds through them, so a poisoned lock is still safe to use.
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn events<'c>(&'c self, conn: &'c Connection) -> EventRepository<'c> {
+        EventRepository::new(conn).with_clock(&*self.clock)
+    }
+
+    /// Whether `now` falls in the quiet hours, if any are set.
+    fn in_quiet_hours(&self, now: DateTime<Utc>) -> bool {
+        let Some((start, end)) = self.quiet_hours else {
+            return false;
+        };
+
+        let hour = now.with_timezone(&Local).hour() as u8;
+        if start < end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    /// Moves recurring events whose date passed while the scheduler wasn't
+    /// running to their next occurrence. Events still inside the notification
+    /// window are left to the first tick, which owes them a notification.
+    /// Returns how many notifications were shown.
+    pub fn catch_up(&self) -> Result<usize, NotifyMeError> {
+        let missed = self.claim_missed()?;
+
+        Ok(self.dispatch(&missed))
+    }
+
+    /// The database half of `catch_up`, returning the notifications owed
+    /// for the events it moved on.
+    fn claim_missed(&self) -> Result<Vec<(NotificationKind, Event)>, NotifyMeError> {
+        let advance = Duration::minutes(self.advance_minutes);
+        let cutoff = self
+            .clock
+            .now_utc()
+            .duration_trunc(Duration::minutes(1))
+            .unwrap()
+            - advance;
+
+        let conn = self.conn();
+        let events = self.events(&conn);
+        let (overdue, _) = events.find_overdue_recurring(cutoff)?;
+
+        events.transaction(|events| {
+            let mut notifications = Vec::new();
+            for mut event in overdue {
+                let missed_date = event.date;
+                let mut missed = 0;
+                while event.date.with_timezone(&Utc) < cutoff {
+                    event.date = advance_date_in(
+                        event.date,
+                        &event.recurrence_pattern,
+                        event.zone().as_ref(),
+                    );
+                    missed += 1;
                 }
-            };
+
+                if self.dry_run {
+                    info!(
+                        id = event.id,
+                        name = %event.name,
+                        occurrence = %missed_date.to_rfc3339(),
+                        "Dry run: would move it on {} times to {}",
+                        missed,
+                        event.date.format("%Y-%m-%d %H:%M")
+                    );
+                    continue;
+                }
+
+                events.update_date(event.id, &event.date)?;
+                // Restarting stays quiet about missed occurrences, including
+                // ones quiet hours held back.
+                events.clear_deferred(event.id)?;
+
+                info!(
+                    id = event.id,
+                    name = %event.name,
+                    occurrence = %missed_date.to_rfc3339(),
+                    "Caught up ({} missed), next at {}",
+                    missed,
+                    event.date.format("%Y-%m-%d %H:%M")
+                );
+
+                if !self.skip_catchup_notifications && !event.paused {
+                    notifications.push((NotificationKind::Due, event));
+                }
+            }
+
+            Ok(notifications)
+        })
+    }
+
+    /// Events dated between `cutoff` and now that were never notified,
+    /// e.g. because the machine was off or asleep, by date.
+    pub fn missed_events_since(&self, cutoff: DateTime<Utc>) -> Result<Vec<Event>, NotifyMeError> {
+        let conn = self.conn();
+        let (missed, _) = self
+            .events(&conn)
+            .find_missed(cutoff, self.clock.now_utc())?;
+
+        Ok(missed)
+    }
+
+    /// Warns about the events missed since the last tick, when that was
+    /// longer ago than the advance window a tick covers. Runs before
+    /// `claim_missed` moves recurring ones on.
+    fn report_missed(&self) -> Result<(), NotifyMeError> {
+        let last_run = {
+            let conn = self.conn();
+            get_meta(&conn, LAST_RUN_KEY)?
+        };
+        let Some(last_run) = last_run.and_then(|at| DateTime::parse_from_rfc3339(&at).ok()) else {
+            return Ok(());
+        };
+        let last_run = last_run.with_timezone(&Utc);
+
+        if self.clock.now_utc() - last_run <= Duration::minutes(self.advance_minutes) {
+            return Ok(());
+        }
+
+        let missed = self.missed_events_since(last_run)?;
+        if !missed.is_empty() {
+            let events = missed
+                .iter()
+                .map(|event| format!("{} ({})", event.name, event.date.format("%Y-%m-%d %H:%M")))
+                .collect::<Vec<_>>();
+            warn!(
+                "Missed {} events since {}: {}",
+                missed.len(),
+                last_run.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                events.join(", ")
+            );
         }
 
         Ok(())
     }
 
-    fn update_event_date(&self, event: Event) -> Result<(), String> {
-        let mut stmt = match self
-            .conn
-            .prepare("UPDATE events SET date = ?1 WHERE id = ?2;")
-        {
-            Ok(stmt) => stmt,
-            Err(err) => return Err(err.to_string()),
-        };
+    /// Records that a tick finished at `now`, for `report_missed`.
+    fn record_run(&self, now: DateTime<Utc>) -> Result<(), NotifyMeError> {
+        if self.dry_run {
+            return Ok(());
+        }
 
-        let new_date = match event.recurrence_pattern {
-            RecurrencePattern::Daily => event.date + Duration::days(1),
-            RecurrencePattern::Weekly => event.date + Duration::weeks(1),
-            RecurrencePattern::Monthly => {
-                let next_month = event.date.month() % 12 + 1; // wraps around after December
-                let next_year = if next_month == 1 {
-                    event.date.year() + 1
-                } else {
-                    event.date.year()
-                };
-
-                event
-                    .date
-                    .with_year(next_year)
-                    .unwrap()
-                    .with_month(next_month)
-                    .unwrap_or(event.date)
+        set_meta(&self.conn(), LAST_RUN_KEY, &to_sql_date(&now))?;
+        Ok(())
+    }
+
+    /// Notifies the events owed a notification at `now`, then moves
+    /// recurring ones on to their next occurrence. Returns how many
+    /// notifications were shown.
+    pub fn check_and_notify(&self, now: DateTime<Utc>) -> Result<usize, NotifyMeError> {
+        let due = self.claim_due(now)?;
+
+        Ok(self.dispatch(&due))
+    }
+
+    /// One tick on demand, outside the loop `start` runs: catches up on
+    /// missed occurrences, then checks at the clock's current time. Returns
+    /// how many notifications were shown.
+    pub fn check_now(&self) -> Result<usize, NotifyMeError> {
+        let missed = self.catch_up()?;
+        let due = self.check_and_notify(self.clock.now_utc())?;
+
+        Ok(missed + due)
+    }
+
+    /// The database half of `check_and_notify`: marks the events owed a
+    /// notification at `now` as notified, moves recurring ones that are due
+    /// on to their next occurrence, and returns the notifications to show.
+    /// Claiming them up front means a tick that starts while the last one's
+    /// notifications are still showing doesn't show them twice.
+    fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(NotificationKind, Event)>, NotifyMeError> {
+        let advance = Duration::minutes(self.advance_minutes);
+
+        // Unreadable rows are logged and left for `db check` rather than
+        // holding up every other event.
+        let conn = self.conn();
+        let events = self.events(&conn);
+        let (candidates, _) = events.find_due(now, advance)?;
+        let quiet = self.in_quiet_hours(now);
+
+        // Once quiet hours are over, what they held back goes first.
+        let mut due = Vec::new();
+        if !quiet {
+            let (deferred, _) = events.find_deferred()?;
+            due.extend(
+                deferred
+                    .into_iter()
+                    .map(|event| (NotificationKind::Due, event)),
+            );
+        }
+        let deferred_ids: Vec<_> = due.iter().map(|(_, event)| event.id).collect();
+        due.extend(
+            candidates
+                .into_iter()
+                .filter(|event| !deferred_ids.contains(&event.id))
+                .filter_map(|event| Some((notification_kind(&event, now, advance)?, event))),
+        );
+
+        let muted = muted_calendars(&conn)?;
+
+        let total = due.len();
+        let mut notifications = Vec::new();
+        // Only notifications to show count towards the limit, so deferring
+        // or silencing events doesn't crowd out those that do notify.
+        for (index, (kind, event)) in due.into_iter().enumerate() {
+            if notifications.len() as u32 >= self.max_notifications_per_tick {
+                warn!(
+                    "Fired {} notifications this tick, deferring {} more to the next one",
+                    notifications.len(),
+                    total - index
+                );
+                break;
             }
-            _ => unreachable!(),
+
+            let occurrence = event.date.to_rfc3339();
+            let _event = info_span!(
+                "event",
+                id = event.id,
+                name = %event.name,
+                occurrence = %occurrence,
+            )
+            .entered();
+
+            if self.dry_run {
+                info!(?kind, "Dry run: would notify");
+                continue;
+            }
+
+            let silenced = if muted.contains(&event.calendar) {
+                info!(calendar = %event.calendar, "Calendar is muted, not notifying");
+                true
+            } else if event.paused {
+                info!("Paused, not notifying");
+                true
+            } else if quiet && event.priority == Priority::High {
+                info!("High priority, notifying despite quiet hours");
+                false
+            } else if quiet && kind == NotificationKind::Due {
+                info!("Quiet hours, deferring until they end");
+                events.mark_deferred(event.id, now)?;
+                continue;
+            } else if quiet {
+                info!("Quiet hours, not notifying");
+                true
+            } else {
+                false
+            };
+
+            let claimed = events.transaction(|events| {
+                if !unchanged(events, &event)? {
+                    return Ok(false);
+                }
+                events.mark_notified(event.id, now)?;
+
+                // The warning leaves the event where it is, still owed its
+                // notification at its time.
+                if kind == NotificationKind::Due
+                    && !matches!(event.recurrence_pattern, RecurrencePattern::Once)
+                {
+                    advance_event_date(events, &event)?;
+                }
+
+                Ok(true)
+            })?;
+            if !claimed {
+                info!("Claimed through another connection, not notifying");
+                continue;
+            }
+
+            if !silenced {
+                notifications.push((kind, event));
+            }
+        }
+
+        Ok(notifications)
+    }
+
+    /// Shows `notifications`, logging the ones that fail rather than giving
+    /// up on the rest. Returns how many were shown.
+    fn dispatch(&self, notifications: &[(NotificationKind, Event)]) -> usize {
+        let mut shown = 0;
+        for (kind, event) in notifications {
+            let occurrence = event.date.to_rfc3339();
+            let _event = info_span!(
+                "event",
+                id = event.id,
+                name = %event.name,
+                occurrence = %occurrence,
+            )
+            .entered();
+
+            match self.notifier.notify(event, *kind) {
+                Ok(()) => {
+                    info!(?kind, "Notified");
+                    shown += 1;
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::notification_fired();
+                }
+                Err(err) => {
+                    error!(?kind, "Notifying failed: {}", err);
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::notification_failed();
+                }
+            }
+        }
+
+        shown
+    }
+
+    /// Once a day, permanently removes events that have been in the trash
+    /// longer than the configured retention period.
+    fn expire_trash(&self) -> Result<(), NotifyMeError> {
+        let Some(retention_days) = self.trash_retention_days else {
+            return Ok(());
         };
 
-        match stmt.execute((new_date.to_rfc3339(), event.id)) {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err.to_string()),
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let conn = self.conn();
+        let today = self.clock.today().to_string();
+        if get_meta(&conn, LAST_TRASH_CLEANUP_KEY)?.as_deref() == Some(today.as_str()) {
+            return Ok(());
         }
+
+        let removed = Client::new(&conn)
+            .with_clock(&*self.clock)
+            .with_pre_op_backup(self.pre_op_backup.clone())
+            .purge(
+                PurgeTarget::OlderThan(Duration::days(retention_days.into())),
+                true,
+            )?;
+
+        set_meta(&conn, LAST_TRASH_CLEANUP_KEY, &today)?;
+
+        info!(
+            "Removed {} events deleted more than {} days ago",
+            removed, retention_days
+        );
+
+        Ok(())
     }
 
-    pub async fn start(&self) {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+    /// Ticks every `interval_secs` until the process exits. Database work
+    /// runs on the blocking thread pool and notifications are shown there in
+    /// the background, so neither a slow disk nor a notification daemon that
+    /// takes seconds to answer holds up the next tick.
+    pub async fn start(self: Arc<Self>) {
+        let period = StdDuration::from_secs(self.interval_secs);
+        let mut interval = tokio::time::interval(period);
+
+        let catch_up = async {
+            if let Err(err) = self.blocking(Scheduler::report_missed).await {
+                error!("Looking for missed events failed: {}", err);
+            }
+
+            match self.blocking(Scheduler::claim_missed).await {
+                Ok(missed) => self.dispatch_in_background(missed),
+                Err(err) => error!("Catching up on missed events failed: {}", err),
+            }
+        };
+        catch_up.instrument(info_span!("catch_up")).await;
 
         loop {
             interval.tick().await;
-            info!("Starting tick");
-            if let Err(err) = self.check_and_notify() {
-                error!("{}", err);
-            } else {
+            let now = self.clock.now_utc();
+            let tick = info_span!("tick", at = %to_sql_date(&now));
+
+            self.tick(now, period).instrument(tick).await;
+        }
+    }
+
+    /// One iteration of `start`'s loop, run inside the tick's span.
+    async fn tick(self: &Arc<Self>, now: DateTime<Utc>, period: StdDuration) {
+        info!("Starting tick");
+        let started = Instant::now();
+
+        match self
+            .blocking(move |scheduler| scheduler.claim_due(now))
+            .await
+        {
+            Ok(due) => {
+                self.dispatch_in_background(due);
+                if let Err(err) = self
+                    .blocking(move |scheduler| scheduler.record_run(now))
+                    .await
+                {
+                    error!("Recording the tick failed: {}", err);
+                }
+                #[cfg(feature = "metrics")]
+                crate::metrics::tick_finished(now);
                 info!("Successfully ticked")
             }
+            Err(err) => error!("{}", err),
+        }
+
+        if let Err(err) = self.blocking(Scheduler::expire_trash).await {
+            error!("Trash cleanup failed: {}", err);
+        }
+
+        let elapsed = started.elapsed();
+        if elapsed > period {
+            warn!(
+                "Tick took {:.1}s, longer than the {}s interval",
+                elapsed.as_secs_f64(),
+                self.interval_secs
+            );
+        }
+    }
+
+    /// Runs `f` on the blocking thread pool, inside the caller's span.
+    async fn blocking<T, F>(self: &Arc<Self>, f: F) -> Result<T, NotifyMeError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Scheduler) -> Result<T, NotifyMeError> + Send + 'static,
+    {
+        let scheduler = Arc::clone(self);
+        let span = Span::current();
+        match tokio::task::spawn_blocking(move || span.in_scope(|| f(&scheduler))).await {
+            Ok(result) => result,
+            Err(err) => Err(format!("Scheduler task failed: {}", err).into()),
+        }
+    }
+
+    fn dispatch_in_background(self: &Arc<Self>, notifications: Vec<(NotificationKind, Event)>) {
+        if notifications.is_empty() {
+            return;
+        }
+
+        let scheduler = Arc::clone(self);
+        let span = Span::current();
+        tokio::task::spawn_blocking(move || {
+            span.in_scope(|| scheduler.dispatch(&notifications));
+        });
+    }
+}
+
+fn muted_calendars(conn: &Connection) -> Result<Vec<String>, NotifyMeError> {
+    let mut stmt = conn.prepare("SELECT calendar FROM calendar_settings WHERE muted")?;
+
+    let calendars = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    Ok(calendars)
+}
+
+/// Whether `event` is still stored as it was read: not notified, moved or
+/// deleted through another connection since. Checked inside the claim's
+/// transaction, so of two schedulers on one database only one claims it.
+fn unchanged(events: EventRepository, event: &Event) -> Result<bool, NotifyMeError> {
+    let stored = match events.get(event.id) {
+        Ok(stored) => stored,
+        Err(NotifyMeError::NotFound(_)) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    Ok(stored.date == event.date && stored.last_notified_at == event.last_notified_at)
+}
+
+/// Moves `event` to its next occurrence, returning the new date.
+fn advance_event_date(
+    events: EventRepository,
+    event: &Event,
+) -> Result<DateTime<Local>, NotifyMeError> {
+    let new_date = advance_date_in(event.date, &event.recurrence_pattern, event.zone().as_ref());
+    events.update_date(event.id, &new_date)?;
+
+    Ok(new_date)
+}
+
+/// Whether notifications can carry buttons; only the freedesktop
+/// notification spec has them.
+const ACTIONS_SUPPORTED: bool = cfg!(all(unix, not(target_os = "macos")));
+
+/// Shows the desktop notification for `event`. An event with a link gets an
+/// "Open" button where actions are supported; waiting for it to be clicked
+/// blocks, so that notification is shown from its own thread.
+fn show_notification(event: &Event) -> Result<(), NotifyMeError> {
+    let mut notification = Notification::new();
+    notification
+        .summary(&event.name)
+        .sound_name(SOUND)
+        .body(&notification_body(event))
+        .icon("computer");
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(url) = event.url.clone() {
+        notification.action("open", "Open");
+        thread::spawn(move || match notification.show() {
+            Ok(handle) => handle.wait_for_action(|action| {
+                if action == "open" {
+                    if let Err(err) = opener::open(&url) {
+                        error!("{}", err);
+                    }
+                }
+            }),
+            Err(err) => error!("{}", err),
+        });
+
+        return Ok(());
+    }
+
+    notification.show()?;
+    Ok(())
+}
+
+/// What to do when notifications can't be shown on this platform.
+#[cfg(all(unix, not(target_os = "macos")))]
+const NOTIFY_HINT: &str = "On Linux, ensure a notification daemon is running (dunst, mako, etc.)";
+#[cfg(target_os = "macos")]
+const NOTIFY_HINT: &str =
+    "Allow notifications for your terminal in System Settings > Notifications";
+#[cfg(windows)]
+const NOTIFY_HINT: &str = "Turn notifications on in Settings > System > Notifications";
+
+/// One line of the `--check-notify` report.
+pub struct NotifyCheck {
+    pub label: &'static str,
+    /// What was found, or why the check failed.
+    pub outcome: Result<String, String>,
+}
+
+/// Checks that notifications can be shown here, ending with a test
+/// notification. On Linux the notification daemon is queried first, since
+/// without one notifications fail silently.
+pub fn check_notifications() -> Vec<NotifyCheck> {
+    let mut checks = Vec::new();
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let server = notify_rust::get_server_information()
+            .map(|info| {
+                format!(
+                    "{} {} (spec {})",
+                    info.name, info.version, info.spec_version
+                )
+            })
+            .map_err(|err| format!("{}\n  {}", err, NOTIFY_HINT));
+        let found = server.is_ok();
+        checks.push(NotifyCheck {
+            label: "notification daemon",
+            outcome: server,
+        });
+
+        if !found {
+            return checks;
+        }
+
+        let actions = notify_rust::get_capabilities()
+            .map(|capabilities| {
+                if capabilities.iter().any(|c| c == "actions") {
+                    "supported".to_string()
+                } else {
+                    "not supported, links go in the message instead".to_string()
+                }
+            })
+            .map_err(|err| err.to_string());
+        checks.push(NotifyCheck {
+            label: "\"Open\" buttons",
+            outcome: actions,
+        });
+    }
+
+    let test = Notification::new()
+        .summary(env!("CARGO_PKG_NAME"))
+        .body("Notifications are working")
+        .sound_name(SOUND)
+        .show()
+        .map(|_| "sent".to_string())
+        .map_err(|err| format!("{}\n  {}", err, NOTIFY_HINT));
+    checks.push(NotifyCheck {
+        label: "test notification",
+        outcome: test,
+    });
+
+    checks
+}
+
+/// The event's message, followed by where it takes place when known, and by
+/// its link when the notification can't have an "Open" button.
+fn notification_body(event: &Event) -> String {
+    let mut lines = Vec::new();
+    if !event.message.is_empty() {
+        lines.push(event.message.clone());
+    }
+
+    if let Some(location) = &event.location {
+        lines.push(format!("Where: {}", location));
+    }
+
+    if let (false, Some(url)) = (ACTIONS_SUPPORTED, &event.url) {
+        lines.push(url.clone());
+    }
+
+    lines.join("\n")
+}
+
+/// Which notification, if any, an event in the scheduler's window is owed:
+/// the advance warning once it is within `advance`, then the notification at
+/// its time. `last_notified_at` records what already fired, so events
+/// deferred by the per-tick limit are picked up on later ticks.
+fn notification_kind(
+    event: &Event,
+    now: DateTime<Utc>,
+    advance: Duration,
+) -> Option<NotificationKind> {
+    let date = event.date.with_timezone(&Utc);
+
+    if date <= now {
+        match event.last_notified_at {
+            Some(notified_at) if notified_at >= date => None,
+            _ => Some(NotificationKind::Due),
+        }
+    } else {
+        match event.last_notified_at {
+            Some(notified_at) if notified_at >= date - advance => None,
+            _ => Some(NotificationKind::Upcoming),
         }
     }
 }
@@ -0,0 +1,482 @@
+use std::{env, fmt, fs, path::PathBuf, sync::Arc};
+
+use chrono::{
+    DateTime, Datelike, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone,
+};
+
+/// Where the IANA time zone database lives unless TZDIR says otherwise.
+const DEFAULT_ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// An IANA time zone such as "Europe/Berlin", read from the system's
+/// zoneinfo database. Implements `TimeZone`, so dates can be kept in the
+/// zone's wall-clock time across its DST changes.
+#[derive(Clone, Debug)]
+pub struct Zone(Arc<ZoneInfo>);
+
+#[derive(Debug)]
+struct ZoneInfo {
+    name: String,
+    /// Instants, in UTC seconds, at which the offset changes, with the
+    /// offset from then on.
+    transitions: Vec<(i64, FixedOffset)>,
+    /// Offset before the first transition.
+    initial: FixedOffset,
+    /// Rule for instants after the last transition.
+    rule: Option<Rule>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ZoneOffset {
+    zone: Zone,
+    offset: FixedOffset,
+}
+
+impl Zone {
+    pub fn load(name: &str) -> Result<Zone, String> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+            && name.split('/').all(|part| !part.is_empty());
+        if !valid {
+            return Err(format!("Unknown time zone '{}'", name));
+        }
+
+        let dir = env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ZONEINFO_DIR));
+
+        let data = match fs::read(dir.join(name)) {
+            Ok(data) => data,
+            Err(_) => return Err(format!("Unknown time zone '{}'", name)),
+        };
+
+        match parse_tzif(name, &data) {
+            Some(info) => Ok(Zone(Arc::new(info))),
+            None => Err(format!("Failed to read time zone '{}'", name)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    fn offset_at(&self, timestamp: i64) -> FixedOffset {
+        let info = &self.0;
+        match info.transitions.last() {
+            Some(&(last, offset)) if timestamp >= last => info
+                .rule
+                .as_ref()
+                .map_or(offset, |rule| rule.offset_at(timestamp)),
+            Some(_) => {
+                let i = info.transitions.partition_point(|&(at, _)| at <= timestamp);
+                match i {
+                    0 => info.initial,
+                    _ => info.transitions[i - 1].1,
+                }
+            }
+            None => info
+                .rule
+                .as_ref()
+                .map_or(info.initial, |rule| rule.offset_at(timestamp)),
+        }
+    }
+
+    fn zone_offset(&self, offset: FixedOffset) -> ZoneOffset {
+        ZoneOffset {
+            zone: self.clone(),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Offset for ZoneOffset {
+    fn fix(&self) -> FixedOffset {
+        self.offset
+    }
+}
+
+impl fmt::Display for ZoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.offset)
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = ZoneOffset;
+
+    fn from_offset(offset: &ZoneOffset) -> Self {
+        offset.zone.clone()
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<ZoneOffset> {
+        self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<ZoneOffset> {
+        let timestamp = local.and_utc().timestamp();
+
+        // A wall-clock time can only be read with the offsets in force
+        // around it; a day either side covers any real transition.
+        let mut offsets = [
+            self.offset_at(timestamp - SECS_PER_DAY),
+            self.offset_at(timestamp + SECS_PER_DAY),
+        ]
+        .into_iter()
+        .filter(|offset| self.offset_at(timestamp - i64::from(offset.local_minus_utc())) == *offset)
+        .collect::<Vec<_>>();
+
+        // The larger offset is the earlier instant.
+        offsets.sort_by_key(|offset| -offset.local_minus_utc());
+        offsets.dedup();
+
+        match offsets[..] {
+            [] => LocalResult::None,
+            [offset] => LocalResult::Single(self.zone_offset(offset)),
+            [earliest, latest, ..] => {
+                LocalResult::Ambiguous(self.zone_offset(earliest), self.zone_offset(latest))
+            }
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> ZoneOffset {
+        self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        self.zone_offset(self.offset_at(utc.and_utc().timestamp()))
+    }
+}
+
+/// A POSIX TZ rule like `CET-1CEST,M3.5.0,M10.5.0/3`, which zoneinfo files
+/// carry for instants past their last listed transition.
+#[derive(Debug)]
+struct Rule {
+    standard: FixedOffset,
+    dst: Option<Dst>,
+}
+
+#[derive(Debug)]
+struct Dst {
+    offset: FixedOffset,
+    /// Day and local standard time, in seconds, at which DST starts.
+    start: (RuleDay, i64),
+    /// Day and local DST time, in seconds, at which DST ends.
+    end: (RuleDay, i64),
+}
+
+#[derive(Debug)]
+enum RuleDay {
+    /// `Jn`: day 1 to 365, never counting 29 February.
+    Julian(u32),
+    /// `n`: day 0 to 365, counting 29 February.
+    Ordinal(u32),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` of month `m`, week 5
+    /// being the last.
+    MonthWeekDay(u32, u32, u32),
+}
+
+impl Rule {
+    fn offset_at(&self, timestamp: i64) -> FixedOffset {
+        let Some(dst) = &self.dst else {
+            return self.standard;
+        };
+
+        let local = timestamp + i64::from(self.standard.local_minus_utc());
+        let Some(year) = DateTime::from_timestamp(local, 0).map(|date| date.year()) else {
+            return self.standard;
+        };
+
+        let (Some(start), Some(end)) = (dst.start.0.date(year), dst.end.0.date(year)) else {
+            return self.standard;
+        };
+        let start = start.and_time(NaiveTime::MIN).and_utc().timestamp() + dst.start.1
+            - i64::from(self.standard.local_minus_utc());
+        let end = end.and_time(NaiveTime::MIN).and_utc().timestamp() + dst.end.1
+            - i64::from(dst.offset.local_minus_utc());
+
+        // In the southern hemisphere DST spans the new year.
+        let in_dst = if start < end {
+            (start..end).contains(&timestamp)
+        } else {
+            !(end..start).contains(&timestamp)
+        };
+
+        if in_dst {
+            dst.offset
+        } else {
+            self.standard
+        }
+    }
+}
+
+impl RuleDay {
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            RuleDay::Julian(day) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                NaiveDate::from_yo_opt(year, if leap && day >= 60 { day + 1 } else { day })
+            }
+            RuleDay::Ordinal(day) => NaiveDate::from_yo_opt(year, day + 1),
+            RuleDay::MonthWeekDay(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+        }
+    }
+}
+
+/// Reads a TZif file (RFC 8536), preferring the 64-bit data and footer of
+/// version 2 and later.
+fn parse_tzif(name: &str, data: &[u8]) -> Option<ZoneInfo> {
+    let mut reader = Reader { data, pos: 0 };
+
+    let mut header = reader.header()?;
+    if header.version != 0 {
+        reader.skip(header.data_len(4))?;
+        header = reader.header()?;
+    }
+    let time_size = if header.version == 0 { 4 } else { 8 };
+
+    let mut times = Vec::with_capacity(header.time_count);
+    for _ in 0..header.time_count {
+        times.push(match time_size {
+            4 => i64::from(reader.i32()?),
+            _ => reader.i64()?,
+        });
+    }
+
+    let indices = reader.take(header.time_count)?.to_vec();
+
+    let mut types = Vec::with_capacity(header.type_count);
+    for _ in 0..header.type_count {
+        types.push(FixedOffset::east_opt(reader.i32()?)?);
+        reader.skip(2)?; // is_dst and abbreviation index
+    }
+
+    reader.skip(
+        header.char_count
+            + header.leap_count * (time_size + 4)
+            + header.std_count
+            + header.ut_count,
+    )?;
+
+    let transitions = times
+        .into_iter()
+        .zip(indices)
+        .map(|(at, index)| Some((at, *types.get(usize::from(index))?)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let rule = match header.version {
+        0 => None,
+        _ => std::str::from_utf8(&data[reader.pos..])
+            .ok()
+            .map(|footer| footer.trim_matches('\n'))
+            .filter(|footer| !footer.is_empty())
+            .and_then(parse_rule),
+    };
+
+    Some(ZoneInfo {
+        name: name.to_string(),
+        transitions,
+        initial: *types.first()?,
+        rule,
+    })
+}
+
+struct Header {
+    version: u8,
+    ut_count: usize,
+    std_count: usize,
+    leap_count: usize,
+    time_count: usize,
+    type_count: usize,
+    char_count: usize,
+}
+
+impl Header {
+    fn data_len(&self, time_size: usize) -> usize {
+        self.time_count * (time_size + 1)
+            + self.type_count * 6
+            + self.char_count
+            + self.leap_count * (time_size + 4)
+            + self.std_count
+            + self.ut_count
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        usize::try_from(self.i32()?).ok()
+    }
+
+    fn header(&mut self) -> Option<Header> {
+        if self.take(4)? != b"TZif" {
+            return None;
+        }
+        let version = match self.take(1)?[0] {
+            0 => 0,
+            version => version - b'0',
+        };
+        self.skip(15)?;
+
+        Some(Header {
+            version,
+            ut_count: self.count()?,
+            std_count: self.count()?,
+            leap_count: self.count()?,
+            time_count: self.count()?,
+            type_count: self.count()?,
+            char_count: self.count()?,
+        })
+    }
+}
+
+fn parse_rule(tz: &str) -> Option<Rule> {
+    let mut rest = tz;
+
+    skip_name(&mut rest)?;
+    // POSIX offsets count hours west of UTC.
+    let standard = FixedOffset::west_opt(parse_time(&mut rest)? as i32)?;
+
+    if rest.is_empty() {
+        return Some(Rule {
+            standard,
+            dst: None,
+        });
+    }
+
+    skip_name(&mut rest)?;
+    let offset = match rest.starts_with(',') || rest.is_empty() {
+        true => FixedOffset::west_opt(-standard.local_minus_utc() - 3600)?,
+        false => FixedOffset::west_opt(parse_time(&mut rest)? as i32)?,
+    };
+
+    let (start, end) = match rest.strip_prefix(',') {
+        Some(rules) => {
+            rest = rules;
+            let start = parse_rule_day(&mut rest)?;
+            rest = rest.strip_prefix(',')?;
+            let end = parse_rule_day(&mut rest)?;
+            (start, end)
+        }
+        // The US rules, which POSIX falls back to.
+        None => (
+            (RuleDay::MonthWeekDay(3, 2, 0), 7200),
+            (RuleDay::MonthWeekDay(11, 1, 0), 7200),
+        ),
+    };
+
+    Some(Rule {
+        standard,
+        dst: Some(Dst { offset, start, end }),
+    })
+}
+
+/// Skips a zone abbreviation, either alphabetic or quoted as `<+0330>`.
+fn skip_name(rest: &mut &str) -> Option<()> {
+    let len = match rest.strip_prefix('<') {
+        Some(quoted) => quoted.find('>')? + 2,
+        None => rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len()),
+    };
+
+    if len < 3 {
+        return None;
+    }
+    *rest = &rest[len..];
+    Some(())
+}
+
+/// Parses `[+-]hh[:mm[:ss]]` into seconds.
+fn parse_time(rest: &mut &str) -> Option<i64> {
+    let sign = match rest.chars().next()? {
+        '-' => -1,
+        _ => 1,
+    };
+    let unsigned = rest.trim_start_matches(['+', '-']);
+
+    let len = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(unsigned.len());
+    let mut seconds = 0;
+    for (part, unit) in unsigned[..len].split(':').zip([3600, 60, 1]) {
+        seconds += part.parse::<i64>().ok()? * unit;
+    }
+
+    *rest = &unsigned[len..];
+    Some(sign * seconds)
+}
+
+/// Parses a `Jn`, `n` or `Mm.w.d` day followed by an optional `/time`,
+/// which defaults to 02:00.
+fn parse_rule_day(rest: &mut &str) -> Option<(RuleDay, i64)> {
+    let len = rest.find([',', '/']).unwrap_or(rest.len());
+    let spec = &rest[..len];
+    *rest = &rest[len..];
+
+    let day = if let Some(day) = spec.strip_prefix('J') {
+        RuleDay::Julian(day.parse().ok().filter(|day| (1..=365).contains(day))?)
+    } else if let Some(spec) = spec.strip_prefix('M') {
+        let mut parts = spec.split('.').map(str::parse::<u32>);
+        let (month, week, weekday) = (
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+        );
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        RuleDay::MonthWeekDay(month, week, weekday)
+    } else {
+        RuleDay::Ordinal(spec.parse().ok().filter(|day| *day <= 365)?)
+    };
+
+    let time = match rest.strip_prefix('/') {
+        Some(time) => {
+            *rest = time;
+            parse_time(rest)?
+        }
+        None => 7200,
+    };
+
+    Some((day, time))
+}
@@ -0,0 +1,72 @@
+//! Shared setup for the integration tests: migrated databases, a fixed
+//! instant to schedule fixtures around, and a clock pinned to it.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use notify_me::{
+    clock::ManualClock,
+    db,
+    repository::{EventRepository, NewEvent},
+};
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+/// A migrated in-memory database.
+pub fn open_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&mut conn).unwrap();
+    conn
+}
+
+/// A migrated database file that is removed on drop, for tests that need
+/// more than one connection to the same data.
+pub struct TempDb {
+    _dir: TempDir,
+    pub path: PathBuf,
+}
+
+impl TempDb {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notify_me.db");
+        db::open(&path).unwrap();
+
+        Self { _dir: dir, path }
+    }
+
+    /// A new connection, configured the way the binary opens one.
+    pub fn connect(&self) -> Connection {
+        db::open(&self.path).unwrap()
+    }
+}
+
+/// The minute fixtures are placed around.
+pub fn base() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2030, 3, 20, 9, 0, 0).unwrap()
+}
+
+/// 30 seconds into `base`'s minute, so the scheduler's rounding to whole
+/// minutes is exercised.
+pub fn now() -> DateTime<Utc> {
+    base() + Duration::seconds(30)
+}
+
+pub fn at_offset(minutes: i64) -> DateTime<Utc> {
+    base() + Duration::minutes(minutes)
+}
+
+/// A clock standing at `now`.
+pub fn clock() -> ManualClock {
+    ManualClock::new(now())
+}
+
+pub fn insert(conn: &Connection, event: NewEvent) -> i32 {
+    EventRepository::new(conn).insert(event).unwrap().id
+}
+
+/// A one-time event `minutes` from `base`.
+pub fn insert_at(conn: &Connection, name: &str, minutes: i64) -> i32 {
+    insert(conn, NewEvent::new(name, at_offset(minutes)))
+}
@@ -0,0 +1,30 @@
+use std::process::Command;
+
+#[test]
+fn bash_completions_name_the_subcommands() {
+    let output = Command::new(env!("CARGO_BIN_EXE_notify-me"))
+        .args(["completions", "bash"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("complete -o default -F _notify_me notify-me"));
+    for subcommand in [
+        "list",
+        "search",
+        "pause",
+        "export",
+        "calendars",
+        "db",
+        "completions",
+    ] {
+        assert!(
+            script.contains(&format!("\"notify-me,{}\")", subcommand)),
+            "{} missing",
+            subcommand
+        );
+    }
+    assert!(script.contains("\"notify-me__db,vacuum\")"));
+    assert!(script.contains("__complete-ids"));
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use notify_me::config::{Config, Setting, Source};
+use tempfile::TempDir;
+
+#[test]
+fn settings_record_where_they_came_from() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "db_path = \"/data/events.db\"\ntrash_retention_days = 30\n",
+    )
+    .unwrap();
+
+    let config = Config::load(Some(&path)).unwrap();
+    assert_eq!(config.path.as_deref(), Some(path.as_path()));
+    assert_eq!(config.source("db_path"), Source::File);
+    assert_eq!(config.source("backup_retention"), Source::Default);
+
+    let lines = config
+        .settings()
+        .iter()
+        .map(Setting::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "db_path = \"/data/events.db\"  # from config file",
+            "trash_retention_days = 30  # from config file",
+            "backup_dir = \"/data/backups\"  # default",
+            "# backup_retention is unset  # default",
+            "pre_op_backup_retention = 10  # default",
+            "# log_file is unset  # default",
+            "fail_on_empty_today = false  # default",
+        ]
+    );
+}
+
+#[test]
+fn example_config_loads_as_the_defaults() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    let example = notify_me::config::example();
+    fs::write(&path, &example).unwrap();
+
+    let config = Config::load(Some(&path)).unwrap();
+    let defaults = Config::default();
+    assert_eq!(config.db_path, defaults.db_path);
+    assert_eq!(config.backup_dir(), defaults.backup_dir());
+    assert_eq!(
+        config.pre_op_backup_retention,
+        defaults.pre_op_backup_retention
+    );
+    assert_eq!(config.trash_retention_days, None);
+
+    for setting in defaults.settings() {
+        assert!(
+            example.contains(&format!("\n{} = ", setting.key))
+                || example.contains(&format!("\n# {} = ", setting.key)),
+            "{}",
+            setting.key
+        );
+    }
+}
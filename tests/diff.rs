@@ -0,0 +1,95 @@
+use common::TempDb;
+use notify_me::{
+    client::Client,
+    db,
+    diff::EventChange,
+    json::Value,
+    repository::{EventRepository, NewEvent},
+};
+
+mod common;
+
+#[test]
+fn diff_matches_events_by_uuid_across_copies() {
+    let ours = TempDb::new();
+    let conn = ours.connect();
+    let shared = common::insert(&conn, NewEvent::new("Shared", common::at_offset(60)));
+    let renamed = common::insert(&conn, NewEvent::new("Dentist", common::at_offset(120)));
+    let trashed = common::insert(&conn, NewEvent::new("Trashed", common::at_offset(180)));
+
+    let dir = tempfile::tempdir().unwrap();
+    let copy = dir.path().join("copy.db");
+    conn.execute("VACUUM INTO ?1", [copy.to_str().unwrap()])
+        .unwrap();
+    let theirs = db::open(&copy).unwrap();
+
+    let mine = common::insert(&conn, NewEvent::new("Only here", common::at_offset(240)));
+    EventRepository::new(&conn).soft_delete(trashed).unwrap();
+    let theirs_only = common::insert(&theirs, NewEvent::new("Only there", common::at_offset(300)));
+    theirs
+        .execute(
+            "UPDATE events SET name = 'Dentist appointment' WHERE id = ?1",
+            [renamed],
+        )
+        .unwrap();
+    // Same id, different event: a copy that diverged before syncing.
+    assert_eq!(mine, theirs_only);
+
+    let diff = Client::new(&conn).diff_database(&theirs).unwrap();
+
+    let summary: Vec<_> = diff
+        .0
+        .iter()
+        .map(|change| match change {
+            EventChange::Ours(event) => format!("> {}", event.name),
+            EventChange::Theirs(event) => format!("< {}", event.name),
+            EventChange::Changed { ours, fields } => format!(
+                "~ {} {}",
+                ours.name,
+                fields
+                    .iter()
+                    .map(|(name, _, _)| name.as_str())
+                    // Depends on whether the edits fell in the same second.
+                    .filter(|name| *name != "updated_at")
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            "~ Dentist name",
+            "~ Trashed deleted_at",
+            "> Only here",
+            "< Only there",
+        ]
+    );
+    assert!(
+        !summary.iter().any(|line| line.contains("Shared")),
+        "{}",
+        shared
+    );
+
+    let EventChange::Changed { fields, .. } = &diff.0[0] else {
+        unreachable!()
+    };
+    assert_eq!(
+        fields[0],
+        (
+            "name".to_string(),
+            Value::from("Dentist"),
+            Value::from("Dentist appointment")
+        )
+    );
+
+    let output = console::strip_ansi_codes(&diff.to_string()).to_string();
+    assert!(
+        output.contains("    name: \"Dentist\" -> \"Dentist appointment\"\n"),
+        "{}",
+        output
+    );
+
+    let identical = Client::new(&theirs).diff_database(&theirs).unwrap();
+    assert!(identical.is_empty());
+}
@@ -0,0 +1,1586 @@
+use std::{env, fs, process::Command};
+
+use chrono::{DateTime, Datelike, Duration, Local, Months, TimeZone, Utc, Weekday};
+use common::open_db;
+use notify_me::{
+    backup::PreOpBackup,
+    client::{
+        parse_date, Client, ImportStats, MonthCalendar, OnConflict, OverdueEvents, PurgeTarget,
+        WeekCalendar,
+    },
+    clock::{Clock, ManualClock},
+    db,
+    error::NotifyMeError,
+    event::{
+        advance_date, advance_date_in, parse_recurrence, validate_event_message,
+        validate_event_name, EventBuilder, EventField, EventList, EventWithStatus, Priority,
+        RecurrencePattern, Status,
+    },
+    repository::{EventRepository, NewEvent},
+    scheduler::DEFAULT_ADVANCE_MINUTES,
+    zone::Zone,
+};
+
+mod common;
+
+fn save(client: &Client, name: &str, message: &str) -> i32 {
+    client
+        .save_event(NewEvent {
+            message: message.to_string(),
+            ..NewEvent::new(name, Utc::now() + Duration::days(1))
+        })
+        .unwrap()
+        .id
+}
+
+#[test]
+fn saved_event_is_listed() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let id = save(&client, "Dentist", "Bring the insurance card");
+
+    let event = client.get_event(id).unwrap();
+    assert_eq!(event.name, "Dentist");
+    assert_eq!(event.recurrence_pattern, RecurrencePattern::Once);
+
+    let events = client.list_events().unwrap();
+    assert_eq!(events.0.len(), 1);
+    assert_eq!(events.0[0].id, id);
+}
+
+#[test]
+fn unreadable_date_is_skipped() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let broken = save(&client, "Broken", "");
+    let kept = save(&client, "Kept", "");
+    conn.execute("UPDATE events SET date = 'garbage' WHERE id = ?1", [broken])
+        .unwrap();
+
+    let events = client.list_events().unwrap();
+    assert_eq!(events.0.iter().map(|e| e.id).collect::<Vec<_>>(), [kept]);
+
+    let skipped = db::unreadable_events(&conn).unwrap();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].id, broken);
+    assert!(skipped[0].error.contains("garbage"));
+}
+
+#[test]
+fn legacy_date_without_offset_is_local_time() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let id = save(&client, "Legacy", "");
+    conn.execute(
+        "UPDATE events SET date = '2030-03-20 09:00:00' WHERE id = ?1",
+        [id],
+    )
+    .unwrap();
+
+    let event = client.get_event(id).unwrap();
+    assert_eq!(
+        event.date,
+        Local.with_ymd_and_hms(2030, 3, 20, 9, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn full_text_search_matches_prefixes() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let dentist = save(&client, "Dentist", "Checkup downtown");
+    save(&client, "Bills", "Pay the electricity bill");
+
+    let events = client.full_text_search("dent*").unwrap();
+    assert_eq!(events.0.iter().map(|e| e.id).collect::<Vec<_>>(), [dentist]);
+
+    conn.execute(
+        "UPDATE events SET message = 'Moved uptown' WHERE id = ?1",
+        [dentist],
+    )
+    .unwrap();
+    assert!(client.full_text_search("downtown").unwrap().0.is_empty());
+    assert_eq!(client.full_text_search("uptown").unwrap().0.len(), 1);
+}
+
+#[test]
+fn near_duplicates_compare_message_prefix() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    save(&client, "Bills", "Pay the electricity bill before friday");
+
+    let exact = client
+        .find_near_duplicate_by_content("Pay the electricity bill before friday")
+        .unwrap();
+    assert_eq!(exact.len(), 1);
+
+    let prefix = client
+        .find_near_duplicate_by_content("Pay the electricity bill, second notice")
+        .unwrap();
+    assert_eq!(prefix.len(), 1);
+
+    let different = client
+        .find_near_duplicate_by_content("Water the plants")
+        .unwrap();
+    assert!(different.is_empty());
+}
+
+#[test]
+fn every_n_weeks_round_trips_and_advances() {
+    let pattern = parse_recurrence("every_n_weeks:3").unwrap();
+    assert_eq!(pattern, RecurrencePattern::EveryNWeeks(3));
+    assert_eq!(String::from(pattern), "every_n_weeks:3");
+    assert!(parse_recurrence("every_n_weeks:0").is_err());
+
+    let date = Local.with_ymd_and_hms(2030, 1, 1, 9, 0, 0).unwrap();
+    assert_eq!(
+        advance_date(date, &pattern),
+        Local.with_ymd_and_hms(2030, 1, 22, 9, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn daily_events_keep_their_wall_clock_time_across_dst() {
+    let new_york = Zone::load("America/New_York").unwrap();
+    let at = |month, day, hour, minute| {
+        new_york
+            .with_ymd_and_hms(2024, month, day, hour, minute, 0)
+            .unwrap()
+    };
+
+    // US clocks sprang forward at 02:00 on 2024-03-10: 23 hours later, but
+    // still 09:00 in New York.
+    let before = at(3, 9, 9, 0);
+    let after = advance_date(before.clone(), &RecurrencePattern::Daily);
+    assert_eq!(after.naive_local(), at(3, 10, 9, 0).naive_local());
+    assert_eq!(after.clone() - before.clone(), Duration::hours(23));
+    assert_eq!(
+        after.with_timezone(&Utc),
+        Utc.with_ymd_and_hms(2024, 3, 10, 13, 0, 0).unwrap()
+    );
+
+    // The same through advance_date_in, for an event stored in local time
+    // but keeping New York's.
+    let stored = before.with_timezone(&Local);
+    let next = advance_date_in(stored, &RecurrencePattern::Daily, Some(&new_york));
+    assert_eq!(
+        next.with_timezone(&new_york).naive_local(),
+        after.naive_local()
+    );
+
+    // 02:30 doesn't exist that day, so it is pushed past the gap.
+    let skipped = advance_date(at(3, 9, 2, 30), &RecurrencePattern::Daily);
+    assert_eq!(skipped.naive_local(), at(3, 10, 3, 30).naive_local());
+
+    // And back again on 2024-11-03, 25 hours later.
+    let before = at(11, 2, 9, 0);
+    let after = advance_date(before.clone(), &RecurrencePattern::Daily);
+    assert_eq!(after.naive_local(), at(11, 3, 9, 0).naive_local());
+    assert_eq!(after - before, Duration::hours(25));
+}
+
+#[test]
+fn nth_weekday_round_trips_and_rejects_bad_positions() {
+    let pattern = parse_recurrence("nth_weekday:2:tue").unwrap();
+    assert_eq!(
+        pattern,
+        RecurrencePattern::EveryNthWeekdayOfMonth(Weekday::Tue, 2)
+    );
+    assert_eq!(String::from(pattern), "nth_weekday:2:tue");
+    assert_eq!(pattern.to_string(), "every 2nd Tuesday of the month");
+
+    let last = parse_recurrence("NTH_WEEKDAY:5:Friday").unwrap();
+    assert_eq!(String::from(last), "nth_weekday:5:fri");
+    assert_eq!(last.to_string(), "every last Friday of the month");
+
+    for invalid in [
+        "nth_weekday:0:tue",
+        "nth_weekday:6:tue",
+        "nth_weekday:2:someday",
+        "nth_weekday:2",
+    ] {
+        assert!(parse_recurrence(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn nth_weekday_advances_to_each_position_of_the_next_month() {
+    // Tuesdays: February 2030 has four, April and December five, and
+    // January 2031 four, reached across the year end.
+    for (from, expected) in [
+        ((2030, 1, 8), [5, 12, 19, 26, 26]),
+        ((2030, 3, 12), [2, 9, 16, 23, 30]),
+        ((2030, 11, 26), [3, 10, 17, 24, 31]),
+        ((2030, 12, 31), [7, 14, 21, 28, 28]),
+    ] {
+        let date = Local
+            .with_ymd_and_hms(from.0, from.1, from.2, 9, 30, 0)
+            .unwrap();
+        let next_month = date
+            .date_naive()
+            .checked_add_months(Months::new(1))
+            .unwrap();
+
+        for (nth, day) in (1..=5).zip(expected) {
+            let pattern = RecurrencePattern::EveryNthWeekdayOfMonth(Weekday::Tue, nth);
+            let next = advance_date(date, &pattern);
+
+            assert_eq!(
+                next.date_naive(),
+                next_month.with_day(day).unwrap(),
+                "{} from {}",
+                pattern,
+                date
+            );
+            assert_eq!(next.time(), date.time());
+        }
+    }
+}
+
+#[test]
+fn nth_weekday_events_keep_their_position_month_to_month() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let id = common::insert(
+        &conn,
+        NewEvent {
+            recurrence: parse_recurrence("nth_weekday:1:mon").unwrap(),
+            ..NewEvent::new(
+                "Planning",
+                Local
+                    .with_ymd_and_hms(2030, 1, 7, 10, 0, 0)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            )
+        },
+    );
+
+    let days: Vec<_> = client
+        .event_timeline(id, 4)
+        .unwrap()
+        .iter()
+        .map(|date| date.date_naive().to_string())
+        .collect();
+    assert_eq!(
+        days,
+        ["2030-02-04", "2030-03-04", "2030-04-01", "2030-05-06"]
+    );
+}
+
+#[test]
+fn full_text_search_ranks_multi_word_matches() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let checkup = save(
+        &client,
+        "Dentist",
+        "Checkup downtown, bring the insurance card",
+    );
+    let card = save(&client, "Birthday", "Buy a card for Sam");
+    save(&client, "Bills", "Pay the electricity bill");
+
+    let ids = |query| {
+        client
+            .full_text_search(query)
+            .unwrap()
+            .0
+            .iter()
+            .map(|event| event.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("insurance card"), [checkup]);
+    assert_eq!(ids("card OR bill*").len(), 3);
+    assert_eq!(ids("card"), [card, checkup]);
+    assert!(ids("card electricity").is_empty());
+
+    let hits = client.search_with_snippets("dent* card").unwrap();
+    assert_eq!(hits.0.len(), 1);
+    assert!(hits.0[0].snippet.contains("\u{2}Dentist\u{3}"));
+}
+
+#[test]
+fn search_falls_back_to_like_without_the_fts_index() {
+    let conn = open_db();
+    conn.execute_batch(
+        "DROP TRIGGER events_fts_insert;
+         DROP TRIGGER events_fts_delete;
+         DROP TRIGGER events_fts_update;
+         DROP TABLE events_fts;",
+    )
+    .unwrap();
+    assert!(!db::has_search_index(&conn).unwrap());
+
+    let client = Client::new(&conn);
+    let dentist = save(&client, "Dentist", "Checkup downtown, 100% covered");
+    save(&client, "Bills", "Pay the electricity bill");
+
+    let ids = |query| {
+        client
+            .full_text_search(query)
+            .unwrap()
+            .0
+            .iter()
+            .map(|event| event.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("dent*"), [dentist]);
+    assert_eq!(ids("DOWNTOWN checkup"), [dentist]);
+    assert_eq!(ids("100%"), [dentist]);
+    assert!(ids("downtown bill").is_empty());
+    assert!(ids("10_%").is_empty());
+
+    let hits = client.search_with_snippets("dent* town").unwrap();
+    assert_eq!(
+        hits.0[0].snippet,
+        "Checkup down\u{2}town\u{3}, 100% covered"
+    );
+}
+
+#[test]
+fn event_created_with_an_id_keeps_it() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let date = Utc::now() + Duration::days(1);
+    let tags = ["work".to_string()];
+    save(&client, "Autoincremented", "");
+
+    client
+        .create_event_with_id(
+            42,
+            "Restored",
+            "From a backup",
+            RecurrencePattern::Weekly,
+            date,
+            &tags,
+        )
+        .unwrap();
+    let restored = client.get_event(42).unwrap();
+    assert_eq!(restored.id, 42);
+    assert_eq!(restored.name, "Restored");
+    assert_eq!(restored.message, "From a backup");
+    assert_eq!(restored.recurrence_pattern, RecurrencePattern::Weekly);
+    assert_eq!(restored.tags, tags);
+
+    // The same id again replaces the row rather than adding one, and the
+    // search index with it.
+    client
+        .create_event_with_id(42, "Replaced", "", RecurrencePattern::Once, date, &[])
+        .unwrap();
+    let replaced = client.get_event(42).unwrap();
+    assert_eq!(replaced.name, "Replaced");
+    assert_eq!(replaced.uuid, restored.uuid);
+    assert!(replaced.tags.is_empty());
+    assert!(client.full_text_search("backup").unwrap().0.is_empty());
+    assert!(client.full_text_search("Restored").unwrap().0.is_empty());
+    assert_eq!(client.full_text_search("Replaced").unwrap().0.len(), 1);
+    assert_eq!(client.list_events().unwrap().0.len(), 2);
+}
+
+#[test]
+fn event_ids_must_be_positive() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let date = Utc::now() + Duration::days(1);
+
+    for id in [0, -1, i32::MIN] {
+        let result =
+            client.create_event_with_id(id, "Invalid", "", RecurrencePattern::Once, date, &[]);
+        assert!(
+            matches!(&result, Err(NotifyMeError::Other(message)) if message == "Invalid ID"),
+            "{}: {:?}",
+            id,
+            result
+        );
+    }
+    assert!(client.list_events().unwrap().0.is_empty());
+}
+
+#[test]
+fn built_event_is_inserted() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let date = Local.with_ymd_and_hms(2030, 3, 20, 9, 0, 0).unwrap();
+
+    let event = EventBuilder::new()
+        .name("  Standup ")
+        .message("Daily sync")
+        .date(date)
+        .recurrence(RecurrencePattern::Daily)
+        .priority(Priority::High)
+        .tags(vec!["work".to_string()])
+        .calendar("work")
+        .build()
+        .unwrap();
+    assert_eq!(event.id, 0);
+    assert_eq!(event.name, "Standup");
+
+    let id = client.insert_event(event).unwrap();
+    let stored = client.get_event(id).unwrap();
+    assert_eq!(stored.name, "Standup");
+    assert_eq!(stored.message, "Daily sync");
+    assert_eq!(stored.date, date);
+    assert_eq!(stored.recurrence_pattern, RecurrencePattern::Daily);
+    assert_eq!(stored.priority, Priority::High);
+    assert_eq!(stored.tags, ["work"]);
+    assert_eq!(stored.calendar, "work");
+}
+
+#[test]
+fn event_lists_sort_by_date_then_id() {
+    let event = |id, day, hour| {
+        let mut event = EventBuilder::new()
+            .name(format!("Event {}", id))
+            .date(Local.with_ymd_and_hms(2030, 3, day, hour, 0, 0).unwrap())
+            .build()
+            .unwrap();
+        event.id = id;
+        event
+    };
+    let mut list = EventList(vec![
+        event(1, 22, 9),
+        event(5, 20, 14),
+        event(3, 21, 9),
+        event(4, 20, 9),
+        event(2, 20, 14),
+    ]);
+
+    list.0.sort();
+
+    let ids: Vec<_> = list.0.iter().map(|event| event.id).collect();
+    assert_eq!(ids, [4, 2, 5, 3, 1]);
+    assert_eq!(list.0.iter().max().unwrap().id, 1);
+}
+
+#[test]
+fn names_must_be_non_blank_and_short() {
+    assert_eq!(
+        validate_event_name(""),
+        Err("The event name can't be blank")
+    );
+    assert_eq!(
+        validate_event_name(" \t\n "),
+        Err("The event name can't be blank")
+    );
+    assert_eq!(
+        validate_event_name(&"a".repeat(256)),
+        Err("The event name can't be longer than 255 characters")
+    );
+    // Counted in characters, not bytes.
+    assert_eq!(validate_event_name(&"é".repeat(255)), Ok(()));
+    assert_eq!(validate_event_name(" Dentist "), Ok(()));
+}
+
+#[test]
+fn messages_may_be_blank_but_not_long() {
+    assert_eq!(validate_event_message(""), Ok(()));
+    assert_eq!(validate_event_message("   "), Ok(()));
+    assert_eq!(validate_event_message(&"é".repeat(1000)), Ok(()));
+    assert_eq!(
+        validate_event_message(&"a".repeat(1001)),
+        Err("The event message can't be longer than 1000 characters")
+    );
+}
+
+#[test]
+fn builder_requires_name_and_date() {
+    let date = Local::now();
+
+    assert!(EventBuilder::new().date(date).build().is_err());
+    assert!(EventBuilder::new().name("Event").build().is_err());
+    assert!(EventBuilder::new().name(" ").date(date).build().is_err());
+    assert!(EventBuilder::new().name("Event").date(date).build().is_ok());
+}
+
+#[test]
+fn reset_leaves_an_empty_current_schema() {
+    let mut conn = open_db();
+    let client = Client::new(&conn);
+    let id = save(&client, "Dentist", "Checkup downtown");
+    client
+        .set_calendar_muted(db::DEFAULT_CALENDAR, true)
+        .unwrap();
+    client.mark_done(id).unwrap();
+
+    db::reset(&mut conn).unwrap();
+
+    assert_eq!(db::user_version(&conn).unwrap(), db::schema_version());
+    let client = Client::new(&conn);
+    assert!(client.list_events().unwrap().0.is_empty());
+    assert!(client.list_calendars().unwrap().is_empty());
+    assert!(client.full_text_search("downtown").unwrap().0.is_empty());
+    save(&client, "Dentist", "Checkup downtown");
+    assert_eq!(client.full_text_search("downtown").unwrap().0.len(), 1);
+}
+
+#[test]
+fn month_calendar_lays_out_days_from_monday() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let at = |day, hour, minute| {
+        Local
+            .with_ymd_and_hms(2030, 3, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    for (name, date) in [
+        ("Rent", at(1, 0, 0)),
+        ("Dentist appointment", at(20, 9, 0)),
+        ("Gym", at(20, 18, 0)),
+        ("Late", at(31, 23, 59)),
+        ("Next month", at(31, 23, 59) + Duration::minutes(1)),
+        ("Last month", at(1, 0, 0) - Duration::minutes(1)),
+    ] {
+        client.save_event(NewEvent::new(name, date)).unwrap();
+    }
+
+    let events = client.fetch_events_in_month(2030, 3).unwrap();
+    let names: Vec<_> = events.0.iter().map(|event| event.name.as_str()).collect();
+    assert_eq!(names, ["Rent", "Dentist appointment", "Gym", "Late"]);
+
+    // March 2030 starts on a Friday.
+    let calendar = MonthCalendar {
+        year: 2030,
+        month: 3,
+        events,
+    }
+    .to_string();
+    let lines: Vec<_> = calendar.lines().collect();
+    assert_eq!(lines[0], "March 2030");
+    assert_eq!(
+        lines[1],
+        "Mon      Tue      Wed      Thu      Fri      Sat      Sun"
+    );
+    assert_eq!(lines[3], format!("{}1        2        3", " ".repeat(36)));
+    assert_eq!(lines[4], format!("{}Rent", " ".repeat(36)));
+    assert!(
+        calendar.contains(&format!("\n{}Dentist\n{0}Gym\n", " ".repeat(18))),
+        "{}",
+        calendar
+    );
+    assert!(
+        calendar.ends_with(&format!("31\n{}Late", " ".repeat(54))),
+        "{}",
+        calendar
+    );
+
+    // April 2030 starts on a Monday, and is left with a short last week.
+    let calendar = MonthCalendar {
+        year: 2030,
+        month: 4,
+        events: client.fetch_events_in_month(2030, 4).unwrap(),
+    }
+    .to_string();
+    assert_eq!(
+        calendar.lines().nth(3),
+        Some("1        2        3        4        5        6        7")
+    );
+    assert_eq!(calendar.lines().nth(4), Some("Next mon"));
+    assert!(calendar.ends_with("\n29       30"), "{}", calendar);
+
+    // September 2030 starts on a Sunday.
+    let calendar = MonthCalendar {
+        year: 2030,
+        month: 9,
+        events: client.fetch_events_in_month(2030, 9).unwrap(),
+    }
+    .to_string();
+    assert_eq!(
+        calendar.lines().nth(3),
+        Some(format!("{}1", " ".repeat(54)).as_str())
+    );
+
+    assert!(client.fetch_events_in_month(2030, 13).is_err());
+}
+
+#[test]
+fn next_event_skips_past_paused_and_deleted_events() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    assert!(client.next_event().unwrap().is_none());
+
+    let insert = |name, minutes| common::insert_at(&conn, name, minutes);
+    insert("Past", -5);
+    let paused = insert("Paused", 5);
+    let deleted = insert("Deleted", 10);
+    let next = insert("Next", 15);
+    insert("Later", 20);
+    client.set_paused(paused, true).unwrap();
+    EventRepository::new(&conn).soft_delete(deleted).unwrap();
+
+    assert_eq!(
+        client.next_event().unwrap().map(|event| event.id),
+        Some(next)
+    );
+}
+
+#[test]
+fn adjacent_events_follow_the_calendar_and_skip_paused_ones() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn)
+        .with_clock(&clock)
+        .with_calendar(Some("work".to_string()));
+    let insert = |name: &str, minutes, calendar: &str| {
+        common::insert(
+            &conn,
+            NewEvent {
+                calendar: Some(calendar.to_string()),
+                ..NewEvent::new(name, common::at_offset(minutes))
+            },
+        )
+    };
+    let previous = insert("Standup", -30, "work");
+    let paused_before = insert("Paused before", -10, "work");
+    insert("Home before", -5, "home");
+    let now = insert("Now", 0, "work");
+    insert("Home after", 5, "home");
+    let paused_after = insert("Paused after", 10, "work");
+    let next = insert("Review", 30, "work");
+    client.set_paused(paused_before, true).unwrap();
+    client.set_paused(paused_after, true).unwrap();
+
+    let adjacent = |at: DateTime<Utc>, after| {
+        client
+            .fetch_adjacent_event(at.with_timezone(&Local), after)
+            .unwrap()
+            .map(|event| event.id)
+    };
+    assert_eq!(adjacent(common::now(), true), Some(next));
+    assert_eq!(adjacent(common::now(), false), Some(now));
+    assert_eq!(adjacent(common::at_offset(-1), false), Some(previous));
+    assert_eq!(adjacent(common::at_offset(31), true), None);
+}
+
+#[test]
+fn overdue_events_are_unnotified_past_one_time_events() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let insert = |event| common::insert(&conn, event);
+    let hour = insert(NewEvent::new("Hour", common::at_offset(-60)));
+    let days = insert(NewEvent::new("Days", common::at_offset(-2 * 24 * 60 - 5)));
+    insert(NewEvent::new("Upcoming", common::at_offset(5)));
+    insert(NewEvent {
+        recurrence: RecurrencePattern::Daily,
+        ..NewEvent::new("Daily", common::at_offset(-60))
+    });
+    let notified = insert(NewEvent::new("Notified", common::at_offset(-30)));
+    let deleted = insert(NewEvent::new("Deleted", common::at_offset(-30)));
+    let events = EventRepository::new(&conn);
+    events
+        .mark_notified(notified, common::at_offset(-30))
+        .unwrap();
+    events.soft_delete(deleted).unwrap();
+
+    let overdue = client.fetch_overdue_events().unwrap();
+    let ids: Vec<_> = overdue.0.iter().map(|event| event.id).collect();
+    assert_eq!(ids, [days, hour]);
+
+    let text = OverdueEvents {
+        events: overdue,
+        now: common::now().into(),
+    }
+    .to_string();
+    assert!(text.contains("2 days overdue"), "{}", text);
+    assert!(text.contains("1 hour overdue"), "{}", text);
+
+    client.acknowledge(&[days]).unwrap();
+    let ids: Vec<_> = client
+        .fetch_overdue_events()
+        .unwrap()
+        .0
+        .iter()
+        .map(|event| event.id)
+        .collect();
+    assert_eq!(ids, [hour]);
+}
+
+#[test]
+fn created_event_shows_in_today_view() {
+    let conn = open_db();
+    let day = Local.with_ymd_and_hms(2030, 3, 20, 12, 0, 0).unwrap();
+    let clock = ManualClock::new(day.with_timezone(&Utc));
+    let client = Client::new(&conn).with_clock(&clock);
+    let today = client
+        .save_event(NewEvent::new("Today", (day + Duration::hours(3)).into()))
+        .unwrap()
+        .id;
+    let tomorrow = client
+        .save_event(NewEvent::new("Tomorrow", (day + Duration::days(1)).into()))
+        .unwrap()
+        .id;
+
+    let grouped = client.fetch_events_grouped_by_proximity().unwrap();
+    let ids = |events: &EventList| events.0.iter().map(|e| e.id).collect::<Vec<_>>();
+    assert_eq!(ids(&grouped.today), [today]);
+    assert_eq!(ids(&grouped.tomorrow), [tomorrow]);
+    assert_eq!(client.count_events_today().unwrap(), 1);
+}
+
+#[test]
+fn soft_deleted_events_leave_listings_for_the_trash() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let kept = common::insert_at(&conn, "Kept", 60);
+    let deleted = common::insert_at(&conn, "Deleted", 60);
+
+    EventRepository::new(&conn).soft_delete(deleted).unwrap();
+
+    let listed = client.list_events().unwrap();
+    assert_eq!(listed.0.iter().map(|e| e.id).collect::<Vec<_>>(), [kept]);
+    assert!(client.get_event(deleted).is_err());
+    let trash = client.fetch_deleted_events(10).unwrap();
+    assert_eq!(trash.0.iter().map(|e| e.id).collect::<Vec<_>>(), [deleted]);
+
+    client.restore_event(deleted).unwrap();
+    assert_eq!(client.list_events().unwrap().0.len(), 2);
+}
+
+#[test]
+fn purge_backs_up_only_once_there_is_something_to_remove() {
+    let conn = open_db();
+    let dir = tempfile::tempdir().unwrap();
+    let snapshots = dir.path().join("pre-op");
+    let client = Client::new(&conn).with_pre_op_backup(Some(PreOpBackup {
+        dir: snapshots.clone(),
+        retention: 10,
+    }));
+    let live = common::insert_at(&conn, "Live", 60);
+
+    assert_eq!(client.purge(PurgeTarget::AllDeleted, true).unwrap(), 0);
+    assert!(client.purge(PurgeTarget::Event(live), true).is_err());
+    assert!(!snapshots.exists());
+
+    EventRepository::new(&conn).soft_delete(live).unwrap();
+    assert_eq!(client.purge(PurgeTarget::AllDeleted, true).unwrap(), 1);
+    let taken = fs::read_dir(&snapshots)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(taken.len(), 1);
+    assert!(taken[0].ends_with("-purge.db"), "{:?}", taken);
+}
+
+#[test]
+fn purge_refuses_events_outside_the_trash() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let live = common::insert_at(&conn, "Live", 60);
+
+    let err = client.purge(PurgeTarget::Event(live), true).unwrap_err();
+    assert!(err.to_string().contains("is not deleted"), "{}", err);
+    assert!(matches!(
+        client.purge(PurgeTarget::Event(live + 1), true),
+        Err(NotifyMeError::NotFound(_))
+    ));
+    assert_eq!(client.list_events().unwrap().0.len(), 1);
+}
+
+#[test]
+fn purge_older_than_leaves_recent_trash() {
+    let conn = open_db();
+    let clock = common::clock();
+    let events = EventRepository::new(&conn).with_clock(&clock);
+    let old = common::insert_at(&conn, "Old", 60);
+    let recent = common::insert_at(&conn, "Recent", 60);
+    let live = common::insert_at(&conn, "Live", 60);
+
+    events.soft_delete(old).unwrap();
+    clock.advance(Duration::days(20));
+    events.soft_delete(recent).unwrap();
+    clock.advance(Duration::days(20));
+
+    let client = Client::new(&conn).with_clock(&clock);
+    assert_eq!(
+        client
+            .purge(PurgeTarget::OlderThan(Duration::days(30)), true)
+            .unwrap(),
+        1
+    );
+    let trash = client.fetch_deleted_events(10).unwrap();
+    assert_eq!(trash.0.iter().map(|e| e.id).collect::<Vec<_>>(), [recent]);
+    assert_eq!(client.get_event(live).unwrap().id, live);
+}
+
+/// The status `fetch_all_events_unfiltered` gives event `id` at `now`.
+fn status_of(conn: &rusqlite::Connection, id: i32) -> EventWithStatus {
+    let clock = common::clock();
+    Client::new(conn)
+        .with_clock(&clock)
+        .fetch_all_events_unfiltered()
+        .unwrap()
+        .into_iter()
+        .find(|event| event.event.id == id)
+        .unwrap()
+}
+
+#[test]
+fn future_events_are_active() {
+    let conn = open_db();
+    let id = common::insert_at(&conn, "Upcoming", 60);
+
+    let event = status_of(&conn, id);
+    assert_eq!(event.status, Status::Active);
+    assert!(event.to_string().starts_with("[ ] "), "{}", event);
+}
+
+#[test]
+fn past_events_still_live_are_past() {
+    let conn = open_db();
+    let id = common::insert_at(&conn, "Overdue", -60);
+
+    let event = status_of(&conn, id);
+    assert_eq!(event.status, Status::Past);
+    assert!(event.to_string().starts_with("[\u{23f8}] "), "{}", event);
+}
+
+#[test]
+fn trashed_events_are_deleted_whatever_their_date() {
+    let conn = open_db();
+    let id = common::insert_at(&conn, "Unwanted", 60);
+    EventRepository::new(&conn).soft_delete(id).unwrap();
+
+    let event = status_of(&conn, id);
+    assert_eq!(event.status, Status::Deleted);
+    assert!(event.to_string().starts_with("[\u{2717}] "), "{}", event);
+}
+
+#[test]
+fn events_marked_done_are_completed_rather_than_deleted() {
+    let conn = open_db();
+    let id = common::insert_at(&conn, "Finished", -60);
+    Client::new(&conn).mark_done(id).unwrap();
+
+    let event = status_of(&conn, id);
+    assert_eq!(event.status, Status::Completed);
+    assert!(event.to_string().starts_with("[\u{2713}] "), "{}", event);
+    assert!(event
+        .to_json()
+        .to_string()
+        .contains("\"status\":\"completed\""));
+}
+
+#[test]
+fn update_event_field_sets_each_field_and_moves_updated_at() {
+    let conn = open_db();
+    let clock = common::clock();
+    let id = EventRepository::new(&conn)
+        .with_clock(&clock)
+        .insert(NewEvent::new("Dentist", common::at_offset(60)))
+        .unwrap()
+        .id;
+    let client = Client::new(&conn).with_clock(&clock);
+    let update = |field, value| {
+        clock.advance(Duration::minutes(1));
+        client.update_event_field(id, field, value).unwrap();
+        let event = client.get_event(id).unwrap();
+        assert_eq!(event.updated_at, clock.now_utc(), "{:?}", field);
+        event
+    };
+
+    assert_eq!(
+        update(EventField::Name, "  Orthodontist ").name,
+        "Orthodontist"
+    );
+    assert_eq!(
+        update(EventField::Message, "Bring forms").message,
+        "Bring forms"
+    );
+    assert_eq!(
+        update(EventField::Date, "2030-04-01T08:30:00+02:00").date,
+        Utc.with_ymd_and_hms(2030, 4, 1, 6, 30, 0).unwrap()
+    );
+    assert_eq!(
+        update(EventField::RecurrencePattern, "weekly").recurrence_pattern,
+        RecurrencePattern::Weekly
+    );
+    assert_eq!(
+        update(EventField::Priority, "HIGH").priority,
+        Priority::High
+    );
+    assert_eq!(
+        update(EventField::Location, "Main St").location.as_deref(),
+        Some("Main St")
+    );
+    assert_eq!(update(EventField::Location, " ").location, None);
+    assert_eq!(
+        update(EventField::Url, "https://example.com/visit")
+            .url
+            .as_deref(),
+        Some("https://example.com/visit")
+    );
+    assert_eq!(update(EventField::Url, "").url, None);
+    assert_eq!(
+        update(EventField::Color, "#FF8800").color.as_deref(),
+        Some("#ff8800")
+    );
+    assert_eq!(update(EventField::Color, "").color, None);
+    assert_eq!(
+        update(EventField::Tags, "health, teeth").tags,
+        ["health", "teeth"]
+    );
+    assert!(update(EventField::Tags, "").tags.is_empty());
+}
+
+#[test]
+fn update_event_field_rejects_invalid_values() {
+    let conn = open_db();
+    let clock = common::clock();
+    let id = EventRepository::new(&conn)
+        .with_clock(&clock)
+        .insert(NewEvent::new("Dentist", common::at_offset(60)))
+        .unwrap()
+        .id;
+    let client = Client::new(&conn).with_clock(&clock);
+    let before = client.get_event(id).unwrap();
+    clock.advance(Duration::minutes(1));
+
+    let long_message = "x".repeat(10_000);
+    for (field, value) in [
+        (EventField::Name, "  "),
+        (EventField::Message, long_message.as_str()),
+        (EventField::Date, "tomorrow"),
+        (EventField::RecurrencePattern, "fortnightly-ish"),
+        (EventField::Priority, "urgent"),
+        (EventField::Url, "example.com"),
+        (EventField::Color, "mauve"),
+    ] {
+        assert!(
+            client.update_event_field(id, field, value).is_err(),
+            "{:?} = {:?}",
+            field,
+            value
+        );
+    }
+    assert!(matches!(
+        client.update_event_field(id + 1, EventField::Name, "Other"),
+        Err(NotifyMeError::NotFound(_))
+    ));
+
+    let after = client.get_event(id).unwrap();
+    assert_eq!(after.updated_at, before.updated_at);
+    assert_eq!(
+        (after.name, after.message, after.date),
+        (before.name, before.message, before.date)
+    );
+}
+
+#[test]
+fn single_event_ics_has_the_event_and_an_advance_alarm() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let id = common::insert(
+        &conn,
+        NewEvent {
+            message: "Bring forms, ID\nand card".to_string(),
+            recurrence: RecurrencePattern::Weekly,
+            priority: Priority::High,
+            tags: vec!["health".to_string()],
+            ..NewEvent::new("Dentist", common::at_offset(0))
+        },
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dentist.ics");
+
+    client.export_ics_single(id, &path).unwrap();
+
+    let ics = fs::read_to_string(&path).unwrap();
+    assert!(ics.ends_with("END:VCALENDAR\r\n"), "{}", ics);
+    let lines = ics.split("\r\n").collect::<Vec<_>>();
+    let event = &lines[lines.iter().position(|l| *l == "BEGIN:VEVENT").unwrap()..];
+    let uuid = client.get_event(id).unwrap().uuid;
+    for expected in [
+        format!("UID:{}@notify-me", uuid),
+        "DTSTART:20300320T090000Z".to_string(),
+        "SUMMARY:Dentist".to_string(),
+        "DESCRIPTION:Bring forms\\, ID\\nand card".to_string(),
+        "CATEGORIES:health".to_string(),
+        "PRIORITY:1".to_string(),
+        "RRULE:FREQ=WEEKLY".to_string(),
+    ] {
+        assert!(
+            event.contains(&expected.as_str()),
+            "{} in {}",
+            expected,
+            ics
+        );
+    }
+
+    let alarm = lines.iter().position(|l| *l == "BEGIN:VALARM").unwrap();
+    assert_eq!(
+        lines[alarm..alarm + 5],
+        [
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "DESCRIPTION:Dentist",
+            format!("TRIGGER:-PT{}M", DEFAULT_ADVANCE_MINUTES).as_str(),
+            "END:VALARM",
+        ]
+    );
+    assert_eq!(lines[alarm + 5], "END:VEVENT");
+}
+
+#[test]
+fn single_event_ics_reports_the_path_it_could_not_write() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let id = common::insert_at(&conn, "Dentist", 0);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("missing").join("dentist.ics");
+
+    let err = client.export_ics_single(id, &path).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with(&format!("{}: ", path.display())),
+        "{}",
+        err
+    );
+    assert!(matches!(
+        client.export_ics_single(id + 1, &dir.path().join("none.ics")),
+        Err(NotifyMeError::NotFound(_))
+    ));
+}
+
+/// Imports a JSON document listing `records`.
+fn import_json(
+    client: &Client,
+    records: &str,
+    on_conflict: OnConflict,
+    dry_run: bool,
+) -> Result<ImportStats, NotifyMeError> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.json");
+    fs::write(
+        &path,
+        format!("{{\"schema_version\": 1, \"events\": [{}]}}", records),
+    )
+    .unwrap();
+
+    client.import_json(&path, on_conflict, dry_run)
+}
+
+/// Names and messages of the listed events.
+fn listed(client: &Client) -> Vec<(String, String)> {
+    client
+        .list_events()
+        .unwrap()
+        .0
+        .into_iter()
+        .map(|event| (event.name, event.message))
+        .collect()
+}
+
+const DENTIST_JSON: &str =
+    r#"{"name": "Dentist", "message": "Imported", "date": "2030-03-20T09:00:00Z"}"#;
+const GYM_JSON: &str = r#"{"name": "Gym", "date": "2030-03-21T18:00:00Z"}"#;
+
+#[test]
+fn json_import_rolls_back_on_a_bad_record() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let records = format!(r#"{}, {}, {{"name": "Undated"}}"#, DENTIST_JSON, GYM_JSON);
+
+    let err = import_json(&client, &records, OnConflict::Skip, false).unwrap_err();
+    assert_eq!(err.to_string(), "Record 2: 'date' is missing");
+    assert!(listed(&client).is_empty());
+}
+
+#[test]
+fn json_import_dry_run_leaves_the_database_alone() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let records = format!("{}, {}", DENTIST_JSON, GYM_JSON);
+
+    let stats = import_json(&client, &records, OnConflict::Skip, true).unwrap();
+    assert_eq!(stats.inserted, 2);
+    assert!(listed(&client).is_empty());
+
+    let stats = import_json(&client, &records, OnConflict::Skip, false).unwrap();
+    assert_eq!(stats.inserted, 2);
+    assert_eq!(listed(&client).len(), 2);
+}
+
+#[test]
+fn json_import_skips_overwrites_or_duplicates_matching_events() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    common::insert(
+        &conn,
+        NewEvent {
+            message: "Local".to_string(),
+            ..NewEvent::new("Dentist", common::base())
+        },
+    );
+    let records = format!("{}, {}", DENTIST_JSON, GYM_JSON);
+    let dentist = |message: &str| ("Dentist".to_string(), message.to_string());
+    let gym = ("Gym".to_string(), String::new());
+
+    let stats = import_json(&client, &records, OnConflict::Skip, false).unwrap();
+    assert_eq!(
+        (stats.inserted, stats.skipped, stats.overwritten),
+        (1, 1, 0)
+    );
+    assert_eq!(listed(&client), [dentist("Local"), gym.clone()]);
+
+    let stats = import_json(&client, DENTIST_JSON, OnConflict::Overwrite, false).unwrap();
+    assert_eq!(
+        (stats.inserted, stats.skipped, stats.overwritten),
+        (0, 0, 1)
+    );
+    assert_eq!(listed(&client), [dentist("Imported"), gym.clone()]);
+
+    let stats = import_json(&client, DENTIST_JSON, OnConflict::Duplicate, false).unwrap();
+    assert_eq!(
+        (stats.inserted, stats.skipped, stats.overwritten),
+        (1, 0, 0)
+    );
+    assert_eq!(
+        listed(&client),
+        [dentist("Imported"), dentist("Imported"), gym]
+    );
+}
+
+#[test]
+fn parse_date_rejects_times_dst_repeats_or_skips() {
+    let new_york = |input: &str| parse_date(&format!("{} America/New_York", input), None);
+
+    let (date, _) = new_york("03/11/2030 00:30").unwrap();
+    assert_eq!(date, Utc.with_ymd_and_hms(2030, 11, 3, 4, 30, 0).unwrap());
+
+    let err = new_york("03/11/2030 01:30").unwrap_err().to_string();
+    assert!(err.contains("Ambiguous local time"), "{}", err);
+    assert!(err.contains("-04:00") && err.contains("-05:00"), "{}", err);
+
+    let err = new_york("10/03/2030 02:30").unwrap_err().to_string();
+    assert!(err.contains("doesn't exist"), "{}", err);
+    assert!(new_york("10/03/2030 03:30").is_ok());
+}
+
+#[test]
+fn due_soon_covers_the_next_minutes() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let ids = [5, 30, 60].map(|offset| common::insert_at(&conn, "Event", offset));
+    common::insert_at(&conn, "Past", -1);
+
+    let soon = client.fetch_events_due_soon(30).unwrap();
+    assert_eq!(soon.0.iter().map(|e| e.id).collect::<Vec<_>>(), ids[..2]);
+
+    let soon = client.fetch_events_due_soon(60).unwrap();
+    assert_eq!(soon.0.iter().map(|e| e.id).collect::<Vec<_>>(), ids);
+    assert!(client.fetch_events_due_soon(1).unwrap().0.is_empty());
+}
+
+#[test]
+fn bulk_update_changes_only_matching_live_events() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let once = [30, 60].map(|offset| common::insert_at(&conn, "Meeting", offset));
+    let daily = common::insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Standup", common::at_offset(30))
+        },
+    );
+    let deleted = common::insert_at(&conn, "Deleted", 30);
+    EventRepository::new(&conn).soft_delete(deleted).unwrap();
+
+    let (from, to) = (RecurrencePattern::Once, RecurrencePattern::Weekly);
+    assert_eq!(client.count_events_with_recurrence(from).unwrap(), 2);
+    assert_eq!(client.bulk_update_recurrence(from, to).unwrap(), 2);
+
+    for id in once {
+        let event = client.get_event(id).unwrap();
+        assert_eq!(event.recurrence_pattern, to);
+        assert_eq!(event.updated_at, common::now());
+    }
+    let recurrence = |id| {
+        client
+            .get_event_including_deleted(id)
+            .unwrap()
+            .recurrence_pattern
+    };
+    assert_eq!(recurrence(daily), RecurrencePattern::Daily);
+    assert_eq!(recurrence(deleted), from);
+    assert_eq!(client.count_events_with_recurrence(from).unwrap(), 0);
+}
+
+#[test]
+fn next_n_events_are_the_nearest_upcoming() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let ids = |events: EventList| events.0.iter().map(|e| e.id).collect::<Vec<_>>();
+
+    common::insert_at(&conn, "Past", -5);
+    assert!(client.fetch_next_n_events(3).unwrap().0.is_empty());
+
+    let later = common::insert_at(&conn, "Later", 60 * 24 * 365);
+    let soon = common::insert_at(&conn, "Soon", 5);
+    let sooner = common::insert_at(&conn, "Sooner", 1);
+    assert_eq!(
+        ids(client.fetch_next_n_events(3).unwrap()),
+        [sooner, soon, later]
+    );
+
+    common::insert_at(&conn, "Latest", 60 * 24 * 400);
+    assert_eq!(
+        ids(client.fetch_next_n_events(3).unwrap()),
+        [sooner, soon, later]
+    );
+    assert!(client.fetch_next_n_events(0).unwrap().0.is_empty());
+}
+
+#[test]
+fn csv_import_reads_past_malformed_rows() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.csv");
+    fs::write(
+        &path,
+        "date,name,notes,recurrence_pattern,tags\n\
+         2030-03-20T09:00:00Z,Standup,ignored,daily,\"work, daily\"\n\
+         2030-03-21T09:00:00Z,\"Bad\"quote,,once,\n\
+         \"Multi\nline\",Unterminated,,\n\
+         not a date,Broken,,once,\n\
+         2030-03-22T09:00:00Z,Bad pattern,,fortnightly,\n\
+         \n\
+         22/03/2030 10:30,Dentist,,,\n\
+         2030-03-20T09:00:00Z,Standup,,daily,\n",
+    )
+    .unwrap();
+
+    let stats = client.import_csv(&path, true).unwrap();
+    assert_eq!((stats.inserted, stats.skipped, stats.failed), (2, 1, 4));
+
+    let events = client.list_events().unwrap().0;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "Standup");
+    assert_eq!(events[0].recurrence_pattern, RecurrencePattern::Daily);
+    assert_eq!(events[0].tags, ["daily", "work"]);
+    assert_eq!(events[1].name, "Dentist");
+    assert_eq!(
+        events[1].date,
+        Local.with_ymd_and_hms(2030, 3, 22, 10, 30, 0).unwrap()
+    );
+    assert_eq!(events[1].recurrence_pattern, RecurrencePattern::Once);
+
+    fs::write(&path, "Lunch,,weekly,2030-03-23T12:00:00Z\n").unwrap();
+    assert!(client.import_csv(&path, true).is_err());
+    assert_eq!(client.import_csv(&path, false).unwrap().inserted, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_script_export_is_valid_sh_that_passes_text_through() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let name = "Bob's \"party\"";
+    let message = "Costs $5, `date` or $(date)\nBring 'snacks'; \\ and\t* ?";
+    save(&client, name, message);
+    save(&client, "Plain", "");
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("events.sh");
+    client.export_shell_script(&script).unwrap();
+
+    let check = Command::new("sh").arg("-n").arg(&script).output().unwrap();
+    assert!(check.status.success(), "{:?}", check);
+
+    // Stands in for the binary, printing its arguments as it got them.
+    let stub = dir.path().join(env!("CARGO_PKG_NAME"));
+    fs::write(
+        &stub,
+        "#!/bin/sh\nfor arg; do printf '%s\\0' \"$arg\"; done\n",
+    )
+    .unwrap();
+    fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", dir.path().display(), env::var("PATH").unwrap());
+    let run = Command::new("sh")
+        .arg(&script)
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert!(run.status.success(), "{:?}", run);
+
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    let args: Vec<_> = stdout.split('\0').collect();
+    let value = |flag| {
+        let mut values = args.iter().skip_while(|arg| **arg != flag).skip(1);
+        values.next().copied()
+    };
+    assert_eq!(value("--name"), Some(name));
+    assert_eq!(value("--message"), Some(message));
+    assert_eq!(args.iter().filter(|arg| **arg == "--create").count(), 2);
+}
+
+#[test]
+fn tags_are_listed_most_used_first() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    assert!(client.list_all_tags().unwrap().is_empty());
+
+    let tagged = |name: &str, tags: &[&str]| {
+        client
+            .save_event(NewEvent {
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..NewEvent::new(name, Utc::now() + Duration::days(1))
+            })
+            .unwrap()
+            .id
+    };
+    tagged("Standup", &["work", "daily"]);
+    tagged("Review", &["work"]);
+    tagged("Gym", &["health", "daily"]);
+    tagged("Dentist", &["health"]);
+    tagged("Payday", &["work", "money"]);
+    tagged("Untagged", &[]);
+    let trashed = tagged("Trashed", &["money", "trash"]);
+    EventRepository::new(&conn).soft_delete(trashed).unwrap();
+
+    assert_eq!(
+        client.list_all_tags().unwrap(),
+        [
+            ("work".to_string(), 3),
+            ("daily".to_string(), 2),
+            ("health".to_string(), 2),
+            ("money".to_string(), 1),
+        ]
+    );
+}
+
+#[test]
+fn count_by_recurrence_groups_live_events() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    assert!(client.count_by_recurrence().unwrap().is_empty());
+
+    let insert = |name: &str, recurrence| {
+        common::insert(
+            &conn,
+            NewEvent {
+                recurrence,
+                ..NewEvent::new(name, common::base())
+            },
+        )
+    };
+    insert("Standup", RecurrencePattern::Daily);
+    insert("Review", RecurrencePattern::Weekly);
+    insert("Retro", RecurrencePattern::EveryNWeeks(2));
+    insert("Planning", RecurrencePattern::EveryNWeeks(2));
+    insert("Dentist", RecurrencePattern::Once);
+    let deleted = insert("Gym", RecurrencePattern::Daily);
+    EventRepository::new(&conn).soft_delete(deleted).unwrap();
+
+    let counts = client.count_by_recurrence().unwrap();
+    assert_eq!(counts.len(), 4);
+    assert_eq!(counts["daily"], 1);
+    assert_eq!(counts["weekly"], 1);
+    assert_eq!(counts["every_n_weeks:2"], 2);
+    assert_eq!(counts["once"], 1);
+}
+
+#[test]
+fn stats_count_the_coming_week_and_busiest_weekday() {
+    let conn = open_db();
+    let clock = common::clock();
+    let client = Client::new(&conn).with_clock(&clock);
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.total, 0);
+    assert!(stats.busiest_weekday().is_none());
+
+    let today = common::now().with_timezone(&Local).date_naive();
+    let at = |days| {
+        Local
+            .from_local_datetime(
+                &(today + Duration::days(days))
+                    .and_hms_opt(12, 0, 0)
+                    .unwrap(),
+            )
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    for days in [0, 0, 2, 7] {
+        common::insert(&conn, NewEvent::new("Event", at(days)));
+    }
+    let deleted = common::insert(&conn, NewEvent::new("Deleted", at(1)));
+    EventRepository::new(&conn).soft_delete(deleted).unwrap();
+
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.total, 4);
+    assert_eq!(stats.deleted, 1);
+    assert_eq!(stats.by_recurrence, [("once".to_string(), 4)]);
+    let week: Vec<_> = stats.next_days.iter().map(|(_, count)| *count).collect();
+    assert_eq!(week, [2, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(stats.next_days[0].0, today);
+    // 2030-03-20 is a Wednesday; the event a week on lands there too.
+    assert_eq!(stats.busiest_weekday(), Some((Weekday::Wed, 3)));
+}
+
+#[test]
+fn monthly_timeline_on_the_31st_clamps_to_month_ends() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let start = Local.with_ymd_and_hms(2030, 1, 31, 9, 0, 0).unwrap();
+    let id = common::insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Monthly,
+            ..NewEvent::new("Rent", start.with_timezone(&Utc))
+        },
+    );
+
+    let timeline = client.event_timeline(id, 12).unwrap();
+
+    // Once clamped to February's 28th, the event stays on the 28th.
+    let expected: Vec<_> = (2..=12)
+        .map(|month| (2030, month))
+        .chain([(2031, 1)])
+        .map(|(year, month)| Local.with_ymd_and_hms(year, month, 28, 9, 0, 0).unwrap())
+        .collect();
+    assert_eq!(timeline, expected);
+
+    let once = common::insert(&conn, NewEvent::new("Once", start.with_timezone(&Utc)));
+    assert!(client.event_timeline(once, 5).unwrap().is_empty());
+    assert_eq!(client.get_event(id).unwrap().date, start);
+}
+
+#[test]
+fn tag_intersection_requires_every_tag() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let insert = |name: &str, tags: &[&str]| {
+        client
+            .save_event(NewEvent {
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..NewEvent::new(name, Utc::now() + Duration::days(1))
+            })
+            .unwrap();
+    };
+    insert("Release", &["work", "urgent", "q3"]);
+    insert("Review", &["Work", "urgent"]);
+    insert("Standup", &["work"]);
+    insert("Groceries", &[]);
+
+    let names = |tags: &[&str]| {
+        let mut names: Vec<_> = client
+            .fetch_events_by_tag_intersection(tags)
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|event| event.name)
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(names(&[]), ["Groceries", "Release", "Review", "Standup"]);
+    assert_eq!(names(&["WORK"]), ["Release", "Review", "Standup"]);
+    assert_eq!(names(&["work", "urgent"]), ["Release", "Review"]);
+    assert_eq!(names(&["work", "urgent", "q3"]), ["Release"]);
+    assert_eq!(names(&["work", "Work"]), ["Release", "Review", "Standup"]);
+    assert!(names(&["work", "home"]).is_empty());
+
+    let any: Vec<_> = client
+        .with_tags(&["q3".to_string(), "URGENT".to_string()], true)
+        .list_events()
+        .unwrap()
+        .0
+        .into_iter()
+        .map(|event| event.name)
+        .collect();
+    assert_eq!(any, ["Release", "Review"]);
+}
+
+#[test]
+fn week_calendar_expands_occurrences_into_hour_rows() {
+    let conn = open_db();
+    let client = Client::new(&conn);
+    let at = |day, hour, minute| {
+        Local
+            .with_ymd_and_hms(2030, 3, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    for (name, date, recurrence) in [
+        ("Standup", at(1, 9, 15), RecurrencePattern::Daily),
+        ("Swimming", at(6, 7, 0), RecurrencePattern::Weekly),
+        (
+            "Dentist appointment",
+            at(20, 14, 30),
+            RecurrencePattern::Once,
+        ),
+        ("Last week", at(17, 23, 59), RecurrencePattern::Once),
+        ("Next week", at(25, 0, 0), RecurrencePattern::Once),
+    ] {
+        client
+            .save_event(NewEvent {
+                recurrence,
+                ..NewEvent::new(name, date)
+            })
+            .unwrap();
+    }
+
+    // 2030-03-18 is a Monday.
+    let start = Local
+        .with_ymd_and_hms(2030, 3, 18, 0, 0, 0)
+        .unwrap()
+        .date_naive();
+    let events = client.fetch_events_in_week(start).unwrap();
+    assert_eq!(events.0.len(), 9);
+    assert_eq!(events.0[0].name, "Standup");
+    assert_eq!(
+        events.0[0].date,
+        Local.with_ymd_and_hms(2030, 3, 18, 9, 15, 0).unwrap()
+    );
+
+    let calendar = |width| {
+        WeekCalendar {
+            start,
+            events: EventList(events.0.clone()),
+            width,
+        }
+        .to_string()
+    };
+
+    assert_eq!(
+        calendar(82),
+        [
+            "Week 12, 18 Mar to 24 Mar 2030",
+            "      Mon 18     Tue 19     Wed 20     Thu 21     Fri 22     Sat 23     Sun 24",
+            "07:00                       :00 Swimmi",
+            "08:00",
+            "09:00 :15 Standu :15 Standu :15 Standu :15 Standu :15 Standu :15 Standu :15 Standu",
+            "10:00",
+            "11:00",
+            "12:00",
+            "13:00",
+            "14:00                       :30 Dentis",
+            "15:00",
+            "16:00",
+            "17:00",
+            "18:00",
+        ]
+        .join("\n")
+    );
+
+    let narrow = calendar(60);
+    assert!(
+        narrow.starts_with(
+            "Week 12, 18 Mar to 24 Mar 2030\n\
+             Mon 18/03\n  09:15 Standup\n\
+             Tue 19/03\n  09:15 Standup\n\
+             Wed 20/03\n  07:00 Swimming\n  09:15 Standup\n  14:30 Dentist appointment\n"
+        ),
+        "{}",
+        narrow
+    );
+    assert!(narrow.ends_with("Sun 24/03\n  09:15 Standup"), "{}", narrow);
+}
@@ -0,0 +1,417 @@
+use std::{fs, io, process::Command};
+
+use notify_me::{db, error::NotifyMeError};
+use rusqlite::{Connection, OpenFlags};
+
+fn notify_me() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_notify-me"))
+}
+
+#[test]
+fn exits_with_the_documented_codes() {
+    let output = notify_me().arg("--no-such-flag").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--no-such-flag"));
+
+    let output = notify_me().arg("--help").output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exit status:"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\n",
+            dir.path().join("missing").join("notify_me.db").display()
+        ),
+    )
+    .unwrap();
+
+    let output = notify_me()
+        .arg("--config")
+        .arg(&config)
+        .arg("list")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Error: Cannot open database"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("doesn't exist"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn failed_migrations_exit_with_the_database_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("notify_me.db");
+    // Left by something else: the first migration keeps it, a later one
+    // can't index the columns it lacks.
+    rusqlite::Connection::open(&db_path)
+        .unwrap()
+        .execute_batch("CREATE TABLE events (x INTEGER)")
+        .unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(&config, format!("db_path = \"{}\"\n", db_path.display())).unwrap();
+
+    for flag in ["--migrate-db", "list"] {
+        let output = notify_me()
+            .arg("--config")
+            .arg(&config)
+            .arg(flag)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("Error: Migration "), "{}", stderr);
+        assert!(stderr.contains(" failed: "), "{}", stderr);
+    }
+}
+
+#[test]
+fn read_only_databases_fail_the_write_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("notify_me.db");
+    db::open(&db_path).unwrap();
+
+    let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+    let err = db::check_db_writable(&conn).unwrap_err();
+    assert!(matches!(err, NotifyMeError::Database(_)), "{:?}", err);
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn read_only_database_files_exit_with_the_database_code() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("notify_me.db");
+    db::open(&db_path).unwrap();
+    fs::set_permissions(&db_path, fs::Permissions::from_mode(0o444)).unwrap();
+    if fs::OpenOptions::new().write(true).open(&db_path).is_ok() {
+        // Permissions don't bind root, so there is nothing to check.
+        return;
+    }
+    let config = dir.path().join("config.toml");
+    fs::write(&config, format!("db_path = \"{}\"\n", db_path.display())).unwrap();
+
+    let run = |extra: &[&str]| {
+        notify_me()
+            .arg("--config")
+            .arg(&config)
+            .args(extra)
+            .arg("list")
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Error: Cannot write to database"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("is read-only"), "{}", stderr);
+    assert!(stderr.contains("--skip-db-check"), "{}", stderr);
+
+    assert_eq!(run(&["--skip-db-check"]).status.code(), Some(0));
+}
+
+#[test]
+fn import_takes_only_known_conflict_modes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\n",
+            dir.path().join("notify_me.db").display()
+        ),
+    )
+    .unwrap();
+    let json = dir.path().join("events.json");
+    fs::write(
+        &json,
+        r#"{"schema_version": 1, "events": [{"name": "Gym", "date": "2030-03-21T18:00:00Z"}]}"#,
+    )
+    .unwrap();
+    let import = |mode: &str| {
+        notify_me()
+            .arg("--config")
+            .arg(&config)
+            .args(["import", "--on-conflict", mode, "--json"])
+            .arg(&json)
+            .output()
+            .unwrap()
+    };
+
+    for mode in ["skip", "overwrite", "duplicate"] {
+        let output = import(mode);
+        assert_eq!(output.status.code(), Some(0), "{}: {:?}", mode, output);
+    }
+
+    let output = import("replace");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("[possible values: skip, overwrite, duplicate]"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn interrupted_prompts_cancel_rather_than_fail() {
+    let interrupted = dialoguer::Error::IO(io::Error::from(io::ErrorKind::Interrupted));
+    assert!(matches!(
+        NotifyMeError::from(interrupted),
+        NotifyMeError::Cancelled
+    ));
+
+    let closed = dialoguer::Error::IO(io::Error::from(io::ErrorKind::UnexpectedEof));
+    assert!(matches!(NotifyMeError::from(closed), NotifyMeError::Io(_)));
+}
+
+#[test]
+fn quiet_prints_only_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\n",
+            dir.path().join("notify_me.db").display()
+        ),
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = notify_me()
+            .arg("--config")
+            .arg(&config)
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&["list", "--quiet"]), "");
+    assert_eq!(run(&["list"]), "No events\n");
+
+    let create = [
+        "--create",
+        "--name",
+        "Dentist",
+        "--date",
+        "20/03/2030 09:00",
+    ];
+    assert_eq!(run(&[&["-q"], &create[..]].concat()), "1\n");
+    assert_eq!(run(&create), "Created event 2: Dentist\n");
+    assert!(run(&["list", "-q"]).contains("Event: Dentist"));
+
+    let output = notify_me()
+        .args(["--quiet", "--verbose", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn no_color_and_the_flag_leave_out_escape_sequences() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\n",
+            dir.path().join("notify_me.db").display()
+        ),
+    )
+    .unwrap();
+    // Colors forced on, as if writing to a terminal, unless turned off.
+    let run = |no_color: Option<&str>, args: &[&str]| {
+        let mut command = notify_me();
+        command
+            .env("CLICOLOR_FORCE", "1")
+            .env_remove("NO_COLOR")
+            .arg("--config")
+            .arg(&config)
+            .args(args);
+        if let Some(value) = no_color {
+            command.env("NO_COLOR", value);
+        }
+        let output = command.output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{:?}", output);
+        [output.stdout, output.stderr].concat()
+    };
+    let colored = |output: &[u8]| String::from_utf8_lossy(output).contains("\x1b[");
+
+    run(
+        None,
+        &[
+            "--create",
+            "--name",
+            "Dentist",
+            "--date",
+            "20/03/2030 09:00",
+            "--color",
+            "red",
+            "--priority",
+            "high",
+        ],
+    );
+    for args in [&["list"][..], &["--ansi-test"]] {
+        assert!(colored(&run(None, args)), "{:?}", args);
+        assert!(colored(&run(Some(""), args)), "{:?}", args);
+        assert!(!colored(&run(Some("1"), args)), "{:?}", args);
+        let flagged = [&["--no-color"], args].concat();
+        assert!(!colored(&run(None, &flagged)), "{:?}", args);
+    }
+}
+
+#[test]
+fn version_reports_the_build() {
+    let output = notify_me().arg("version").output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with(&format!("notify-me {} (", env!("CARGO_PKG_VERSION"))),
+        "{}",
+        stdout
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    let db_path = dir.path().join("notify_me.db");
+    fs::write(&config, format!("db_path = \"{}\"\n", db_path.display())).unwrap();
+
+    let output = notify_me()
+        .args(["--config", config.to_str().unwrap(), "version", "--verbose"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\ncommit: "), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("\nsqlite: {}\n", rusqlite::version())),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("(not created yet, "), "{}", stdout);
+    assert!(stdout.contains("\nfeatures: "), "{}", stdout);
+    assert!(!db_path.exists());
+}
+
+#[test]
+fn migrate_db_reports_what_it_applied() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\n",
+            dir.path().join("notify_me.db").display()
+        ),
+    )
+    .unwrap();
+    let run = |flag: &str| {
+        let output = notify_me()
+            .arg("--config")
+            .arg(&config)
+            .arg(flag)
+            .output()
+            .unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let (code, stdout) = run("--dry-run-migrate");
+    assert_eq!(code, Some(0));
+    assert!(
+        stdout.starts_with("Would apply migration 1: "),
+        "{}",
+        stdout
+    );
+
+    let (code, stdout) = run("--migrate-db");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout.lines().count(),
+        stdout.matches("Applied migration ").count()
+    );
+    assert!(stdout.starts_with("Applied migration 1: "), "{}", stdout);
+
+    assert_eq!(
+        run("--migrate-db"),
+        (Some(2), "No migrations pending.\n".to_string())
+    );
+    assert_eq!(
+        run("--dry-run-migrate"),
+        (Some(2), "No migrations pending.\n".to_string())
+    );
+}
+
+#[test]
+fn piped_without_a_subcommand_prints_today() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    let db_path = dir.path().join("notify_me.db");
+    fs::write(&config, format!("db_path = \"{}\"\n", db_path.display())).unwrap();
+
+    let output = notify_me().arg("--config").arg(&config).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "No events today\n"
+    );
+
+    fs::write(
+        &config,
+        format!(
+            "db_path = \"{}\"\nfail_on_empty_today = true\n",
+            db_path.display()
+        ),
+    )
+    .unwrap();
+    let output = notify_me().arg("--config").arg(&config).output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: no events\n"
+    );
+}
+
+#[test]
+fn count_fails_if_empty_only_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    let db_path = dir.path().join("notify_me.db");
+    fs::write(&config, format!("db_path = \"{}\"\n", db_path.display())).unwrap();
+    let count = |args: &[&str]| {
+        notify_me()
+            .arg("--config")
+            .arg(&config)
+            .arg("count")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = count(&["--today"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n");
+
+    let output = count(&["--recurrence", "weekly", "--fail-if-empty"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n");
+}
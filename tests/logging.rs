@@ -0,0 +1,53 @@
+use std::{fs, process::Command};
+
+use chrono::Utc;
+use notify_me::repository::NewEvent;
+
+mod common;
+
+use common::TempDb;
+
+#[test]
+fn log_file_gets_event_fields_at_info_level() {
+    let db = TempDb::new();
+    let conn = db.connect();
+    let id = common::insert(&conn, NewEvent::new("Dentist", Utc::now()));
+    // Paused, so checking logs the event without showing a notification.
+    conn.execute("UPDATE events SET paused = 1 WHERE id = ?1", [id])
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(&config, format!("db_path = \"{}\"\n", db.path.display())).unwrap();
+    let log = dir.path().join("logs").join("notify_me.log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_notify-me"))
+        .env_remove("RUST_LOG")
+        .arg("--config")
+        .arg(&config)
+        .arg("--log-file")
+        .arg(&log)
+        .arg("--force-check")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    // stderr stays at the default error level.
+    assert!(output.stderr.is_empty(), "{:?}", output);
+
+    let lines = fs::read_to_string(&log).unwrap();
+    let line = lines
+        .lines()
+        .find(|line| line.contains("Paused, not notifying"))
+        .unwrap_or_else(|| panic!("{}", lines));
+    assert!(
+        line.contains(" INFO  notify_me::scheduler] event{"),
+        "{}",
+        line
+    );
+    assert!(
+        line.contains(&format!("id={} name=Dentist", id)),
+        "{}",
+        line
+    );
+    assert!(line.contains("occurrence="), "{}", line);
+}
@@ -0,0 +1,44 @@
+use std::process::Command;
+
+#[test]
+fn man_page_documents_the_command_line() {
+    let output = Command::new(env!("CARGO_BIN_EXE_notify-me"))
+        .arg("man")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.starts_with(".TH NOTIFY\\-ME 1\n"));
+    for section in [
+        "NAME",
+        "SYNOPSIS",
+        "OPTIONS",
+        "COMMANDS",
+        "ENVIRONMENT",
+        "FILES",
+    ] {
+        assert!(
+            page.contains(&format!(".SH {}\n", section)),
+            "{} missing",
+            section
+        );
+    }
+    assert!(page.contains(".SS \"notify\\-me calendars rename\""));
+    assert!(page.contains("\\fB\\-\\-list\\-config\\fR"));
+    assert!(page.contains(".B NOTIFY_ME_DB_KEY"));
+    assert!(page.contains("pre_op_backup_retention = 10"));
+    assert!(!page.contains("__complete"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_notify-me"))
+        .arg("man")
+        .arg(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("notify-me.1")).unwrap(),
+        page
+    );
+}
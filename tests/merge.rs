@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use chrono::Duration;
+use common::TempDb;
+use notify_me::{
+    backup::{self, Prefer},
+    client::Client,
+    db::{self, to_sql_date},
+    event::{Event, RecurrencePattern},
+    repository::NewEvent,
+};
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+mod common;
+
+/// Every event in `conn`, deleted ones included, by id.
+fn events(conn: &Connection) -> Vec<Event> {
+    let mut events: Vec<_> = Client::new(conn)
+        .fetch_all_events_unfiltered()
+        .unwrap()
+        .into_iter()
+        .map(|event| event.event)
+        .collect();
+    events.sort_by_key(|event| event.id);
+    events
+}
+
+#[test]
+fn merging_a_disjoint_database_copies_every_event() {
+    let ours = TempDb::new();
+    let conn = ours.connect();
+    common::insert(&conn, NewEvent::new("Ours", common::at_offset(60)));
+
+    let theirs = TempDb::new();
+    {
+        let other = theirs.connect();
+        common::insert(&other, NewEvent::new("Theirs", common::at_offset(120)));
+        common::insert(
+            &other,
+            NewEvent {
+                tags: vec!["gym".to_string(), "health".to_string()],
+                ..NewEvent::new("Tagged", common::at_offset(180))
+            },
+        );
+    }
+    let their_uuids: Vec<_> = events(&theirs.connect())
+        .into_iter()
+        .map(|event| event.uuid)
+        .collect();
+
+    let stats = backup::merge_database(&conn, &theirs.path, Prefer::Local).unwrap();
+    assert_eq!((stats.inserted, stats.updated, stats.skipped), (2, 0, 0));
+    assert!(stats.conflicts.is_empty());
+
+    let merged = events(&conn);
+    let names: Vec<_> = merged.iter().map(|event| event.name.as_str()).collect();
+    assert_eq!(names, ["Ours", "Theirs", "Tagged"]);
+    // Their ids collide with ours; the uuids carry over.
+    assert_eq!(
+        merged[1..].iter().map(|e| &e.uuid).collect::<Vec<_>>(),
+        their_uuids.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(merged[2].tags, ["gym", "health"]);
+
+    let again = backup::merge_database(&conn, &theirs.path, Prefer::Local).unwrap();
+    assert_eq!((again.inserted, again.updated, again.skipped), (0, 0, 2));
+}
+
+/// Our database, and a copy of it taken before both sides went on
+/// separately: each renamed `Edited`, and only the copy's scheduler
+/// advanced `Advanced` to its next week. `Unchanged` is left alone.
+fn diverged() -> (TempDb, TempDir, PathBuf) {
+    let ours = TempDb::new();
+    let conn = ours.connect();
+    common::insert_at(&conn, "Edited", 60);
+    common::insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Weekly,
+            ..NewEvent::new("Advanced", common::at_offset(-30))
+        },
+    );
+    common::insert_at(&conn, "Unchanged", 120);
+
+    let dir = tempfile::tempdir().unwrap();
+    let copy = dir.path().join("copy.db");
+    conn.execute("VACUUM INTO ?1", [copy.to_str().unwrap()])
+        .unwrap();
+    let theirs = db::open(&copy).unwrap();
+
+    let rename = |conn: &Connection, name: &str, days: i64| {
+        conn.execute(
+            "UPDATE events SET name = ?1, updated_at = ?2 WHERE name = 'Edited'",
+            (name, to_sql_date(&(common::base() + Duration::days(days)))),
+        )
+        .unwrap();
+    };
+    rename(&conn, "Our name", 1);
+    rename(&theirs, "Their name", 2);
+    theirs
+        .execute(
+            "UPDATE events SET date = ?1, last_notified_at = ?2 WHERE name = 'Advanced'",
+            (
+                to_sql_date(&(common::at_offset(-30) + Duration::weeks(1))),
+                to_sql_date(&common::at_offset(-30)),
+            ),
+        )
+        .unwrap();
+
+    (ours, dir, copy)
+}
+
+#[test]
+fn merging_by_uuid_resolves_edits_by_preference() {
+    for (prefer, kept, name, updated) in [
+        (Prefer::Local, "local", "Our name", 1),
+        (Prefer::Other, "other", "Their name", 2),
+        (Prefer::Newest, "other", "Their name", 2),
+    ] {
+        let (ours, _dir, copy) = diverged();
+        let conn = ours.connect();
+        let uuid = events(&conn)[0].uuid.clone();
+
+        let stats = backup::merge_database(&conn, &copy, prefer).unwrap();
+        assert_eq!(
+            stats.conflicts,
+            [format!("Our name ({}): kept {}", uuid, kept)],
+            "{:?}",
+            prefer
+        );
+        assert_eq!(
+            (stats.inserted, stats.updated, stats.skipped),
+            (0, updated, 3 - updated),
+            "{:?}",
+            prefer
+        );
+
+        let merged = events(&conn);
+        assert_eq!(merged.len(), 3, "{:?}", prefer);
+        assert_eq!(merged[0].name, name, "{:?}", prefer);
+        // Whichever side wins, the advanced occurrence isn't shown again.
+        assert_eq!(
+            merged[1].date,
+            common::at_offset(-30) + Duration::weeks(1),
+            "{:?}",
+            prefer
+        );
+        assert_eq!(merged[2].name, "Unchanged", "{:?}", prefer);
+    }
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "metrics")]
+
+use chrono::Utc;
+use notify_me::{event::Priority, metrics, repository::NewEvent};
+
+mod common;
+
+#[test]
+fn renders_event_counts_and_scheduler_health() {
+    let conn = common::open_db();
+    common::insert_at(&conn, "Dentist", 60);
+    common::insert_at(&conn, "Standup", 90);
+    let urgent = common::insert(
+        &conn,
+        NewEvent {
+            priority: Priority::High,
+            ..NewEvent::new("Taxes", common::at_offset(120))
+        },
+    );
+    conn.execute(
+        "UPDATE events SET deleted_at = ?1 WHERE id = ?2",
+        (notify_me::db::to_sql_date(&Utc::now()), urgent),
+    )
+    .unwrap();
+    metrics::notification_fired();
+    metrics::tick_finished(common::base());
+
+    let text = metrics::render(&conn).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert!(
+        lines.contains(&"# TYPE notify_me_events_total gauge"),
+        "{}",
+        text
+    );
+    assert!(
+        lines.contains(&"notify_me_events_total{recurrence=\"once\",priority=\"normal\"} 2"),
+        "{}",
+        text
+    );
+    assert!(!text.contains("priority=\"high\""), "{}", text);
+    assert!(
+        lines.contains(&"notify_me_notifications_fired_total 1"),
+        "{}",
+        text
+    );
+    assert!(
+        lines.contains(&"notify_me_notifications_failed_total 0"),
+        "{}",
+        text
+    );
+    assert!(
+        lines.contains(
+            &format!(
+                "notify_me_scheduler_last_tick_seconds {}",
+                common::base().timestamp()
+            )
+            .as_str()
+        ),
+        "{}",
+        text
+    );
+    let size = lines
+        .iter()
+        .find_map(|line| line.strip_prefix("notify_me_db_size_bytes "))
+        .unwrap();
+    assert!(size.parse::<u64>().unwrap() > 0, "{}", text);
+}
@@ -0,0 +1,183 @@
+use std::{fs, path::Path, process::Command};
+
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+use common::{open_db, TempDb};
+use notify_me::{
+    client::Client,
+    event::{Event, RecurrencePattern},
+    repository::{EventFilter, EventRepository, NewEvent},
+};
+
+mod common;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/reminders.rem");
+
+fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .unwrap()
+}
+
+fn named<'a>(events: &'a [Event], name: &str) -> Vec<&'a Event> {
+    events.iter().filter(|event| event.name == name).collect()
+}
+
+#[test]
+fn import_maps_rem_at_through_and_omit() {
+    let conn = open_db();
+    let stats = Client::new(&conn)
+        .import_remind(Path::new(FIXTURE))
+        .unwrap();
+    // The four unsupported directives, and the omitted conference day.
+    assert_eq!((stats.inserted, stats.skipped, stats.failed), (8, 5, 0));
+
+    let (events, _) = EventRepository::new(&conn)
+        .list(&EventFilter::default())
+        .unwrap();
+
+    let dentist = named(&events, "Dentist, bring the insurance card");
+    assert_eq!(dentist.len(), 1);
+    assert_eq!(dentist[0].message, "Dentist, bring the insurance card");
+    assert_eq!(dentist[0].date, local(2030, 3, 15, 9, 30));
+    assert_eq!(dentist[0].recurrence_pattern, RecurrencePattern::Once);
+
+    // Without AT, at the start of the working day.
+    let passport = named(&events, "Renew the passport");
+    assert_eq!(passport[0].date, local(2030, 3, 20, 9, 0));
+
+    let dinner = named(&events, "Team dinner");
+    assert_eq!(dinner[0].date, local(2030, 3, 16, 18, 0));
+    assert_eq!(dinner[0].recurrence_pattern, RecurrencePattern::Weekly);
+
+    // A bare day of the month repeats monthly.
+    let rent = named(&events, "Pay 100% of the rent");
+    assert_eq!(rent[0].recurrence_pattern, RecurrencePattern::Monthly);
+    assert_eq!((rent[0].date.day(), rent[0].date.hour()), (5, 10));
+
+    let breakfast = named(&events, "Christmas breakfast");
+    assert_eq!(breakfast[0].date, local(2030, 12, 25, 8, 0));
+
+    // THROUGH becomes one event a day, leaving out the OMIT.
+    let conference: Vec<_> = named(&events, "Conference")
+        .iter()
+        .map(|event| (event.date, event.recurrence_pattern))
+        .collect();
+    assert_eq!(
+        conference,
+        [1, 2, 4].map(|day| (local(2030, 4, day, 7, 15), RecurrencePattern::Once))
+    );
+
+    assert!(named(&events, "Inside a conditional").is_empty());
+    assert!(named(&events, "Unsupported clause").is_empty());
+}
+
+#[test]
+fn import_warns_about_each_unsupported_directive() {
+    let db = TempDb::new();
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(&config, format!("db_path = \"{}\"\n", db.path.display())).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_notify-me"))
+        .env("RUST_LOG", "warn")
+        .arg("--config")
+        .arg(&config)
+        .arg("--import-remind")
+        .arg(FIXTURE)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warnings: Vec<_> = stderr
+        .lines()
+        .filter(|line| line.contains(" WARN "))
+        .map(|line| line.split("] ").nth(1).unwrap())
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "line 3: unsupported directive 'SET', skipping",
+            "line 16: conditional blocks are not supported, skipping",
+            "line 19: unsupported directive 'FSET', skipping",
+            "line 20: unsupported date token 'SATISFY', skipping",
+        ]
+    );
+}
+
+#[test]
+fn export_reads_back_through_import() {
+    let conn = open_db();
+    let date = |day, hour| local(2030, 3, day, hour, 30).to_utc();
+    let events = [
+        (
+            "Dentist",
+            "Bring 100% of the paperwork",
+            RecurrencePattern::Once,
+            date(15, 9),
+        ),
+        ("Standup", "", RecurrencePattern::Daily, date(18, 10)),
+        (
+            "Dinner",
+            "Team dinner",
+            RecurrencePattern::Weekly,
+            date(16, 18),
+        ),
+        (
+            "Bins",
+            "Take the bins out",
+            RecurrencePattern::EveryNWeeks(2),
+            date(19, 7),
+        ),
+        (
+            "Rent",
+            "Pay the rent",
+            RecurrencePattern::Monthly,
+            date(5, 10),
+        ),
+        (
+            "Book club",
+            "",
+            RecurrencePattern::EveryNthWeekdayOfMonth(chrono::Weekday::Tue, 2),
+            date(12, 19),
+        ),
+    ];
+    for (name, message, recurrence, date) in events {
+        common::insert(
+            &conn,
+            NewEvent {
+                message: message.to_string(),
+                recurrence,
+                ..NewEvent::new(name, date)
+            },
+        );
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("exported.rem");
+    Client::new(&conn).export_remind(&path).unwrap();
+
+    let imported = open_db();
+    let stats = Client::new(&imported).import_remind(&path).unwrap();
+    // The nth weekday form is remind's, but not one import reads.
+    assert_eq!((stats.inserted, stats.skipped, stats.failed), (5, 1, 0));
+
+    let (read_back, _) = EventRepository::new(&imported)
+        .list(&EventFilter::default())
+        .unwrap();
+    for (name, message, recurrence, date) in &events[..5] {
+        let text = if message.is_empty() { name } else { message };
+        let event = named(&read_back, text);
+        assert_eq!(event.len(), 1, "{}", text);
+        assert_eq!(event[0].message, *text);
+        assert_eq!(event[0].recurrence_pattern, *recurrence, "{}", text);
+        if *recurrence == RecurrencePattern::Monthly {
+            // Moved to the next 5th from today.
+            assert_eq!(
+                (event[0].date.day(), event[0].date.time()),
+                (5, date.with_timezone(&Local).time())
+            );
+        } else {
+            assert_eq!(event[0].date, *date, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use chrono::{Duration, Local, TimeZone, Utc};
+use common::open_db;
+use notify_me::{
+    clock::Clock,
+    db,
+    error::NotifyMeError,
+    event::RecurrencePattern,
+    repository::{EventFilter, EventRepository, NewEvent},
+};
+
+mod common;
+
+#[test]
+fn insert_returns_stored_event() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let date = Utc.with_ymd_and_hms(2030, 3, 20, 9, 0, 0).unwrap();
+
+    let event = events
+        .insert(NewEvent {
+            message: "Bring the card".to_string(),
+            recurrence: RecurrencePattern::Weekly,
+            tags: vec!["health".to_string()],
+            ..NewEvent::new("Dentist", date)
+        })
+        .unwrap();
+
+    assert!(event.id > 0);
+    assert!(!event.uuid.is_empty());
+    assert_eq!(event.name, "Dentist");
+    assert_eq!(event.date, date);
+    assert_eq!(event.recurrence_pattern, RecurrencePattern::Weekly);
+    assert_eq!(event.calendar, db::DEFAULT_CALENDAR);
+    assert_eq!(event.tags, ["health"]);
+}
+
+#[test]
+fn soft_deleted_events_are_only_found_when_asked_for() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let id = events.insert(NewEvent::new("Old", Utc::now())).unwrap().id;
+
+    events.soft_delete(id).unwrap();
+
+    assert!(matches!(events.get(id), Err(NotifyMeError::NotFound(_))));
+    assert!(events
+        .get_including_deleted(id)
+        .unwrap()
+        .deleted_at
+        .is_some());
+    assert!(events.list(&EventFilter::default()).unwrap().0.is_empty());
+
+    let all = EventFilter {
+        include_deleted: true,
+        ..EventFilter::default()
+    };
+    assert_eq!(events.list(&all).unwrap().0.len(), 1);
+}
+
+#[test]
+fn list_filters_by_calendar_tag_and_range() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let now = Utc::now();
+    let work = events
+        .insert(NewEvent {
+            calendar: Some("work".to_string()),
+            tags: vec!["Meeting".to_string()],
+            ..NewEvent::new("Standup", now + Duration::hours(1))
+        })
+        .unwrap()
+        .id;
+    let home = events
+        .insert(NewEvent::new("Laundry", now + Duration::days(3)))
+        .unwrap()
+        .id;
+
+    let ids = |filter: EventFilter| {
+        let (found, _) = events.list(&filter).unwrap();
+        found.iter().map(|event| event.id).collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids(EventFilter::default()), [work, home]);
+    assert_eq!(
+        ids(EventFilter {
+            calendar: Some("work".to_string()),
+            ..EventFilter::default()
+        }),
+        [work]
+    );
+    assert_eq!(
+        ids(EventFilter {
+            tags: vec!["meeting".to_string()],
+            ..EventFilter::default()
+        }),
+        [work]
+    );
+    assert_eq!(
+        ids(EventFilter {
+            range: (now + Duration::days(1), now + Duration::days(7)),
+            ..EventFilter::default()
+        }),
+        [home]
+    );
+    assert_eq!(
+        ids(EventFilter {
+            order_by: "date DESC".to_string(),
+            ..EventFilter::default()
+        }),
+        [home, work]
+    );
+}
+
+#[test]
+fn count_applies_the_same_filter_as_list() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let now = Utc::now();
+    events
+        .insert(NewEvent {
+            recurrence: RecurrencePattern::Weekly,
+            tags: vec!["work".to_string()],
+            ..NewEvent::new("Standup", now + Duration::hours(1))
+        })
+        .unwrap();
+    events
+        .insert(NewEvent {
+            recurrence: RecurrencePattern::Weekly,
+            ..NewEvent::new("Gym", now + Duration::days(3))
+        })
+        .unwrap();
+    let deleted = events
+        .insert(NewEvent::new("Old", now + Duration::hours(2)))
+        .unwrap();
+    events.soft_delete(deleted.id).unwrap();
+
+    let counted = |filter: EventFilter| {
+        let count = events.count(&filter).unwrap();
+        assert_eq!(count, events.list(&filter).unwrap().0.len());
+        count
+    };
+
+    assert_eq!(counted(EventFilter::default()), 2);
+    assert_eq!(
+        counted(EventFilter {
+            recurrence: Some(RecurrencePattern::Weekly),
+            ..EventFilter::default()
+        }),
+        2
+    );
+    assert_eq!(
+        counted(EventFilter {
+            recurrence: Some(RecurrencePattern::Once),
+            ..EventFilter::default()
+        }),
+        0
+    );
+    assert_eq!(
+        counted(EventFilter {
+            recurrence: Some(RecurrencePattern::Weekly),
+            tags: vec!["work".to_string()],
+            ..EventFilter::default()
+        }),
+        1
+    );
+    assert_eq!(
+        counted(EventFilter {
+            range: (now, now + Duration::days(1)),
+            include_deleted: true,
+            ..EventFilter::default()
+        }),
+        2
+    );
+}
+
+#[test]
+fn find_on_day_uses_local_day() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let day = Local::now().date_naive() + Duration::days(2);
+    let at = |hour| {
+        Local
+            .from_local_datetime(&day.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let early = events.insert(NewEvent::new("Early", at(0))).unwrap().id;
+    let late = events.insert(NewEvent::new("Late", at(23))).unwrap().id;
+    events
+        .insert(NewEvent::new("Next day", at(23) + Duration::hours(1)))
+        .unwrap();
+
+    let (found, _) = events.find_on_day(day, &EventFilter::default()).unwrap();
+    assert_eq!(
+        found.iter().map(|event| event.id).collect::<Vec<_>>(),
+        [early, late]
+    );
+}
+
+#[test]
+fn find_due_covers_unnotified_past_and_lead_time_ahead() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let lead = Duration::minutes(10);
+
+    let mut expected = Vec::new();
+    for offset in [-600, -11, -10, 0, 10, 11] {
+        let id = common::insert_at(&conn, "Event", offset);
+        if offset <= 10 {
+            expected.push(id);
+        }
+    }
+    // Notified since its date, so owed nothing more.
+    let notified = common::insert_at(&conn, "Notified", -5);
+    events
+        .mark_notified(notified, common::at_offset(-5))
+        .unwrap();
+    // Notified before its date: the advance warning, still owed the rest.
+    let warned = common::insert_at(&conn, "Warned", -3);
+    events.mark_notified(warned, common::at_offset(-8)).unwrap();
+    expected.insert(3, warned);
+
+    let (due, _) = events.find_due(common::now(), lead).unwrap();
+    assert_eq!(
+        due.iter().map(|event| event.id).collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn update_date_and_mark_notified() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let id = events
+        .insert(NewEvent::new("Event", Utc::now()))
+        .unwrap()
+        .id;
+    let date = Utc.with_ymd_and_hms(2031, 1, 1, 8, 0, 0).unwrap();
+    let notified = Utc.with_ymd_and_hms(2030, 12, 31, 8, 0, 0).unwrap();
+
+    events.update_date(id, &date).unwrap();
+    events.mark_notified(id, notified).unwrap();
+
+    let event = events.get(id).unwrap();
+    assert_eq!(event.date, date);
+    assert_eq!(event.last_notified_at, Some(notified));
+}
+
+#[test]
+fn failed_transaction_rolls_back() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let id = events
+        .insert(NewEvent::new("Event", Utc::now()))
+        .unwrap()
+        .id;
+
+    let result: Result<(), NotifyMeError> = events.transaction(|events| {
+        events.soft_delete(id)?;
+        Err("stop".into())
+    });
+
+    assert!(result.is_err());
+    assert!(events.get(id).is_ok());
+}
+
+#[test]
+fn writes_are_timestamped_by_the_clock() {
+    let conn = open_db();
+    let clock = common::clock();
+    let events = EventRepository::new(&conn).with_clock(&clock);
+
+    let id = events
+        .insert(NewEvent::new("Event", Utc::now()))
+        .unwrap()
+        .id;
+    assert_eq!(events.get(id).unwrap().created_at, clock.now_utc());
+
+    clock.advance(Duration::hours(1));
+    events.soft_delete(id).unwrap();
+    let event = events.get_including_deleted(id).unwrap();
+    assert_eq!(event.deleted_at, Some(clock.now_utc()));
+    assert_eq!(event.updated_at, clock.now_utc());
+}
+
+#[test]
+fn aggregate_counts_leave_the_trash_out() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    // 2030-03-18 is a Monday.
+    let monday = Local.with_ymd_and_hms(2030, 3, 18, 9, 0, 0).unwrap();
+    let wednesday = monday + Duration::days(2);
+
+    for (name, date, recurrence) in [
+        ("Standup", monday, RecurrencePattern::Daily),
+        ("Gym", wednesday, RecurrencePattern::Weekly),
+        ("Yoga", wednesday, RecurrencePattern::Weekly),
+        ("Dentist", monday, RecurrencePattern::Once),
+    ] {
+        events
+            .insert(NewEvent {
+                recurrence,
+                ..NewEvent::new(name, date.with_timezone(&Utc))
+            })
+            .unwrap();
+    }
+    let trashed = events
+        .insert(NewEvent::new("Old", wednesday.with_timezone(&Utc)))
+        .unwrap();
+    events.soft_delete(trashed.id).unwrap();
+
+    assert_eq!(events.count_deleted().unwrap(), 1);
+    let by_recurrence = events.count_by_recurrence().unwrap();
+    assert_eq!(by_recurrence.len(), 3);
+    assert_eq!(by_recurrence["weekly"], 2);
+    assert_eq!(by_recurrence["daily"], 1);
+    assert_eq!(by_recurrence["once"], 1);
+    assert_eq!(events.count_by_weekday().unwrap(), [2, 0, 2, 0, 0, 0, 0]);
+
+    let start = monday.with_timezone(&Utc);
+    assert_eq!(
+        events
+            .count_between(start, start + Duration::days(1))
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        events
+            .count_between(start, start + Duration::days(3))
+            .unwrap(),
+        4
+    );
+}
+
+#[test]
+fn due_soon_and_day_queries_search_the_date_index() {
+    let conn = open_db();
+    let events = EventRepository::new(&conn);
+    let start = Utc.with_ymd_and_hms(2030, 3, 20, 9, 0, 0).unwrap();
+    events
+        .transaction(|events| {
+            for i in 0..10_000 {
+                let date = start - Duration::hours(i);
+                events.insert(NewEvent::new(format!("Event {}", i), date))?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    conn.execute_batch("ANALYZE").unwrap();
+
+    // As `find_due_soon` and `find_on_day` narrow it.
+    let soon = EventFilter {
+        range: (start, start + Duration::minutes(11)),
+        ..EventFilter::default()
+    };
+    let today = EventFilter {
+        range: (start - Duration::hours(9), start + Duration::hours(15)),
+        ..EventFilter::default()
+    };
+    for filter in [soon, today] {
+        let plan = events.query_plan(&filter).unwrap();
+        assert!(
+            plan.iter()
+                .any(|step| step.starts_with("SEARCH events USING INDEX idx_events_")),
+            "{:?}",
+            plan
+        );
+        assert!(!plan.iter().any(|step| step == "SCAN events"), "{:?}", plan);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fs, path::Path};
+
+use common::TempDb;
+use notify_me::{backup, db, repository::NewEvent};
+use rusqlite::Connection;
+
+mod common;
+
+/// Asserts `validate` turns down `path`, naming it, with `reason`.
+fn assert_rejected(path: &Path, reason: &str) {
+    let err = backup::validate(path).unwrap_err();
+    assert!(
+        err.starts_with(&path.display().to_string()),
+        "{} should name {}",
+        err,
+        path.display()
+    );
+    assert!(err.contains(reason), "{} should say {}", err, reason);
+}
+
+#[test]
+fn validate_accepts_notify_me_databases() {
+    let live = TempDb::new();
+
+    backup::validate(&live.path).unwrap();
+}
+
+#[test]
+fn validate_rejects_files_that_are_not_notify_me_databases() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let text = dir.path().join("notes.txt");
+    fs::write(&text, "Dentist on Tuesday, call first\n".repeat(100)).unwrap();
+    assert_rejected(&text, "not a database");
+
+    let other_app = dir.path().join("other.db");
+    Connection::open(&other_app)
+        .unwrap()
+        .execute_batch("CREATE TABLE contacts (name TEXT)")
+        .unwrap();
+    assert_rejected(&other_app, "has no events table");
+
+    let stripped = dir.path().join("stripped.db");
+    Connection::open(&stripped)
+        .unwrap()
+        .execute_batch("CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT, message TEXT)")
+        .unwrap();
+    assert_rejected(&stripped, "missing the 'recurrence_pattern' column");
+}
+
+#[test]
+fn validate_rejects_databases_from_a_newer_build() {
+    let newer = TempDb::new();
+    Connection::open(&newer.path)
+        .unwrap()
+        .pragma_update(None, "user_version", db::schema_version() + 1)
+        .unwrap();
+
+    assert_rejected(
+        &newer.path,
+        &format!(
+            "has schema version {}, newer than the supported {}",
+            db::schema_version() + 1,
+            db::schema_version()
+        ),
+    );
+}
+
+#[test]
+fn restore_leaves_the_live_database_alone_when_the_backup_is_bad() {
+    let live = TempDb::new();
+    common::insert_at(&live.connect(), "Kept", 60);
+    let dir = tempfile::tempdir().unwrap();
+    let bad = dir.path().join("bad.db");
+    fs::write(&bad, "not a database").unwrap();
+
+    assert!(backup::restore(&live.path, &bad).is_err());
+
+    let aside = format!("{}.pre-restore", live.path.display());
+    assert!(!Path::new(&aside).exists());
+    let count: usize = live
+        .connect()
+        .query_row("SELECT count(*) FROM events", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn restore_swaps_in_the_backup_and_keeps_the_old_database() {
+    let live = TempDb::new();
+    common::insert_at(&live.connect(), "Current", 60);
+    let saved = TempDb::new();
+    common::insert(
+        &saved.connect(),
+        NewEvent::new("Saved", common::at_offset(120)),
+    );
+
+    let aside = backup::restore(&live.path, &saved.path).unwrap();
+
+    let name = |conn: Connection| {
+        conn.query_row("SELECT name FROM events", [], |row| row.get::<_, String>(0))
+            .unwrap()
+    };
+    assert_eq!(name(live.connect()), "Saved");
+    assert_eq!(name(db::open(&aside).unwrap()), "Current");
+}
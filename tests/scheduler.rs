@@ -0,0 +1,745 @@
+use std::{
+    sync::{Arc, Barrier, Mutex},
+    time::{Duration as StdDuration, Instant},
+};
+
+use chrono::{DateTime, Days, Duration, Local, Months, TimeZone, Timelike, Utc};
+use common::{at_offset, now};
+use notify_me::{
+    backup::PreOpBackup,
+    client::Client,
+    clock::Clock,
+    db::to_sql_date,
+    error::NotifyMeError,
+    event::{Event, Priority, RecurrencePattern},
+    lock::InstanceLock,
+    repository::{EventRepository, NewEvent},
+    scheduler::{NotificationKind, Notifier, Scheduler, SchedulerBuilder},
+};
+use rusqlite::Connection;
+
+mod common;
+
+/// Records what would have been shown instead of showing it.
+#[derive(Clone, Default)]
+struct RecordingNotifier {
+    sent: Arc<Mutex<Vec<(i32, NotificationKind)>>>,
+}
+
+impl RecordingNotifier {
+    fn sent(&self) -> Vec<(i32, NotificationKind)> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl Notifier for RecordingNotifier {
+    fn notify(&self, event: &Event, kind: NotificationKind) -> Result<(), NotifyMeError> {
+        self.sent.lock().unwrap().push((event.id, kind));
+        Ok(())
+    }
+}
+
+fn open_db() -> Arc<Mutex<Connection>> {
+    Arc::new(Mutex::new(common::open_db()))
+}
+
+fn insert(conn: &Mutex<Connection>, event: NewEvent) -> i32 {
+    common::insert(&conn.lock().unwrap(), event)
+}
+
+fn builder(conn: &Arc<Mutex<Connection>>, notifier: &RecordingNotifier) -> SchedulerBuilder {
+    Scheduler::builder()
+        .connection(Arc::clone(conn))
+        .notifier(Box::new(notifier.clone()))
+        .interval_secs(60)
+        .advance_minutes(10)
+}
+
+fn scheduler(conn: &Arc<Mutex<Connection>>, notifier: &RecordingNotifier) -> Scheduler {
+    builder(conn, notifier).build().unwrap()
+}
+
+#[test]
+fn notifies_events_owed_a_notification() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let ids = [-11, -5, 0, 5, 10, 11].map(|offset| {
+        insert(
+            &conn,
+            NewEvent::new(format!("{:+}", offset), at_offset(offset)),
+        )
+    });
+
+    scheduler(&conn, &notifier).check_and_notify(now()).unwrap();
+
+    // However long ago, an event never notified is still owed.
+    assert_eq!(
+        notifier.sent(),
+        [
+            (ids[0], NotificationKind::Due),
+            (ids[1], NotificationKind::Due),
+            (ids[2], NotificationKind::Due),
+            (ids[3], NotificationKind::Upcoming),
+            (ids[4], NotificationKind::Upcoming),
+        ]
+    );
+}
+
+#[test]
+fn notifies_each_event_once() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let id = insert(&conn, NewEvent::new("Event", at_offset(5)));
+    let scheduler = scheduler(&conn, &notifier);
+
+    scheduler.check_and_notify(now()).unwrap();
+    scheduler
+        .check_and_notify(now() + Duration::minutes(1))
+        .unwrap();
+    assert_eq!(notifier.sent(), [(id, NotificationKind::Upcoming)]);
+
+    scheduler
+        .check_and_notify(now() + Duration::minutes(5))
+        .unwrap();
+    assert_eq!(
+        notifier.sent(),
+        [
+            (id, NotificationKind::Upcoming),
+            (id, NotificationKind::Due)
+        ]
+    );
+}
+
+#[test]
+fn notifies_at_the_lead_time_and_the_exact_minute() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let id = insert(&conn, NewEvent::new("Event", at_offset(10)));
+    let scheduler = scheduler(&conn, &notifier);
+
+    scheduler
+        .check_and_notify(at_offset(-1) + Duration::seconds(59))
+        .unwrap();
+    assert!(notifier.sent().is_empty());
+
+    scheduler.check_and_notify(at_offset(0)).unwrap();
+    scheduler
+        .check_and_notify(at_offset(9) + Duration::seconds(59))
+        .unwrap();
+    assert_eq!(notifier.sent(), [(id, NotificationKind::Upcoming)]);
+
+    scheduler.check_and_notify(at_offset(10)).unwrap();
+    assert_eq!(
+        notifier.sent(),
+        [
+            (id, NotificationKind::Upcoming),
+            (id, NotificationKind::Due)
+        ]
+    );
+}
+
+#[test]
+fn advances_each_recurrence_pattern_once_due() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let start = at_offset(5).with_timezone(&Local).naive_local();
+    let local = |date| Local.from_local_datetime(&date).unwrap();
+    let cases = [
+        (RecurrencePattern::Once, local(start)),
+        (RecurrencePattern::Daily, local(start + Days::new(1))),
+        (RecurrencePattern::Weekly, local(start + Days::new(7))),
+        (
+            RecurrencePattern::EveryNWeeks(3),
+            local(start + Days::new(21)),
+        ),
+        (RecurrencePattern::Monthly, local(start + Months::new(1))),
+    ];
+    let ids = cases.map(|(recurrence, _)| {
+        insert(
+            &conn,
+            NewEvent {
+                recurrence,
+                ..NewEvent::new(String::from(recurrence), at_offset(5))
+            },
+        )
+    });
+
+    let scheduler = scheduler(&conn, &notifier);
+
+    // The warning leaves every event on the occurrence it warns about.
+    scheduler.check_and_notify(now()).unwrap();
+    {
+        let conn = conn.lock().unwrap();
+        let events = EventRepository::new(&conn);
+        for (id, (recurrence, _)) in ids.into_iter().zip(cases) {
+            let event = events.get(id).unwrap();
+            assert_eq!(event.date, at_offset(5), "{}", recurrence);
+            assert_eq!(event.last_notified_at, Some(now()));
+        }
+    }
+
+    scheduler.check_and_notify(at_offset(5)).unwrap();
+    assert_eq!(
+        notifier.sent(),
+        ids.map(|id| (id, NotificationKind::Upcoming))
+            .into_iter()
+            .chain(ids.map(|id| (id, NotificationKind::Due)))
+            .collect::<Vec<_>>()
+    );
+    let conn = conn.lock().unwrap();
+    let events = EventRepository::new(&conn);
+    for (id, (recurrence, next)) in ids.into_iter().zip(cases) {
+        let event = events.get(id).unwrap();
+        assert_eq!(event.date, next, "{}", recurrence);
+        assert_eq!(event.last_notified_at, Some(at_offset(5)));
+    }
+}
+
+#[test]
+fn sees_events_written_through_another_connection() {
+    let db = common::TempDb::new();
+    let notifier = RecordingNotifier::default();
+    let conn = Arc::new(Mutex::new(db.connect()));
+    let scheduler = scheduler(&conn, &notifier);
+
+    let other = db.connect();
+    let id = common::insert_at(&other, "Event", 5);
+    scheduler.check_and_notify(now()).unwrap();
+
+    assert_eq!(notifier.sent(), [(id, NotificationKind::Upcoming)]);
+    let event = EventRepository::new(&other).get(id).unwrap();
+    assert_eq!(event.last_notified_at, Some(now()));
+}
+
+#[test]
+fn schedulers_on_two_connections_claim_each_event_once() {
+    let db = common::TempDb::new();
+    let writer = db.connect();
+    let ids: Vec<_> = (0..200)
+        .map(|i| common::insert_at(&writer, &format!("Event {}", i), 0))
+        .collect();
+    let notifiers = [RecordingNotifier::default(), RecordingNotifier::default()];
+    let schedulers = notifiers.each_ref().map(|notifier| {
+        let conn = Arc::new(Mutex::new(db.connect()));
+        builder(&conn, notifier)
+            .max_notifications_per_tick(200)
+            .build()
+            .unwrap()
+    });
+    let start = Barrier::new(3);
+
+    std::thread::scope(|scope| {
+        for scheduler in &schedulers {
+            let start = &start;
+            scope.spawn(move || {
+                start.wait();
+                for _ in 0..10 {
+                    scheduler.check_and_notify(now()).unwrap();
+                }
+            });
+        }
+
+        // Writes from a third connection meanwhile, as the client would.
+        start.wait();
+        for i in 0..50 {
+            common::insert_at(&writer, &format!("Later {}", i), 60);
+        }
+    });
+
+    let mut sent: Vec<_> = notifiers.iter().flat_map(RecordingNotifier::sent).collect();
+    sent.sort_by_key(|&(id, _)| id);
+    assert_eq!(
+        sent,
+        ids.iter()
+            .map(|&id| (id, NotificationKind::Due))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn null_recurrence_rows_show_today_and_fire() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    // The first schema, as another tool might have written it without the
+    // NOT NULL.
+    conn.execute_batch(
+        "CREATE TABLE events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            message TEXT NOT NULL,
+            recurrence_pattern TEXT,
+            date TEXT NOT NULL,
+            deleted_at TEXT DEFAULT NULL
+        );",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO events (name, message, recurrence_pattern, date) \
+         VALUES ('Legacy', '', NULL, ?1)",
+        [at_offset(0).to_rfc3339()],
+    )
+    .unwrap();
+    notify_me::db::migrate(&mut conn).unwrap();
+
+    let clock = common::clock();
+    let today = Client::new(&conn)
+        .with_clock(&clock)
+        .fetch_events_grouped_by_proximity()
+        .unwrap()
+        .today;
+    assert_eq!(today.0.len(), 1);
+    let legacy = &today.0[0];
+    assert_eq!(legacy.recurrence_pattern, RecurrencePattern::Once);
+
+    let conn = Arc::new(Mutex::new(conn));
+    let notifier = RecordingNotifier::default();
+    scheduler(&conn, &notifier).check_and_notify(now()).unwrap();
+    assert_eq!(notifier.sent(), [(legacy.id, NotificationKind::Due)]);
+}
+
+#[test]
+fn skips_paused_events_and_muted_calendars() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let paused = insert(&conn, NewEvent::new("Paused", at_offset(5)));
+    insert(
+        &conn,
+        NewEvent {
+            calendar: Some("muted".to_string()),
+            ..NewEvent::new("Muted", at_offset(5))
+        },
+    );
+    {
+        let conn = conn.lock().unwrap();
+        let client = Client::new(&conn);
+        client.set_paused(paused, true).unwrap();
+        client.set_calendar_muted("muted", true).unwrap();
+    }
+
+    scheduler(&conn, &notifier).check_and_notify(now()).unwrap();
+
+    assert!(notifier.sent().is_empty());
+}
+
+#[test]
+fn defers_notifications_past_the_tick_limit() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let ids: Vec<_> = (0..20)
+        .map(|_| insert(&conn, NewEvent::new("Event", at_offset(0))))
+        .collect();
+    let scheduler = scheduler(&conn, &notifier);
+
+    // Five a tick by default, the rest picked up on the ticks after.
+    for fired in [5, 10, 15, 20, 20] {
+        scheduler.check_and_notify(now()).unwrap();
+        assert_eq!(notifier.sent().len(), fired);
+    }
+    assert_eq!(
+        notifier.sent(),
+        ids.iter()
+            .map(|&id| (id, NotificationKind::Due))
+            .collect::<Vec<_>>()
+    );
+
+    let scheduler = builder(&conn, &notifier)
+        .max_notifications_per_tick(2)
+        .build()
+        .unwrap();
+    for offset in 1..=3 {
+        insert(&conn, NewEvent::new("Event", at_offset(offset)));
+    }
+    scheduler.check_and_notify(now()).unwrap();
+    assert_eq!(notifier.sent().len(), 22);
+    scheduler.check_and_notify(now()).unwrap();
+    assert_eq!(notifier.sent().len(), 23);
+}
+
+#[test]
+fn events_held_back_by_the_limit_fire_after_their_window() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let ids: Vec<_> = (0..8)
+        .map(|_| insert(&conn, NewEvent::new("Event", at_offset(0))))
+        .collect();
+    let scheduler = scheduler(&conn, &notifier);
+
+    scheduler.check_and_notify(now()).unwrap();
+    assert_eq!(notifier.sent().len(), 5);
+
+    // Long past the advance window by the next tick.
+    scheduler.check_and_notify(at_offset(30)).unwrap();
+    assert_eq!(
+        notifier.sent(),
+        ids.iter()
+            .map(|&id| (id, NotificationKind::Due))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn quiet_hours_silence_but_consume_notifications() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let id = insert(&conn, NewEvent::new("Event", at_offset(5)));
+    let hour = now().with_timezone(&Local).hour() as u8;
+    let scheduler = builder(&conn, &notifier)
+        .quiet_hours(hour, (hour + 1) % 24)
+        .build()
+        .unwrap();
+
+    scheduler.check_and_notify(now()).unwrap();
+
+    assert!(notifier.sent().is_empty());
+    let conn = conn.lock().unwrap();
+    let event = EventRepository::new(&conn).get(id).unwrap();
+    assert_eq!(event.last_notified_at, Some(now()));
+}
+
+#[test]
+fn quiet_hours_hold_back_all_but_high_priority_until_they_end() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let local = |day, hour, minute| {
+        Local
+            .with_ymd_and_hms(2030, 3, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let urgent = insert(
+        &conn,
+        NewEvent {
+            priority: Priority::High,
+            ..NewEvent::new("Urgent", local(20, 22, 30))
+        },
+    );
+    let routine = insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Routine", local(20, 22, 30))
+        },
+    );
+    let scheduler = builder(&conn, &notifier)
+        .quiet_hours(22, 7)
+        .build()
+        .unwrap();
+
+    scheduler.check_and_notify(local(20, 22, 30)).unwrap();
+    assert_eq!(notifier.sent(), [(urgent, NotificationKind::Due)]);
+
+    // Long out of the due window, but still held back until 07:00.
+    scheduler.check_and_notify(local(21, 6, 59)).unwrap();
+    assert_eq!(notifier.sent().len(), 1);
+
+    scheduler.check_and_notify(local(21, 7, 0)).unwrap();
+    assert_eq!(
+        notifier.sent(),
+        [
+            (urgent, NotificationKind::Due),
+            (routine, NotificationKind::Due)
+        ]
+    );
+    {
+        let conn = conn.lock().unwrap();
+        let event = EventRepository::new(&conn).get(routine).unwrap();
+        assert_eq!(event.last_notified_at, Some(local(21, 7, 0)));
+        assert_eq!(event.date, local(21, 22, 30));
+    }
+
+    scheduler.check_and_notify(local(21, 7, 1)).unwrap();
+    assert_eq!(notifier.sent().len(), 2);
+}
+
+#[test]
+fn quiet_hours_deferrals_leave_the_limit_to_high_priority() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let local = |day, hour, minute| {
+        Local
+            .with_ymd_and_hms(2030, 3, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let routine: Vec<_> = (0..5)
+        .map(|_| insert(&conn, NewEvent::new("Routine", local(20, 22, 30))))
+        .collect();
+    let urgent = insert(
+        &conn,
+        NewEvent {
+            priority: Priority::High,
+            ..NewEvent::new("Urgent", local(20, 22, 30))
+        },
+    );
+    let scheduler = builder(&conn, &notifier)
+        .quiet_hours(22, 7)
+        .max_notifications_per_tick(2)
+        .build()
+        .unwrap();
+    let deferred_at = |at| {
+        conn.lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM events WHERE last_deferred_at = ?1",
+                [to_sql_date(&at)],
+                |row| row.get::<_, usize>(0),
+            )
+            .unwrap()
+    };
+
+    scheduler.check_and_notify(local(20, 22, 30)).unwrap();
+    assert_eq!(notifier.sent(), [(urgent, NotificationKind::Due)]);
+    assert_eq!(deferred_at(local(20, 22, 30)), 5);
+
+    // Deferred once, not again on every tick until quiet hours end.
+    scheduler.check_and_notify(local(20, 22, 31)).unwrap();
+    assert_eq!(deferred_at(local(20, 22, 30)), 5);
+    assert_eq!(notifier.sent().len(), 1);
+
+    for (tick, sent) in [(0, 3), (1, 5), (2, 6)] {
+        scheduler.check_and_notify(local(21, 7, tick)).unwrap();
+        assert_eq!(notifier.sent().len(), sent);
+    }
+    let released: Vec<_> = notifier.sent()[1..].iter().map(|(id, _)| *id).collect();
+    assert_eq!(released, routine);
+}
+
+#[test]
+fn dry_run_leaves_the_database_alone() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let id = insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Daily", at_offset(5))
+        },
+    );
+    let scheduler = builder(&conn, &notifier).dry_run(true).build().unwrap();
+
+    scheduler.check_and_notify(now()).unwrap();
+
+    assert!(notifier.sent().is_empty());
+    let conn = conn.lock().unwrap();
+    let event = EventRepository::new(&conn).get(id).unwrap();
+    assert_eq!(event.date, at_offset(5));
+    assert_eq!(event.last_notified_at, None);
+}
+
+#[test]
+fn builder_rejects_invalid_configuration() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+
+    assert!(matches!(
+        Scheduler::builder().build(),
+        Err(NotifyMeError::Config(_))
+    ));
+    assert!(builder(&conn, &notifier).interval_secs(0).build().is_err());
+    assert!(builder(&conn, &notifier)
+        .max_notifications_per_tick(0)
+        .build()
+        .is_err());
+    assert!(builder(&conn, &notifier)
+        .quiet_hours(22, 24)
+        .build()
+        .is_err());
+    assert!(builder(&conn, &notifier).quiet_hours(7, 7).build().is_err());
+    assert!(builder(&conn, &notifier).quiet_hours(22, 7).build().is_ok());
+}
+
+#[test]
+fn catch_up_reads_the_clock() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let id = insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Daily", at_offset(30) - Duration::days(3))
+        },
+    );
+    let scheduler = builder(&conn, &notifier)
+        .clock(Arc::new(common::clock()))
+        .build()
+        .unwrap();
+
+    scheduler.catch_up().unwrap();
+
+    let conn = conn.lock().unwrap();
+    let event = EventRepository::new(&conn).get(id).unwrap();
+    assert_eq!(event.date, at_offset(30));
+    assert!(notifier.sent().is_empty());
+}
+
+#[test]
+fn missed_events_are_those_never_notified_since_the_cutoff() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let scheduler = builder(&conn, &notifier)
+        .clock(Arc::new(common::clock()))
+        .build()
+        .unwrap();
+    insert(&conn, NewEvent::new("Before", at_offset(-121)));
+    let missed = insert(&conn, NewEvent::new("Missed", at_offset(-90)));
+    let notified = insert(&conn, NewEvent::new("Notified", at_offset(-60)));
+    let deleted = insert(&conn, NewEvent::new("Deleted", at_offset(-50)));
+    let paused = insert(&conn, NewEvent::new("Paused", at_offset(-40)));
+    let daily = insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Daily", at_offset(-30))
+        },
+    );
+    insert(&conn, NewEvent::new("Upcoming", at_offset(5)));
+    {
+        let conn = conn.lock().unwrap();
+        let events = EventRepository::new(&conn);
+        events.mark_notified(notified, at_offset(-60)).unwrap();
+        // Notified yesterday, and never moved on from today's occurrence.
+        events
+            .mark_notified(daily, at_offset(-30) - Duration::days(1))
+            .unwrap();
+        events.soft_delete(deleted).unwrap();
+        conn.execute("UPDATE events SET paused = 1 WHERE id = ?1", [paused])
+            .unwrap();
+    }
+
+    let ids: Vec<_> = scheduler
+        .missed_events_since(at_offset(-120))
+        .unwrap()
+        .iter()
+        .map(|event| event.id)
+        .collect();
+    assert_eq!(ids, [missed, daily]);
+}
+
+/// Takes as long as a notification daemon stuck on D-Bus might.
+struct SlowNotifier;
+
+impl Notifier for SlowNotifier {
+    fn notify(&self, _event: &Event, _kind: NotificationKind) -> Result<(), NotifyMeError> {
+        std::thread::sleep(StdDuration::from_secs(2));
+        Ok(())
+    }
+}
+
+/// Notes when each tick reads the time.
+struct TickClock {
+    ticks: Mutex<Vec<Instant>>,
+}
+
+impl Clock for TickClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        self.ticks.lock().unwrap().push(Instant::now());
+        now()
+    }
+}
+
+#[tokio::test]
+async fn slow_notifications_do_not_delay_ticks() {
+    let conn = open_db();
+    insert(&conn, NewEvent::new("Event", at_offset(5)));
+    let clock = Arc::new(TickClock {
+        ticks: Mutex::new(Vec::new()),
+    });
+    let scheduler = Scheduler::builder()
+        .connection(conn)
+        .notifier(Box::new(SlowNotifier))
+        .clock(Arc::clone(&clock) as Arc<dyn Clock>)
+        .interval_secs(1)
+        .build()
+        .unwrap();
+
+    let run = Arc::new(scheduler).start();
+    assert!(tokio::time::timeout(StdDuration::from_millis(2500), run)
+        .await
+        .is_err());
+
+    // Catching up, then ticks at 0s, 1s and 2s.
+    let ticks = clock.ticks.lock().unwrap();
+    assert_eq!(ticks.len(), 4, "{:?}", *ticks);
+    for pair in ticks[1..].windows(2) {
+        assert!(pair[1] - pair[0] < StdDuration::from_millis(1500));
+    }
+}
+
+#[tokio::test]
+async fn expiring_the_trash_purges_old_events_after_a_backup() {
+    let conn = open_db();
+    let old = insert(&conn, NewEvent::new("Old", at_offset(60)));
+    let recent = insert(&conn, NewEvent::new("Recent", at_offset(60)));
+    {
+        let conn = conn.lock().unwrap();
+        let clock = common::clock();
+        let events = EventRepository::new(&conn).with_clock(&clock);
+        clock.set(now() - Duration::days(40));
+        events.soft_delete(old).unwrap();
+        clock.set(now() - Duration::days(10));
+        events.soft_delete(recent).unwrap();
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let scheduler = builder(&conn, &RecordingNotifier::default())
+        .clock(Arc::new(common::clock()))
+        .trash_retention_days(Some(30))
+        .pre_op_backup(Some(PreOpBackup {
+            dir: dir.path().to_path_buf(),
+            retention: 10,
+        }))
+        .build()
+        .unwrap();
+
+    let run = Arc::new(scheduler).start();
+    assert!(tokio::time::timeout(StdDuration::from_millis(500), run)
+        .await
+        .is_err());
+
+    let conn = conn.lock().unwrap();
+    let trash = Client::new(&conn).fetch_deleted_events(10).unwrap();
+    assert_eq!(trash.0.iter().map(|e| e.id).collect::<Vec<_>>(), [recent]);
+    let snapshots = std::fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(snapshots, 1);
+}
+
+#[test]
+fn check_now_catches_up_then_checks_and_counts() {
+    let conn = open_db();
+    let notifier = RecordingNotifier::default();
+    let missed = insert(
+        &conn,
+        NewEvent {
+            recurrence: RecurrencePattern::Daily,
+            ..NewEvent::new("Daily", at_offset(30) - Duration::days(3))
+        },
+    );
+    let due = insert(&conn, NewEvent::new("Due", at_offset(5)));
+    let scheduler = builder(&conn, &notifier)
+        .clock(Arc::new(common::clock()))
+        .skip_catchup_notifications(false)
+        .build()
+        .unwrap();
+
+    assert_eq!(scheduler.check_now().unwrap(), 2);
+    assert_eq!(
+        notifier.sent(),
+        [
+            (missed, NotificationKind::Due),
+            (due, NotificationKind::Upcoming)
+        ]
+    );
+
+    assert_eq!(scheduler.check_now().unwrap(), 0);
+}
+
+#[test]
+fn instance_lock_tells_whether_a_scheduler_runs() {
+    let db = common::TempDb::new();
+    assert!(!InstanceLock::is_held(&db.path));
+
+    let lock = InstanceLock::acquire(&db.path).unwrap();
+    assert!(InstanceLock::is_held(&db.path));
+    assert!(InstanceLock::acquire(&db.path).is_err());
+
+    drop(lock);
+    assert!(!InstanceLock::is_held(&db.path));
+}